@@ -0,0 +1,458 @@
+use std::fs;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    texture::{MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d},
+    Display,
+};
+
+use crate::{camera::Camera, post::PostStack, scene_file::BookmarkFile};
+
+pub const DEFAULT_BOOKMARKS_PATH: &str = "bookmarks.toml";
+const FRAMES_DIR: &str = "frames";
+
+#[derive(Clone, Debug)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub fovy: f32,
+}
+
+impl CameraBookmark {
+    pub fn from_camera(name: &str, camera: &Camera) -> Self {
+        CameraBookmark {
+            name: String::from(name),
+            position: camera.position(),
+            direction: camera.direction().normalize(),
+            up: camera.up(),
+            fovy: camera.fovy(),
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_pose(self.position, self.direction, self.up);
+        camera.set_fovy(self.fovy);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PathKey {
+    pub bookmark: String,
+    // seconds from the start of the path
+    pub time: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    pub name: String,
+    // kept sorted by time
+    pub keys: Vec<PathKey>,
+}
+
+impl CameraPath {
+    pub fn new(name: &str) -> Self {
+        CameraPath {
+            name: String::from(name),
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn add_key(&mut self, bookmark: &str, time: f32) {
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(
+            index,
+            PathKey {
+                bookmark: String::from(bookmark),
+                time,
+            },
+        );
+    }
+
+    // time of the last key that still names a bookmark, like `sample` skips the others
+    pub fn duration(&self, bookmarks: &[CameraBookmark]) -> f32 {
+        self.keys
+            .iter()
+            .rev()
+            .find(|key| {
+                bookmarks
+                    .iter()
+                    .any(|bookmark| bookmark.name == key.bookmark)
+            })
+            .map_or(0.0, |key| key.time)
+    }
+
+    // catmull-rom spline through the key bookmarks, keys naming missing bookmarks are skipped
+    pub fn sample(&self, bookmarks: &[CameraBookmark], time: f32) -> Option<CameraBookmark> {
+        let keys = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                bookmarks
+                    .iter()
+                    .find(|bookmark| bookmark.name == key.bookmark)
+                    .map(|bookmark| (key.time, bookmark))
+            })
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return None;
+        }
+
+        let last = keys.len() - 1;
+        let segment = keys
+            .iter()
+            .rposition(|(key_time, _)| *key_time <= time)
+            .unwrap_or(0)
+            .min(last.saturating_sub(1));
+        let (t1, p1) = keys[segment];
+        let (t2, p2) = keys[(segment + 1).min(last)];
+        let p0 = keys[segment.saturating_sub(1)].1;
+        let p3 = keys[(segment + 2).min(last)].1;
+        let u = if t2 > t1 {
+            ((time - t1) / (t2 - t1)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let spline = |a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, d: Vector3<f32>| {
+            catmull_rom(a, b, c, d, u)
+        };
+        Some(CameraBookmark {
+            name: self.name.clone(),
+            position: Point3::from_vec(spline(
+                p0.position.to_vec(),
+                p1.position.to_vec(),
+                p2.position.to_vec(),
+                p3.position.to_vec(),
+            )),
+            direction: spline(p0.direction, p1.direction, p2.direction, p3.direction).normalize(),
+            up: spline(p0.up, p1.up, p2.up, p3.up).normalize(),
+            fovy: catmull_rom(
+                Vector3::new(p0.fovy, 0.0, 0.0),
+                Vector3::new(p1.fovy, 0.0, 0.0),
+                Vector3::new(p2.fovy, 0.0, 0.0),
+                Vector3::new(p3.fovy, 0.0, 0.0),
+                u,
+            )
+            .x,
+        })
+    }
+}
+
+fn catmull_rom(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    u: f32,
+) -> Vector3<f32> {
+    let (u2, u3) = (u * u, u * u * u);
+    (p1 * 2.0
+        + (p2 - p0) * u
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * u2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * u3)
+        * 0.5
+}
+
+pub struct Bookmarks {
+    pub bookmarks: Vec<CameraBookmark>,
+    pub paths: Vec<CameraPath>,
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Bookmarks {
+            bookmarks: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    // a toml file with the same `[[bookmarks]]` and `[[paths]]` sections as a scene file
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(BookmarkFile::load(file_path)?.to_bookmarks())
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        BookmarkFile::capture(self).save(file_path)
+    }
+}
+
+// writes the post-processed frame, without the ui, to numbered png files
+struct FrameRecorder {
+    output_dir: String,
+    next_frame: u32,
+    texture: Option<SrgbTexture2d>,
+}
+
+impl FrameRecorder {
+    fn capture(
+        &mut self,
+        display: &Display,
+        post_stack: &PostStack,
+        (width, height): (u32, u32),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.texture.as_ref().map(|texture| texture.dimensions()) != Some((width, height)) {
+            self.texture = Some(SrgbTexture2d::empty_with_format(
+                display,
+                SrgbFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )?);
+        }
+        let texture = self.texture.as_ref().unwrap();
+
+        let mut target = SimpleFrameBuffer::new(display, texture)?;
+        post_stack.apply(display, &mut target)?;
+
+        let pixels: RawImage2d<u8> = texture.read();
+        let image = image::RgbaImage::from_raw(width, height, pixels.data.into_owned())
+            .ok_or("frame readback has the wrong size")?;
+        // gl rows start at the bottom
+        let image = image::imageops::flip_vertical(&image);
+
+        fs::create_dir_all(&self.output_dir)?;
+        image.save(format!(
+            "{}/frame_{:05}.png",
+            self.output_dir, self.next_frame
+        ))?;
+        self.next_frame += 1;
+        Ok(())
+    }
+}
+
+pub struct PathPlayer {
+    pub path: usize,
+    pub time: f32,
+    pub playing: bool,
+    pub looping: bool,
+    // while recording, playback advances a fixed 1 / fps per frame and every frame is saved
+    pub record: bool,
+    pub fps: f32,
+}
+
+pub struct BookmarkEditor {
+    pub bookmarks: Bookmarks,
+    pub player: PathPlayer,
+    file_path: String,
+    new_name: String,
+    key_bookmark: usize,
+    key_time: f32,
+    recorder: FrameRecorder,
+}
+
+impl BookmarkEditor {
+    pub fn new() -> Self {
+        let bookmarks = if std::path::Path::new(DEFAULT_BOOKMARKS_PATH).exists() {
+            Bookmarks::load(DEFAULT_BOOKMARKS_PATH).unwrap_or_else(|e| {
+                eprintln!("failed to load {}: {}", DEFAULT_BOOKMARKS_PATH, e);
+                Bookmarks::new()
+            })
+        } else {
+            Bookmarks::new()
+        };
+
+        BookmarkEditor {
+            bookmarks,
+            player: PathPlayer {
+                path: 0,
+                time: 0.0,
+                playing: false,
+                looping: false,
+                record: false,
+                fps: 30.0,
+            },
+            file_path: String::from(DEFAULT_BOOKMARKS_PATH),
+            new_name: String::new(),
+            key_bookmark: 0,
+            key_time: 0.0,
+            recorder: FrameRecorder {
+                output_dir: String::from(FRAMES_DIR),
+                next_frame: 0,
+                texture: None,
+            },
+        }
+    }
+
+    pub fn play(&mut self, path: usize) {
+        if let Some(camera_path) = self.bookmarks.paths.get(path) {
+            self.player.path = path;
+            self.player.time = 0.0;
+            self.player.playing = true;
+            self.recorder.output_dir = format!("{}/{}", FRAMES_DIR, camera_path.name);
+            self.recorder.next_frame = 0;
+        }
+    }
+
+    // moves the camera along the playing path; returns true if it did, which includes the frame
+    // showing the end of the path after which playback stops
+    pub fn update(&mut self, delta_time: f32, camera: &mut Camera) -> bool {
+        if !self.player.playing {
+            return false;
+        }
+        let path = match self.bookmarks.paths.get(self.player.path) {
+            Some(path) => path,
+            None => {
+                self.player.playing = false;
+                return false;
+            }
+        };
+
+        if let Some(pose) = path.sample(&self.bookmarks.bookmarks, self.player.time) {
+            pose.apply(camera);
+        }
+
+        let duration = path.duration(&self.bookmarks.bookmarks);
+        let looping = self.player.looping && !self.player.record && duration > 0.0;
+        if self.player.time >= duration && !looping {
+            // the final pose is on screen this frame, so it still gets rendered and recorded
+            self.player.playing = false;
+            return true;
+        }
+
+        let step = if self.player.record {
+            1.0 / self.player.fps.max(1.0)
+        } else {
+            delta_time
+        };
+        self.player.time += step;
+        if self.player.time > duration {
+            if looping {
+                self.player.time %= duration;
+            } else {
+                self.player.time = duration;
+            }
+        }
+        true
+    }
+
+    // call after the scene has been rendered into the post stack's scene target, on frames where
+    // `update` returned true
+    pub fn capture_frame(&mut self, display: &Display, post_stack: &PostStack, size: (u32, u32)) {
+        if self.player.record {
+            if let Err(e) = self.recorder.capture(display, post_stack, size) {
+                eprintln!("failed to record frame: {}", e);
+                self.player.record = false;
+            }
+        }
+    }
+
+    // returns true when the camera was moved to a bookmark
+    pub fn build_ui(&mut self, ui: &imgui::Ui, camera: &mut Camera) -> bool {
+        let mut camera_moved = false;
+        imgui::Window::new("Camera Bookmarks").build(ui, || {
+            ui.input_text("file", &mut self.file_path).build();
+            if ui.button("load bookmarks") {
+                match Bookmarks::load(&self.file_path) {
+                    Ok(bookmarks) => self.bookmarks = bookmarks,
+                    Err(e) => eprintln!("failed to load {}: {}", self.file_path, e),
+                }
+            }
+            ui.same_line();
+            if ui.button("save bookmarks") {
+                if let Err(e) = self.bookmarks.save(&self.file_path) {
+                    eprintln!("failed to save {}: {}", self.file_path, e);
+                }
+            }
+
+            ui.separator();
+            ui.input_text("name", &mut self.new_name).build();
+            let name = String::from(self.new_name.trim());
+            if ui.button("add bookmark") && !name.is_empty() {
+                self.bookmarks
+                    .bookmarks
+                    .retain(|bookmark| bookmark.name != name);
+                self.bookmarks
+                    .bookmarks
+                    .push(CameraBookmark::from_camera(&name, camera));
+            }
+            ui.same_line();
+            if ui.button("add path") && !name.is_empty() {
+                self.bookmarks.paths.push(CameraPath::new(&name));
+            }
+
+            // bookmarks and keys both have "x" buttons, so each list gets its own id scope
+            let bookmarks_id = ui.push_id("bookmarks");
+            let mut remove = None;
+            for (i, bookmark) in self.bookmarks.bookmarks.iter().enumerate() {
+                let _id = ui.push_id(i as i32);
+                if ui.button("go") {
+                    bookmark.apply(camera);
+                    camera_moved = true;
+                }
+                ui.same_line();
+                if ui.button("x") {
+                    remove = Some(i);
+                }
+                ui.same_line();
+                ui.text(&bookmark.name);
+            }
+            if let Some(i) = remove {
+                self.bookmarks.bookmarks.remove(i);
+            }
+            bookmarks_id.pop();
+
+            if self.bookmarks.paths.is_empty() {
+                return;
+            }
+            ui.separator();
+            let path_names = self
+                .bookmarks
+                .paths
+                .iter()
+                .map(|path| path.name.as_str())
+                .collect::<Vec<_>>();
+            let mut path_index = self.player.path.min(path_names.len() - 1);
+            ui.combo_simple_string("path", &mut path_index, &path_names);
+            self.player.path = path_index;
+
+            if !self.bookmarks.bookmarks.is_empty() {
+                let bookmark_names = self
+                    .bookmarks
+                    .bookmarks
+                    .iter()
+                    .map(|bookmark| bookmark.name.as_str())
+                    .collect::<Vec<_>>();
+                self.key_bookmark = self.key_bookmark.min(bookmark_names.len() - 1);
+                ui.combo_simple_string("key bookmark", &mut self.key_bookmark, &bookmark_names);
+                imgui::Slider::new("key time", 0.0, 60.0).build(ui, &mut self.key_time);
+                if ui.button("add key") {
+                    let bookmark = bookmark_names[self.key_bookmark].to_owned();
+                    self.bookmarks.paths[path_index].add_key(&bookmark, self.key_time);
+                }
+            }
+
+            let path = &mut self.bookmarks.paths[path_index];
+            let keys_id = ui.push_id("keys");
+            let mut remove = None;
+            for (i, key) in path.keys.iter().enumerate() {
+                let _id = ui.push_id(i as i32);
+                if ui.button("x") {
+                    remove = Some(i);
+                }
+                ui.same_line();
+                ui.text(format!("{:6.2}s  {}", key.time, key.bookmark));
+            }
+            if let Some(i) = remove {
+                path.keys.remove(i);
+            }
+            keys_id.pop();
+
+            ui.checkbox("loop", &mut self.player.looping);
+            ui.same_line();
+            ui.checkbox("record frames", &mut self.player.record);
+            imgui::Slider::new("fps", 1.0, 120.0).build(ui, &mut self.player.fps);
+            if self.player.playing {
+                ui.text(format!("{:.2}s", self.player.time));
+                if ui.button("stop") {
+                    self.player.playing = false;
+                }
+            } else if ui.button("play") {
+                self.play(path_index);
+            }
+        });
+        camera_moved
+    }
+}
@@ -0,0 +1,744 @@
+use cgmath::{ElementWise, InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+const SAH_BINS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// relative cost of one ray/box test against one ray/triangle test
+const TRAVERSAL_COST: f32 = 1.0;
+const TRIANGLE_COST: f32 = 1.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Aabb {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_center(center: Point3<f32>, half_extent: Vector3<f32>) -> Self {
+        Aabb {
+            min: center - half_extent,
+            max: center + half_extent,
+        }
+    }
+
+    fn from_triangle(triangle: &[Point3<f32>; 3]) -> Self {
+        let mut aabb = Aabb::empty();
+        for p in triangle {
+            aabb.grow(*p);
+        }
+        aabb
+    }
+
+    pub fn grow(&mut self, p: Point3<f32>) {
+        self.min = Point3::new(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        aabb.grow(other.min);
+        aabb.grow(other.max);
+        aabb
+    }
+
+    // bounds of the box's corners after `matrix`
+    pub fn transformed(&self, matrix: Matrix4<f32>) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for corner in 0..8 {
+            let pick = |bit: i32, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+            aabb.grow(matrix.transform_point(Point3::new(
+                pick(1, self.min.x, self.max.x),
+                pick(2, self.min.y, self.max.y),
+                pick(4, self.min.z, self.max.z),
+            )));
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min + (self.max - self.min) / 2.0
+    }
+
+    fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.x < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    pub fn distance2(&self, p: Point3<f32>) -> f32 {
+        let dx = (self.min.x - p.x).max(p.x - self.max.x).max(0.0);
+        let dy = (self.min.y - p.y).max(p.y - self.max.y).max(0.0);
+        let dz = (self.min.z - p.z).max(p.z - self.max.z).max(0.0);
+        dx * dx + dy * dy + dz * dz
+    }
+
+    // entry distance of the ray along [0, t_max], `inv_direction` is 1 / direction per axis
+    fn intersect_ray(
+        &self,
+        origin: Point3<f32>,
+        inv_direction: Vector3<f32>,
+        t_max: f32,
+    ) -> Option<f32> {
+        let t1 = (self.min - origin).mul_element_wise(inv_direction);
+        let t2 = (self.max - origin).mul_element_wise(inv_direction);
+        let near =
+            t1.x.min(t2.x)
+                .max(t1.y.min(t2.y))
+                .max(t1.z.min(t2.z))
+                .max(0.0);
+        let far =
+            t1.x.max(t2.x)
+                .min(t1.y.max(t2.y))
+                .min(t1.z.max(t2.z))
+                .min(t_max);
+        (near <= far).then_some(near)
+    }
+}
+
+// a triangle of a `TriangleMesh`, by submesh and triangle index within it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TriangleRef {
+    pub submesh: usize,
+    pub triangle: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+    pub triangle: TriangleRef,
+    // ray parameter, a distance when the direction has unit length
+    pub distance: f32,
+    // weights of the triangle's three corners at the hit point
+    pub barycentric: Vector3<f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NearestPoint {
+    pub triangle: TriangleRef,
+    pub point: Point3<f32>,
+    pub distance: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: Aabb,
+    // leaves: first triangle and triangle count; inner nodes: left child, count 0, the right
+    // child follows the left one
+    first: usize,
+    count: usize,
+}
+
+// bounding volume hierarchy over a mesh's triangles; built once with the surface area heuristic,
+// then refit in place whenever the transform of its triangles changes
+pub struct Bvh {
+    nodes: Vec<Node>,
+    refs: Vec<TriangleRef>,
+    // corners in the space the bvh was built in, in `refs` order
+    local: Vec<[Point3<f32>; 3]>,
+    // corners after the last refit transform, used by every query
+    triangles: Vec<[Point3<f32>; 3]>,
+}
+
+impl Bvh {
+    pub fn new(triangles: Vec<(TriangleRef, [Point3<f32>; 3])>) -> Self {
+        let mut items = triangles
+            .into_iter()
+            .map(|(triangle_ref, corners)| {
+                let bounds = Aabb::from_triangle(&corners);
+                (triangle_ref, corners, bounds, bounds.center())
+            })
+            .collect::<Vec<_>>();
+        let mut nodes = vec![Node {
+            bounds: Aabb::empty(),
+            first: 0,
+            count: items.len(),
+        }];
+        if !items.is_empty() {
+            build_node(&mut nodes, 0, &mut items, 0);
+        }
+
+        let refs = items.iter().map(|item| item.0).collect::<Vec<_>>();
+        let local = items.iter().map(|item| item.1).collect::<Vec<_>>();
+        Bvh {
+            nodes,
+            refs,
+            triangles: local.clone(),
+            local,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // moves the triangles to `matrix` applied to the space the bvh was built in and refits every
+    // node's bounds; the tree itself is kept
+    pub fn refit(&mut self, matrix: Matrix4<f32>) {
+        if self.refs.is_empty() {
+            return;
+        }
+        for (world, local) in self.triangles.iter_mut().zip(&self.local) {
+            *world = local.map(|p| matrix.transform_point(p));
+        }
+        // children are always stored after their parent
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].bounds = if node.count > 0 {
+                self.triangles[node.first..node.first + node.count]
+                    .iter()
+                    .fold(Aabb::empty(), |bounds, triangle| {
+                        bounds.union(&Aabb::from_triangle(triangle))
+                    })
+            } else {
+                self.nodes[node.first]
+                    .bounds
+                    .union(&self.nodes[node.first + 1].bounds)
+            };
+        }
+    }
+
+    // closest triangle hit within `t_max`, skipping triangles `accept` rejects
+    pub fn closest_hit(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        t_max: f32,
+        accept: impl Fn(TriangleRef) -> bool,
+    ) -> Option<RayHit> {
+        let mut best: Option<RayHit> = None;
+        self.traverse_ray(origin, direction, t_max, |index, t_max| {
+            if !accept(self.refs[index]) {
+                return t_max;
+            }
+            match intersect_triangle(origin, direction, self.triangles[index]) {
+                Some((t, u, v)) if t < t_max => {
+                    best = Some(RayHit {
+                        triangle: self.refs[index],
+                        distance: t,
+                        barycentric: Vector3::new(1.0 - u - v, u, v),
+                    });
+                    t
+                }
+                _ => t_max,
+            }
+        });
+        best
+    }
+
+    // true if any accepted triangle is hit within `t_max`, stops at the first one found
+    pub fn any_hit(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        t_max: f32,
+        accept: impl Fn(TriangleRef) -> bool,
+    ) -> bool {
+        let mut hit = false;
+        self.traverse_ray(origin, direction, t_max, |index, t_max| {
+            if accept(self.refs[index])
+                && intersect_triangle(origin, direction, self.triangles[index])
+                    .is_some_and(|(t, _, _)| t < t_max)
+            {
+                hit = true;
+                // a negative limit ends the traversal
+                return -1.0;
+            }
+            t_max
+        });
+        hit
+    }
+
+    // `visit` tests one triangle and returns the new ray limit
+    fn traverse_ray(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        mut t_max: f32,
+        mut visit: impl FnMut(usize, f32) -> f32,
+    ) {
+        if self.refs.is_empty() {
+            return;
+        }
+        let inv_direction = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node
+                .bounds
+                .intersect_ray(origin, inv_direction, t_max)
+                .is_none()
+            {
+                continue;
+            }
+            if node.count > 0 {
+                for triangle in node.first..node.first + node.count {
+                    t_max = visit(triangle, t_max);
+                    if t_max < 0.0 {
+                        return;
+                    }
+                }
+                continue;
+            }
+            // push the farther child first so the nearer one is tested first
+            let (left, right) = (node.first, node.first + 1);
+            let t_left = self.nodes[left]
+                .bounds
+                .intersect_ray(origin, inv_direction, t_max);
+            let t_right = self.nodes[right]
+                .bounds
+                .intersect_ray(origin, inv_direction, t_max);
+            match (t_left, t_right) {
+                (Some(l), Some(r)) if l <= r => stack.extend([right, left]),
+                (Some(_), Some(_)) => stack.extend([left, right]),
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+    }
+
+    // every triangle that intersects the box
+    pub fn overlapping(&self, aabb: &Aabb) -> Vec<TriangleRef> {
+        let mut found = Vec::new();
+        if self.refs.is_empty() {
+            return found;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.overlaps(aabb) {
+                continue;
+            }
+            if node.count > 0 {
+                for triangle in node.first..node.first + node.count {
+                    if triangle_overlaps_aabb(&self.triangles[triangle], aabb) {
+                        found.push(self.refs[triangle]);
+                    }
+                }
+            } else {
+                stack.extend([node.first, node.first + 1]);
+            }
+        }
+        found
+    }
+
+    // closest point on any accepted triangle no farther than `max_distance`
+    pub fn nearest_point(
+        &self,
+        point: Point3<f32>,
+        max_distance: f32,
+        accept: impl Fn(TriangleRef) -> bool,
+    ) -> Option<NearestPoint> {
+        let mut best: Option<NearestPoint> = None;
+        let mut best_distance2 = max_distance * max_distance;
+        if self.refs.is_empty() {
+            return None;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds.distance2(point) > best_distance2 {
+                continue;
+            }
+            if node.count > 0 {
+                for triangle in node.first..node.first + node.count {
+                    if !accept(self.refs[triangle]) {
+                        continue;
+                    }
+                    let closest = closest_point_on_triangle(point, &self.triangles[triangle]);
+                    let distance2 = (closest - point).magnitude2();
+                    if distance2 <= best_distance2 {
+                        best_distance2 = distance2;
+                        best = Some(NearestPoint {
+                            triangle: self.refs[triangle],
+                            point: closest,
+                            distance: distance2.sqrt(),
+                        });
+                    }
+                }
+                continue;
+            }
+            let (left, right) = (node.first, node.first + 1);
+            let d_left = self.nodes[left].bounds.distance2(point);
+            let d_right = self.nodes[right].bounds.distance2(point);
+            if d_left <= d_right {
+                stack.extend([right, left]);
+            } else {
+                stack.extend([left, right]);
+            }
+        }
+        best
+    }
+}
+
+type BuildItem = (TriangleRef, [Point3<f32>; 3], Aabb, Point3<f32>);
+
+// splits `items` (the node's own triangles) along the cheapest binned sah plane, or leaves the
+// node a leaf when no split beats testing every triangle
+fn build_node(nodes: &mut Vec<Node>, index: usize, items: &mut [BuildItem], first: usize) {
+    let bounds = items
+        .iter()
+        .fold(Aabb::empty(), |bounds, item| bounds.union(&item.2));
+    nodes[index] = Node {
+        bounds,
+        first,
+        count: items.len(),
+    };
+    if items.len() <= MAX_LEAF_SIZE {
+        return;
+    }
+
+    let centroid_bounds = items.iter().fold(Aabb::empty(), |mut bounds, item| {
+        bounds.grow(item.3);
+        bounds
+    });
+    let mut best: Option<(f32, usize, f32)> = None;
+    for axis in 0..3 {
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - min;
+        if extent <= 0.0 {
+            continue;
+        }
+        let bin_of = |c: f32| (((c - min) / extent * SAH_BINS as f32) as usize).min(SAH_BINS - 1);
+
+        let mut bins = [(Aabb::empty(), 0usize); SAH_BINS];
+        for item in items.iter() {
+            let bin = &mut bins[bin_of(item.3[axis])];
+            bin.0 = bin.0.union(&item.2);
+            bin.1 += 1;
+        }
+        // cost of splitting after each bin, from sweeps in both directions
+        let mut left_area = [0.0; SAH_BINS - 1];
+        let mut left_count = [0; SAH_BINS - 1];
+        let mut running = (Aabb::empty(), 0);
+        for i in 0..SAH_BINS - 1 {
+            running = (running.0.union(&bins[i].0), running.1 + bins[i].1);
+            left_area[i] = running.0.surface_area();
+            left_count[i] = running.1;
+        }
+        let mut running = (Aabb::empty(), 0);
+        for i in (1..SAH_BINS).rev() {
+            running = (running.0.union(&bins[i].0), running.1 + bins[i].1);
+            let cost = left_area[i - 1] * left_count[i - 1] as f32
+                + running.0.surface_area() * running.1 as f32;
+            if left_count[i - 1] > 0
+                && running.1 > 0
+                && best.is_none_or(|(best_cost, _, _)| cost < best_cost)
+            {
+                best = Some((cost, axis, min + extent * i as f32 / SAH_BINS as f32));
+            }
+        }
+    }
+
+    let area = bounds.surface_area();
+    let (cost, axis, split) = match best {
+        Some(best) => best,
+        None => return,
+    };
+    let split_cost = TRAVERSAL_COST + TRIANGLE_COST * cost / area.max(f32::EPSILON);
+    if split_cost >= TRIANGLE_COST * items.len() as f32 {
+        return;
+    }
+
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if items[i].3[axis] < split {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    // bins and the partition can disagree on centroids right at the plane
+    if mid == 0 || mid == items.len() {
+        mid = items.len() / 2;
+    }
+
+    let left = nodes.len();
+    let empty = Node {
+        bounds: Aabb::empty(),
+        first: 0,
+        count: 0,
+    };
+    nodes.extend([empty, empty]);
+    nodes[index].first = left;
+    nodes[index].count = 0;
+    let (left_items, right_items) = items.split_at_mut(mid);
+    build_node(nodes, left, left_items, first);
+    build_node(nodes, left + 1, right_items, first + mid);
+}
+
+// Möller–Trumbore, both faces count; returns the ray parameter and the weights of b and c
+fn intersect_triangle(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    [a, b, c]: [Point3<f32>; 3],
+) -> Option<(f32, f32, f32)> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let ao = origin - a;
+    let u = ao.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = ao.cross(ab);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) * inv_det;
+    (t >= 0.0).then_some((t, u, v))
+}
+
+// Ericson, Real-Time Collision Detection 5.1.5
+fn closest_point_on_triangle(p: Point3<f32>, [a, b, c]: &[Point3<f32>; 3]) -> Point3<f32> {
+    let (a, b, c) = (*a, *b, *c);
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denom = 1.0 / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+// separating axis test over the box normals, the triangle normal and the nine edge cross products
+fn triangle_overlaps_aabb(triangle: &[Point3<f32>; 3], aabb: &Aabb) -> bool {
+    let center = aabb.center();
+    let half = (aabb.max - aabb.min) / 2.0;
+    let v = triangle.map(|p| p - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    let separated = |axis: Vector3<f32>| {
+        let projected = v.map(|p| p.dot(axis));
+        let radius = half.x * axis.x.abs() + half.y * axis.y.abs() + half.z * axis.z.abs();
+        let min = projected[0].min(projected[1]).min(projected[2]);
+        let max = projected[0].max(projected[1]).max(projected[2]);
+        min > radius || max < -radius
+    };
+
+    let box_axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    if box_axes.iter().any(|axis| separated(*axis)) {
+        return false;
+    }
+    if separated(edges[0].cross(edges[1])) {
+        return false;
+    }
+    !box_axes
+        .iter()
+        .flat_map(|axis| edges.iter().map(move |edge| axis.cross(*edge)))
+        .any(separated)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, EuclideanSpace, SquareMatrix};
+
+    use super::*;
+
+    // xorshift, so the soup is the same on every run
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+
+        fn point(&mut self, extent: f32) -> Point3<f32> {
+            Point3::new(
+                (self.next() - 0.5) * extent,
+                (self.next() - 0.5) * extent,
+                (self.next() - 0.5) * extent,
+            )
+        }
+    }
+
+    // small triangles scattered through a box, split over two submeshes
+    fn soup(random: &mut Random) -> Vec<(TriangleRef, [Point3<f32>; 3])> {
+        (0..300)
+            .map(|index| {
+                let center = random.point(10.0);
+                let corners = [0; 3].map(|_| center + random.point(1.5).to_vec());
+                let triangle_ref = TriangleRef {
+                    submesh: index % 2,
+                    triangle: index / 2,
+                };
+                (triangle_ref, corners)
+            })
+            .collect()
+    }
+
+    fn key(triangle: TriangleRef) -> (usize, usize) {
+        (triangle.submesh, triangle.triangle)
+    }
+
+    // odd triangles are rejected by the queries that take a filter
+    fn accept(triangle: TriangleRef) -> bool {
+        triangle.triangle.is_multiple_of(2)
+    }
+
+    fn check_queries(
+        bvh: &Bvh,
+        triangles: &[(TriangleRef, [Point3<f32>; 3])],
+        random: &mut Random,
+    ) {
+        // so a traversal that finds nothing can't pass against a brute force that finds nothing
+        let (mut hits, mut overlaps, mut nearest_points) = (0, 0, 0);
+        for _ in 0..200 {
+            let origin = random.point(30.0);
+            let direction = (random.point(10.0) - origin).normalize();
+            let t_max = random.next() * 30.0;
+
+            let expected = triangles
+                .iter()
+                .filter(|(triangle, _)| accept(*triangle))
+                .filter_map(|(_, corners)| intersect_triangle(origin, direction, *corners))
+                .map(|(t, _, _)| t)
+                .filter(|t| *t < t_max)
+                .fold(None, |best: Option<f32>, t| {
+                    Some(best.map_or(t, |b| b.min(t)))
+                });
+            let hit = bvh.closest_hit(origin, direction, t_max, accept);
+            match (hit, expected) {
+                (Some(hit), Some(t)) => {
+                    assert!((hit.distance - t).abs() < 1e-4);
+                    assert!(accept(hit.triangle));
+                    hits += 1;
+                }
+                (None, None) => {}
+                (hit, expected) => panic!("closest hit {:?}, expected {:?}", hit, expected),
+            }
+            assert_eq!(
+                bvh.any_hit(origin, direction, t_max, accept),
+                expected.is_some()
+            );
+
+            let query =
+                Aabb::from_center(random.point(12.0), random.point(4.0).map(f32::abs).to_vec());
+            let mut found = bvh
+                .overlapping(&query)
+                .into_iter()
+                .map(key)
+                .collect::<Vec<_>>();
+            let mut expected = triangles
+                .iter()
+                .filter(|(_, corners)| triangle_overlaps_aabb(corners, &query))
+                .map(|(triangle, _)| key(*triangle))
+                .collect::<Vec<_>>();
+            found.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(found, expected);
+            overlaps += found.len();
+
+            let point = random.point(20.0);
+            let max_distance = random.next() * 5.0;
+            let expected = triangles
+                .iter()
+                .filter(|(triangle, _)| accept(*triangle))
+                .map(|(_, corners)| (closest_point_on_triangle(point, corners) - point).magnitude())
+                .filter(|distance| *distance <= max_distance)
+                .fold(None, |best: Option<f32>, d| {
+                    Some(best.map_or(d, |b| b.min(d)))
+                });
+            let nearest = bvh.nearest_point(point, max_distance, accept);
+            match (nearest, expected) {
+                (Some(nearest), Some(distance)) => {
+                    assert!((nearest.distance - distance).abs() < 1e-4);
+                    assert!(((nearest.point - point).magnitude() - distance).abs() < 1e-4);
+                    assert!(accept(nearest.triangle));
+                    nearest_points += 1;
+                }
+                (None, None) => {}
+                (nearest, expected) => panic!("nearest {:?}, expected {:?}", nearest, expected),
+            }
+        }
+        assert!(hits > 0 && overlaps > 0 && nearest_points > 0);
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut random = Random(0x2545_f491);
+        let triangles = soup(&mut random);
+        let bvh = Bvh::new(triangles.clone());
+        assert!(bvh.node_count() > 1);
+        check_queries(&bvh, &triangles, &mut random);
+    }
+
+    #[test]
+    fn queries_match_brute_force_after_refit() {
+        let mut random = Random(0x9e37_79b9);
+        let triangles = soup(&mut random);
+        let mut bvh = Bvh::new(triangles.clone());
+        for matrix in [
+            Matrix4::from_translation(Vector3::new(3.0, -2.0, 1.0))
+                * Matrix4::from_axis_angle(Vector3::new(1.0, 2.0, 0.5).normalize(), Deg(40.0))
+                * Matrix4::from_nonuniform_scale(1.5, 0.5, 2.0),
+            Matrix4::from_angle_y(Deg(-75.0)) * Matrix4::from_scale(0.8),
+            Matrix4::identity(),
+        ] {
+            bvh.refit(matrix);
+            let moved = triangles
+                .iter()
+                .map(|(triangle, corners)| (*triangle, corners.map(|p| matrix.transform_point(p))))
+                .collect::<Vec<_>>();
+            check_queries(&bvh, &moved, &mut random);
+        }
+    }
+}
@@ -1,19 +1,39 @@
-use cgmath::{Matrix4, Point3, Vector3};
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
 use glium::uniforms::{AsUniformValue, Uniforms, UniformsStorage};
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 2] = [Projection::Perspective, Projection::Orthographic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+}
+
 pub struct Camera {
     camera_pos: Point3<f32>,
     camera_dir: Vector3<f32>,
     camera_up: Vector3<f32>,
     fovy: f32,
     aspect_ratio: f32,
+    viewport: (u32, u32),
     z_near: f32,
     z_far: f32,
-    world_matrix: Matrix4<f32>,
+    pub projection: Projection,
+    // height of the view volume in orthographic mode, i.e. the zoom
+    pub ortho_height: f32,
 }
 
 impl Camera {
-    pub fn new(aspect_ratio: f32) -> Camera {
+    pub fn new(viewport: (u32, u32)) -> Camera {
         let camera_pos = Point3::<f32>::new(0.0, 1.0, 5.0);
         let camera_dir = Point3::<f32>::new(0.0, 0.0, 0.0) - camera_pos;
         let camera_up = Vector3::<f32>::new(0.0, 1.0, 0.0);
@@ -21,6 +41,7 @@ impl Camera {
         let fovy: f32 = 30.0;
         let z_near: f32 = 0.1;
         let z_far: f32 = 1000.0;
+        let aspect_ratio = viewport.0 as f32 / viewport.1.max(1) as f32;
 
         Camera {
             camera_pos,
@@ -28,43 +49,193 @@ impl Camera {
             camera_up,
             fovy,
             aspect_ratio,
+            viewport,
             z_near,
             z_far,
-            world_matrix: Matrix4::<f32>::from_scale(1.0),
+            projection: Projection::Perspective,
+            ortho_height: 2.0 * camera_dir.magnitude() * (fovy.to_radians() / 2.0).tan(),
         }
     }
 
+    // everything a shader needs to know about the camera; model matrices come from each object
+    #[allow(clippy::type_complexity)]
     pub fn add_uniforms<'a, T, R>(
         &self,
         uniforms: UniformsStorage<'a, T, R>,
-    ) -> UniformsStorage<'a, [[f32; 4]; 4], UniformsStorage<'a, T, R>>
+    ) -> UniformsStorage<
+        'a,
+        [f32; 2],
+        UniformsStorage<
+            'a,
+            f32,
+            UniformsStorage<
+                'a,
+                f32,
+                UniformsStorage<
+                    'a,
+                    [f32; 3],
+                    UniformsStorage<
+                        'a,
+                        [[f32; 4]; 4],
+                        UniformsStorage<
+                            'a,
+                            [[f32; 4]; 4],
+                            UniformsStorage<'a, [[f32; 4]; 4], UniformsStorage<'a, T, R>>,
+                        >,
+                    >,
+                >,
+            >,
+        >,
+    >
     where
         T: AsUniformValue,
         R: Uniforms,
     {
-        let mvp = self.perspective_matrix() * self.view_matrix() * self.world_matrix;
+        let view = self.view_matrix();
+        let projection = self.projection_matrix();
+
+        uniforms
+            .add("view", view.into())
+            .add("projection", projection.into())
+            .add("view_projection", (projection * view).into())
+            .add("camera_pos", self.camera_pos.into())
+            .add("z_near", self.z_near)
+            .add("z_far", self.z_far)
+            .add(
+                "viewport_size",
+                [self.viewport.0 as f32, self.viewport.1 as f32],
+            )
+    }
 
-        uniforms.add("mvp", mvp.into())
+    pub fn position(&self) -> Point3<f32> {
+        self.camera_pos
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.camera_dir
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.camera_up
+    }
+
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    pub fn set_fovy(&mut self, fovy: f32) {
+        self.fovy = fovy;
+    }
+
+    pub fn look_at(&mut self, position: Point3<f32>, target: Point3<f32>) {
+        self.set_pose(position, target - position, Vector3::unit_y());
+    }
+
+    pub fn set_pose(&mut self, position: Point3<f32>, direction: Vector3<f32>, up: Vector3<f32>) {
+        self.camera_pos = position;
+        self.camera_dir = direction;
+        self.camera_up = up;
+    }
+
+    // the camera reflected in the horizontal plane at `height`; a look-at frame can't be mirrored,
+    // so what it sees is the true reflection flipped left to right
+    pub fn mirrored(&self, height: f32) -> Camera {
+        let flip = |v: Vector3<f32>| Vector3::new(v.x, -v.y, v.z);
+        Camera {
+            camera_pos: Point3::new(
+                self.camera_pos.x,
+                2.0 * height - self.camera_pos.y,
+                self.camera_pos.z,
+            ),
+            camera_dir: flip(self.camera_dir),
+            camera_up: flip(self.camera_up),
+            ..*self
+        }
     }
 
     pub fn view_matrix(&self) -> Matrix4<f32> {
-        Matrix4::look_to_rh(self.camera_pos, self.camera_dir, self.camera_up)
+        // straight up/down views would make the up vector degenerate, keep -z/+z at the top instead
+        let dir = self.camera_dir.normalize();
+        let up = if dir.cross(self.camera_up).magnitude2() > 1e-8 {
+            self.camera_up
+        } else {
+            Vector3::new(0.0, 0.0, dir.y.signum())
+        };
+        Matrix4::look_to_rh(self.camera_pos, self.camera_dir, up)
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective => cgmath::perspective(
+                cgmath::Deg(self.fovy),
+                self.aspect_ratio,
+                self.z_near,
+                self.z_far,
+            ),
+            Projection::Orthographic => {
+                let half_height = self.ortho_height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+                // the view volume reaches behind the camera so zooming in never clips the model
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    -self.z_far,
+                    self.z_far,
+                )
+            }
+        }
+    }
+
+    // world-space ray through a point given in normalized device coordinates, as origin and unit
+    // direction; works for both projections
+    pub fn screen_ray(&self, ndc_x: f32, ndc_y: f32) -> (Point3<f32>, Vector3<f32>) {
+        let inverse = (self.projection_matrix() * self.view_matrix())
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+        let unproject = |z: f32| {
+            let p = inverse * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        };
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        (near, (far - near).normalize())
+    }
+
+    // world-space size that covers `fraction` of the viewport height at `point`
+    pub fn screen_scale(&self, point: Point3<f32>, fraction: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective => {
+                let depth = (point - self.camera_pos).dot(self.camera_dir.normalize());
+                2.0 * depth.max(self.z_near) * (self.fovy.to_radians() / 2.0).tan() * fraction
+            }
+            Projection::Orthographic => self.ortho_height * fraction,
+        }
+    }
+
+    // distance at which a sphere of `radius` just fits both the vertical and horizontal fov
+    pub fn framing_distance(&self, radius: f32) -> f32 {
+        let half_fovy = self.fovy.to_radians() / 2.0;
+        let half_fovx = (half_fovy.tan() * self.aspect_ratio).atan();
+        radius / half_fovy.min(half_fovx).sin()
     }
 
-    pub fn perspective_matrix(&self) -> Matrix4<f32> {
-        cgmath::perspective(
-            cgmath::Deg(self.fovy),
-            self.aspect_ratio,
-            self.z_near,
-            self.z_far,
-        )
+    // near/far planes hugging a sphere of `radius` seen from `distance`; the controllers refit them
+    // as the camera moves
+    pub fn fit_clip_planes(&mut self, distance: f32, radius: f32) {
+        self.z_near = ((distance - radius) * 0.5).max(radius * 0.01);
+        self.z_far = (distance + radius) * 4.0;
     }
 
-    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
-        self.aspect_ratio = aspect_ratio;
+    // keeps the orthographic zoom matching what the perspective projection shows at `distance`
+    pub fn set_focus_distance(&mut self, distance: f32) {
+        self.ortho_height = 2.0 * distance * (self.fovy.to_radians() / 2.0).tan();
     }
 
-    pub fn set_world_matrix(&mut self, world_matrix: Matrix4<f32>) {
-        self.world_matrix = world_matrix;
+    // size of the target being rendered to, which also sets the aspect ratio
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width.max(1), height.max(1));
+        self.aspect_ratio = self.viewport.0 as f32 / self.viewport.1 as f32;
     }
 }
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use glium::glutin::event::{
+    ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use crate::camera::Camera;
+
+const ROTATE_DEG_PER_PIXEL: f32 = 0.3;
+const PAN_PER_PIXEL: f32 = 0.0015;
+const DOLLY_PER_LINE: f32 = 0.9;
+const PIXELS_PER_LINE: f32 = 50.0;
+const MAX_PITCH_DEG: f32 = 89.0;
+const MIN_DISTANCE: f32 = 0.05;
+const LOOK_DEG_PER_PIXEL: f32 = 0.15;
+const SPEED_PER_LINE: f32 = 1.1;
+const BOOST_FACTOR: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+impl CameraMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "orbit",
+            CameraMode::Fly => "fly",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Drag {
+    None,
+    Rotate,
+    Pan,
+}
+
+#[derive(Copy, Clone)]
+struct OrbitState {
+    target: Point3<f32>,
+    yaw_deg: f32,
+    pitch_deg: f32,
+    distance: f32,
+}
+
+impl OrbitState {
+    fn position(&self) -> Point3<f32> {
+        self.target + direction(self.yaw_deg, self.pitch_deg) * self.distance
+    }
+}
+
+// rotates, pans and dollies the camera around a target point; mouse input moves the goal state
+// and the camera eases towards it
+pub struct OrbitController {
+    // fraction of the remaining motion kept after one 60 Hz frame, 0 follows the mouse exactly
+    pub damping: f32,
+    goal: OrbitState,
+    current: OrbitState,
+    drag: Drag,
+    cursor: Option<(f64, f64)>,
+}
+
+impl OrbitController {
+    pub fn new(camera: &Camera, target: Point3<f32>) -> Self {
+        let offset = camera.position() - target;
+        let distance = offset.magnitude().max(MIN_DISTANCE);
+        let state = OrbitState {
+            target,
+            yaw_deg: offset.x.atan2(offset.z).to_degrees(),
+            pitch_deg: (offset.y / distance).asin().to_degrees(),
+            distance,
+        };
+
+        OrbitController {
+            damping: 0.8,
+            goal: state,
+            current: state,
+            drag: Drag::None,
+            cursor: None,
+        }
+    }
+
+    // picks up a camera placed from elsewhere, orbiting the point it looks at from the current
+    // distance
+    pub fn sync(&mut self, camera: &Camera) {
+        let direction = camera.direction().normalize();
+        let distance = self.goal.distance;
+        self.goal = OrbitState {
+            target: camera.position() + direction * distance,
+            yaw_deg: (-direction.x).atan2(-direction.z).to_degrees(),
+            pitch_deg: (-direction.y).clamp(-1.0, 1.0).asin().to_degrees(),
+            distance,
+        };
+        self.current = self.goal;
+    }
+
+    // eases to a view that fits the sphere, keeping the current orientation
+    pub fn frame(&mut self, center: Point3<f32>, radius: f32, camera: &mut Camera) {
+        self.goal.target = center;
+        self.goal.distance = camera.framing_distance(radius).max(MIN_DISTANCE);
+        camera.fit_clip_planes(self.goal.distance, radius);
+    }
+
+    // eases to an axis-aligned view, turning the short way round
+    pub fn snap_view(&mut self, yaw_deg: f32, pitch_deg: f32) {
+        let turn = (yaw_deg - self.goal.yaw_deg + 180.0).rem_euclid(360.0) - 180.0;
+        self.goal.yaw_deg += turn;
+        self.goal.pitch_deg = pitch_deg;
+    }
+
+    // `ui_wants_mouse` comes from imgui; drags that start over a window are ignored, but a
+    // release is always handled so a drag can't get stuck
+    pub fn handle_event(&mut self, event: &WindowEvent, ui_wants_mouse: bool) {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed if !ui_wants_mouse => {
+                    self.drag = match button {
+                        MouseButton::Left => Drag::Rotate,
+                        MouseButton::Right | MouseButton::Middle => Drag::Pan,
+                        _ => self.drag,
+                    };
+                }
+                ElementState::Released => self.drag = Drag::None,
+                _ => {}
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some((x, y)) = self.cursor {
+                    self.on_drag((position.x - x) as f32, (position.y - y) as f32);
+                }
+                self.cursor = Some((position.x, position.y));
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { delta, .. } if !ui_wants_mouse => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+                self.goal.distance =
+                    (self.goal.distance * DOLLY_PER_LINE.powf(lines)).max(MIN_DISTANCE);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_drag(&mut self, dx: f32, dy: f32) {
+        match self.drag {
+            Drag::None => {}
+            Drag::Rotate => {
+                self.goal.yaw_deg -= dx * ROTATE_DEG_PER_PIXEL;
+                self.goal.pitch_deg = (self.goal.pitch_deg + dy * ROTATE_DEG_PER_PIXEL)
+                    .clamp(-MAX_PITCH_DEG, MAX_PITCH_DEG);
+            }
+            Drag::Pan => {
+                // right comes from the yaw alone so it stays valid in top and bottom views
+                let yaw = self.goal.yaw_deg.to_radians();
+                let right = Vector3::new(yaw.cos(), 0.0, -yaw.sin());
+                let forward = -direction(self.goal.yaw_deg, self.goal.pitch_deg);
+                let up = right.cross(forward);
+                let scale = self.goal.distance * PAN_PER_PIXEL;
+                self.goal.target += (-right * dx + up * dy) * scale;
+            }
+        }
+    }
+
+    // eases the current state towards the goal and places the camera; the clip planes follow its
+    // distance to `bounds`, the scene as it is this frame
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        camera: &mut Camera,
+        bounds: Option<(Point3<f32>, f32)>,
+    ) {
+        let t = 1.0 - self.damping.clamp(0.0, 0.99).powf(delta_time * 60.0);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+        self.current = OrbitState {
+            target: self.current.target + (self.goal.target - self.current.target) * t,
+            yaw_deg: lerp(self.current.yaw_deg, self.goal.yaw_deg),
+            pitch_deg: lerp(self.current.pitch_deg, self.goal.pitch_deg),
+            distance: lerp(self.current.distance, self.goal.distance),
+        };
+        camera.look_at(self.current.position(), self.current.target);
+        camera.set_focus_distance(self.current.distance);
+        if let Some((center, radius)) = bounds {
+            camera.fit_clip_planes((camera.position() - center).magnitude(), radius);
+        }
+    }
+}
+
+fn direction(yaw_deg: f32, pitch_deg: f32) -> Vector3<f32> {
+    let (yaw, pitch) = (yaw_deg.to_radians(), pitch_deg.to_radians());
+    Vector3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+// first-person camera: WASD moves in the view plane, E/Q move up/down, shift boosts the speed
+// and the mouse looks around while the cursor is captured
+pub struct FlyController {
+    // units per second
+    pub speed: f32,
+    position: Point3<f32>,
+    yaw_deg: f32,
+    pitch_deg: f32,
+    pressed: HashSet<VirtualKeyCode>,
+}
+
+impl FlyController {
+    pub fn new(camera: &Camera) -> Self {
+        let mut controller = FlyController {
+            speed: 1.5,
+            position: camera.position(),
+            yaw_deg: 0.0,
+            pitch_deg: 0.0,
+            pressed: HashSet::new(),
+        };
+        controller.reset(camera);
+        controller
+    }
+
+    // continues from wherever the camera currently is
+    pub fn reset(&mut self, camera: &Camera) {
+        let dir = camera.direction().normalize();
+        self.position = camera.position();
+        self.yaw_deg = dir.x.atan2(dir.z).to_degrees();
+        self.pitch_deg = dir.y.clamp(-1.0, 1.0).asin().to_degrees();
+        self.pressed.clear();
+    }
+
+    // backs off along the view direction until the sphere fits
+    pub fn frame(&mut self, center: Point3<f32>, radius: f32, camera: &mut Camera) {
+        let distance = camera.framing_distance(radius);
+        self.position = center - direction(self.yaw_deg, self.pitch_deg) * distance;
+        camera.fit_clip_planes(distance, radius);
+    }
+
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.pressed.insert(keycode),
+            ElementState::Released => self.pressed.remove(&keycode),
+        };
+    }
+
+    pub fn handle_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.yaw_deg -= dx * LOOK_DEG_PER_PIXEL;
+        self.pitch_deg =
+            (self.pitch_deg - dy * LOOK_DEG_PER_PIXEL).clamp(-MAX_PITCH_DEG, MAX_PITCH_DEG);
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::MouseWheel { delta, .. } = event {
+            let lines = match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+            };
+            self.speed *= SPEED_PER_LINE.powf(lines);
+        }
+    }
+
+    // `bounds` as for `OrbitController::update`
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        camera: &mut Camera,
+        bounds: Option<(Point3<f32>, f32)>,
+    ) {
+        let forward = direction(self.yaw_deg, self.pitch_deg);
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let axis = |positive, negative| {
+            self.pressed.contains(&positive) as i32 as f32
+                - self.pressed.contains(&negative) as i32 as f32
+        };
+
+        let movement = forward * axis(VirtualKeyCode::W, VirtualKeyCode::S)
+            + right * axis(VirtualKeyCode::D, VirtualKeyCode::A)
+            + Vector3::unit_y() * axis(VirtualKeyCode::E, VirtualKeyCode::Q);
+        if movement.magnitude2() > 0.0 {
+            let boost = if self.pressed.contains(&VirtualKeyCode::LShift)
+                || self.pressed.contains(&VirtualKeyCode::RShift)
+            {
+                BOOST_FACTOR
+            } else {
+                1.0
+            };
+            self.position += movement.normalize() * self.speed * boost * delta_time;
+        }
+
+        camera.look_at(self.position, self.position + forward);
+        if let Some((center, radius)) = bounds {
+            camera.fit_clip_planes((self.position - center).magnitude(), radius);
+        }
+    }
+}
@@ -0,0 +1,512 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix4, Quaternion, Vector3};
+use glium::{
+    draw_parameters::DepthClamp,
+    framebuffer::MultiOutputFrameBuffer,
+    index::{NoIndices, PrimitiveType},
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, UncompressedFloatFormat},
+    uniforms::{
+        AsUniformValue, MagnifySamplerFilter, MinifySamplerFilter, Sampler, Uniforms,
+        UniformsStorage,
+    },
+    BackfaceCullingMode, Blend, BlendingFunction, Display, DrawParameters, IndexBuffer,
+    LinearBlendingFactor, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::{
+    environment::{fullscreen_triangle, Environment, VertexP2},
+    light::{DirectionalLight, PointLight, SpotLight},
+    scene_obj::SceneObject,
+    shading::{with_defines, ShadingMode},
+};
+
+// irradiance below this is cut off by the volumes; a sixteenth of an 8-bit step, so the edge stays
+// invisible even once exposure and tone mapping brighten dark pixels. forward lighting has no
+// cutoff, the ui says so next to the g-buffer view
+pub const VOLUME_CUTOFF: f32 = 1.0 / 4096.0;
+// the tessellated volumes are inscribed in the true shapes, so they are inflated a little
+const VOLUME_PADDING: f32 = 1.15;
+const VOLUME_SEGMENTS: u32 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderPath {
+    Forward,
+    Deferred,
+}
+
+impl RenderPath {
+    pub const ALL: [RenderPath; 2] = [RenderPath::Forward, RenderPath::Deferred];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderPath::Forward => "forward",
+            RenderPath::Deferred => "deferred",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GBufferView {
+    Lit,
+    Position,
+    Normal,
+    Albedo,
+    Specular,
+    Shininess,
+    Ambient,
+    Depth,
+}
+
+impl GBufferView {
+    pub const ALL: [GBufferView; 8] = [
+        GBufferView::Lit,
+        GBufferView::Position,
+        GBufferView::Normal,
+        GBufferView::Albedo,
+        GBufferView::Specular,
+        GBufferView::Shininess,
+        GBufferView::Ambient,
+        GBufferView::Depth,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GBufferView::Lit => "lit",
+            GBufferView::Position => "position",
+            GBufferView::Normal => "normal",
+            GBufferView::Albedo => "albedo",
+            GBufferView::Specular => "specular",
+            GBufferView::Shininess => "shininess",
+            GBufferView::Ambient => "ambient",
+            GBufferView::Depth => "depth",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct VertexP {
+    position: [f32; 3],
+}
+
+glium::implement_vertex!(VertexP, position);
+
+struct VolumeMesh {
+    vertex_buffer: VertexBuffer<VertexP>,
+    index_buffer: IndexBuffer<u32>,
+}
+
+impl VolumeMesh {
+    // unit sphere, triangles wound counter-clockwise when seen from outside
+    fn sphere(display: &Display) -> Self {
+        let rings = VOLUME_SEGMENTS / 2;
+        let mut vertices = Vec::new();
+        for ring in 0..=rings {
+            let theta = PI * ring as f32 / rings as f32;
+            for segment in 0..=VOLUME_SEGMENTS {
+                let phi = 2.0 * PI * segment as f32 / VOLUME_SEGMENTS as f32;
+                vertices.push(VertexP {
+                    position: [
+                        theta.sin() * phi.cos(),
+                        theta.cos(),
+                        -theta.sin() * phi.sin(),
+                    ],
+                });
+            }
+        }
+        let mut indices = Vec::new();
+        let stride = VOLUME_SEGMENTS + 1;
+        for ring in 0..rings {
+            for segment in 0..VOLUME_SEGMENTS {
+                let a = ring * stride + segment;
+                let b = a + stride;
+                indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+        Self::new(display, &vertices, &indices)
+    }
+
+    // unit cone with its apex at the origin, opening towards -z with a base radius of 1
+    fn cone(display: &Display) -> Self {
+        let mut vertices = vec![
+            VertexP {
+                position: [0.0, 0.0, 0.0],
+            },
+            VertexP {
+                position: [0.0, 0.0, -1.0],
+            },
+        ];
+        for segment in 0..VOLUME_SEGMENTS {
+            let phi = 2.0 * PI * segment as f32 / VOLUME_SEGMENTS as f32;
+            vertices.push(VertexP {
+                position: [phi.cos(), phi.sin(), -1.0],
+            });
+        }
+        let mut indices = Vec::new();
+        for segment in 0..VOLUME_SEGMENTS {
+            let a = 2 + segment;
+            let b = 2 + (segment + 1) % VOLUME_SEGMENTS;
+            indices.extend_from_slice(&[0, a, b, 1, b, a]);
+        }
+        Self::new(display, &vertices, &indices)
+    }
+
+    fn new(display: &Display, vertices: &[VertexP], indices: &[u32]) -> Self {
+        VolumeMesh {
+            vertex_buffer: VertexBuffer::new(display, vertices).unwrap(),
+            index_buffer: IndexBuffer::new(display, PrimitiveType::TrianglesList, indices).unwrap(),
+        }
+    }
+}
+
+struct GBuffer {
+    position: Texture2d,
+    normal: Texture2d,
+    albedo: Texture2d,
+    specular: Texture2d,
+    ambient: Texture2d,
+    depth: DepthTexture2d,
+}
+
+impl GBuffer {
+    fn new(display: &Display, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let attachment = |format| {
+            Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+                .unwrap()
+        };
+
+        GBuffer {
+            position: attachment(UncompressedFloatFormat::F32F32F32F32),
+            normal: attachment(UncompressedFloatFormat::F16F16F16F16),
+            // tinted colors can go past 1; the alpha holds the shading model
+            albedo: attachment(UncompressedFloatFormat::F16F16F16F16),
+            specular: attachment(UncompressedFloatFormat::F16F16F16F16),
+            ambient: attachment(UncompressedFloatFormat::F16F16F16F16),
+            depth: DepthTexture2d::empty_with_format(
+                display,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .unwrap(),
+        }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.position.dimensions()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn add_uniforms<'a, T, R>(
+        &'a self,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> UniformsStorage<
+        'a,
+        Sampler<'a, DepthTexture2d>,
+        UniformsStorage<
+            'a,
+            Sampler<'a, Texture2d>,
+            UniformsStorage<
+                'a,
+                Sampler<'a, Texture2d>,
+                UniformsStorage<
+                    'a,
+                    Sampler<'a, Texture2d>,
+                    UniformsStorage<
+                        'a,
+                        Sampler<'a, Texture2d>,
+                        UniformsStorage<'a, Sampler<'a, Texture2d>, UniformsStorage<'a, T, R>>,
+                    >,
+                >,
+            >,
+        >,
+    >
+    where
+        T: AsUniformValue,
+        R: Uniforms,
+    {
+        fn nearest<T>(sampler: Sampler<'_, T>) -> Sampler<'_, T> {
+            sampler
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+        }
+
+        uniforms
+            .add("g_position", nearest(self.position.sampled()))
+            .add("g_normal", nearest(self.normal.sampled()))
+            .add("g_albedo", nearest(self.albedo.sampled()))
+            .add("g_specular", nearest(self.specular.sampled()))
+            .add("g_ambient", nearest(self.ambient.sampled()))
+            .add("g_depth", nearest(self.depth.sampled()))
+    }
+}
+
+pub struct DeferredRenderer {
+    pub view: GBufferView,
+    gbuffer: GBuffer,
+    geometry_program: Program,
+    base_program: Program,
+    point_program: Program,
+    spot_program: Program,
+    debug_program: Program,
+    fullscreen: VertexBuffer<VertexP2>,
+    sphere: VolumeMesh,
+    cone: VolumeMesh,
+}
+
+impl DeferredRenderer {
+    pub fn new(display: &Display) -> Self {
+        let lighting_fs = include_str!("shaders/deferred_lighting.fs");
+        let geometry_program = Program::from_source(
+            display,
+            include_str!("shaders/phong_shading.vs"),
+            include_str!("shaders/gbuffer.fs"),
+            None,
+        )
+        .unwrap();
+        let base_program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            lighting_fs,
+            None,
+        )
+        .unwrap();
+        let point_program = Program::from_source(
+            display,
+            include_str!("shaders/light_volume.vs"),
+            &with_defines(lighting_fs, &["POINT_LIGHT"]),
+            None,
+        )
+        .unwrap();
+        let spot_program = Program::from_source(
+            display,
+            include_str!("shaders/light_volume.vs"),
+            &with_defines(lighting_fs, &["SPOT_LIGHT"]),
+            None,
+        )
+        .unwrap();
+        let debug_program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            include_str!("shaders/gbuffer_debug.fs"),
+            None,
+        )
+        .unwrap();
+        let (width, height) = display.get_framebuffer_dimensions();
+
+        DeferredRenderer {
+            view: GBufferView::Lit,
+            gbuffer: GBuffer::new(display, width, height),
+            geometry_program,
+            base_program,
+            point_program,
+            spot_program,
+            debug_program,
+            fullscreen: fullscreen_triangle(display),
+            sphere: VolumeMesh::sphere(display),
+            cone: VolumeMesh::cone(display),
+        }
+    }
+
+    pub fn resize(&mut self, display: &Display, width: u32, height: u32) {
+        if self.gbuffer.dimensions() != (width, height) {
+            self.gbuffer = GBuffer::new(display, width, height);
+        }
+    }
+
+    pub fn gbuffer_position(&self) -> &Texture2d {
+        &self.gbuffer.position
+    }
+
+    pub fn gbuffer_normal(&self) -> &Texture2d {
+        &self.gbuffer.normal
+    }
+
+    // clears the g-buffer and fills it with `objects`, each shaded with its override or
+    // `shading_mode`; expects the camera uniforms in `uniforms`
+    pub fn draw_geometry<'a, 'b, I, T, R>(
+        &self,
+        display: &Display,
+        objects: I,
+        shading_mode: ShadingMode,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = &'b SceneObject>,
+        T: AsUniformValue + Clone,
+        R: Uniforms + Clone,
+    {
+        let outputs = [
+            ("g_position", &self.gbuffer.position),
+            ("g_normal", &self.gbuffer.normal),
+            ("g_albedo", &self.gbuffer.albedo),
+            ("g_specular", &self.gbuffer.specular),
+            ("g_ambient", &self.gbuffer.ambient),
+        ];
+        let mut target =
+            MultiOutputFrameBuffer::with_depth_buffer(display, outputs, &self.gbuffer.depth)?;
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+        for scene_obj in objects {
+            let mode = scene_obj.shading_override.unwrap_or(shading_mode);
+            if is_forward_only(mode) {
+                continue;
+            }
+            let uniforms = uniforms
+                .clone()
+                .add("flat_normals", mode == ShadingMode::Flat)
+                .add("shading_model", shading_model(mode));
+            scene_obj.draw(
+                &mut target,
+                &self.geometry_program,
+                scene_obj.add_uniforms(uniforms),
+            )?;
+        }
+        Ok(())
+    }
+
+    // shades the g-buffer into `frame`: one full-screen pass for ambient, directional and
+    // environment light, then one additive light volume per point and spot light; expects the
+    // camera and toon uniforms in `uniforms`
+    pub fn draw_lighting<S, T, R>(
+        &self,
+        frame: &mut S,
+        uniforms: UniformsStorage<T, R>,
+        point_lights: &[&PointLight],
+        spot_lights: &[&SpotLight],
+        dir_light: &DirectionalLight,
+        environment: &Environment,
+    ) -> Result<(), glium::DrawError>
+    where
+        S: Surface,
+        T: AsUniformValue + Copy,
+        R: Uniforms + Copy,
+    {
+        let uniforms = self.gbuffer.add_uniforms(uniforms);
+
+        if self.view != GBufferView::Lit {
+            let attachment = GBufferView::ALL
+                .iter()
+                .position(|view| *view == self.view)
+                .unwrap() as i32
+                - 1;
+            return frame.draw(
+                &self.fullscreen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.debug_program,
+                &uniforms.add("attachment", attachment),
+                &Default::default(),
+            );
+        }
+
+        frame.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.base_program,
+            &environment.add_uniforms(dir_light.add_uniforms(uniforms)),
+            &DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::Overwrite,
+                    write: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?;
+
+        // only the inside faces of the volumes are drawn, so each pixel is shaded once whether
+        // the camera is inside the volume or not
+        let volume_params = DrawParameters {
+            blend: Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::AlwaysReplace,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            backface_culling: BackfaceCullingMode::CullCounterClockwise,
+            // the far side of a big volume can lie past the far plane
+            depth: glium::Depth {
+                clamp: DepthClamp::Clamp,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for point_light in point_lights {
+            let radius = light_radius(point_light.intensity) * VOLUME_PADDING;
+            let volume_matrix: [[f32; 4]; 4] = (Matrix4::from_translation(point_light.position)
+                * Matrix4::from_scale(radius))
+            .into();
+            frame.draw(
+                &self.sphere.vertex_buffer,
+                &self.sphere.index_buffer,
+                &self.point_program,
+                &point_light.add_uniforms(uniforms.add("volume_matrix", volume_matrix)),
+                &volume_params,
+            )?;
+        }
+
+        for spot_light in spot_lights {
+            let range = light_radius(spot_light.point_light.intensity);
+            let half_angle = spot_light.total_width_deg().to_radians();
+            let translation = Matrix4::from_translation(spot_light.point_light.position);
+            // wide cones are not worth the special case, light them with a sphere instead
+            let (mesh, volume_matrix) = if half_angle < 80f32.to_radians() {
+                let base_radius = range * half_angle.tan() * VOLUME_PADDING;
+                let rotation = Quaternion::from_arc(
+                    Vector3::new(0.0, 0.0, -1.0),
+                    spot_light.direction().normalize(),
+                    Some(Vector3::new(1.0, 0.0, 0.0)),
+                );
+                let matrix = translation
+                    * Matrix4::from(rotation)
+                    * Matrix4::from_nonuniform_scale(
+                        base_radius,
+                        base_radius,
+                        range * VOLUME_PADDING,
+                    );
+                (&self.cone, matrix)
+            } else {
+                (
+                    &self.sphere,
+                    translation * Matrix4::from_scale(range * VOLUME_PADDING),
+                )
+            };
+            let volume_matrix: [[f32; 4]; 4] = volume_matrix.into();
+            frame.draw(
+                &mesh.vertex_buffer,
+                &mesh.index_buffer,
+                &self.spot_program,
+                &spot_light.add_uniforms(uniforms.add("volume_matrix", volume_matrix)),
+                &volume_params,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// gouraud lights the vertices, which the g-buffer can't hold, so those objects are left out of it
+// and drawn forward after the lighting
+pub fn is_forward_only(mode: ShadingMode) -> bool {
+    mode == ShadingMode::Gouraud
+}
+
+// flat is phong on the face normals, in the forward path as well, so it only differs in the
+// normals the geometry pass stores
+fn shading_model(mode: ShadingMode) -> i32 {
+    match mode {
+        // gouraud never reaches the g-buffer, see `is_forward_only`
+        ShadingMode::Flat | ShadingMode::Gouraud | ShadingMode::Phong => 0,
+        ShadingMode::BlinnPhong => 1,
+        ShadingMode::Toon => 2,
+    }
+}
+
+// distance at which an inverse-square light falls below the cutoff
+fn light_radius(intensity: Vector3<f32>) -> f32 {
+    let max_intensity = intensity.x.max(intensity.y).max(intensity.z);
+    (max_intensity / VOLUME_CUTOFF).sqrt()
+}
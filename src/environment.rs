@@ -0,0 +1,285 @@
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    index::{NoIndices, PrimitiveType},
+    texture::{MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::{
+        AsUniformValue, MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior,
+        SamplerWrapFunction, Uniforms, UniformsStorage,
+    },
+    Display, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::camera::Camera;
+
+const IRRADIANCE_SIZE: (u32, u32) = (64, 32);
+const PREFILTERED_SIZE: (u32, u32) = (256, 128);
+const PREFILTERED_LEVELS: u32 = 6;
+
+#[derive(Copy, Clone, Debug)]
+pub struct VertexP2 {
+    position: [f32; 2],
+}
+
+glium::implement_vertex!(VertexP2, position);
+
+pub fn fullscreen_triangle(display: &Display) -> VertexBuffer<VertexP2> {
+    VertexBuffer::new(
+        display,
+        &[
+            VertexP2 {
+                position: [-1.0, -1.0],
+            },
+            VertexP2 {
+                position: [3.0, -1.0],
+            },
+            VertexP2 {
+                position: [-1.0, 3.0],
+            },
+        ],
+    )
+    .unwrap()
+}
+
+pub struct Environment {
+    pub rotation_deg: f32,
+    pub intensity: f32,
+    pub show_skybox: bool,
+    source_path: Option<String>,
+    env_map: Texture2d,
+    irradiance: Texture2d,
+    prefiltered: Texture2d,
+    fullscreen: VertexBuffer<VertexP2>,
+    skybox_program: Program,
+}
+
+impl Environment {
+    pub fn new(display: &Display) -> Self {
+        let placeholder = || {
+            Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::F16F16F16F16,
+                MipmapsOption::NoMipmap,
+                1,
+                1,
+            )
+            .unwrap()
+        };
+        let skybox_program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            include_str!("shaders/skybox.fs"),
+            None,
+        )
+        .unwrap();
+
+        Environment {
+            rotation_deg: 0.0,
+            intensity: 1.0,
+            show_skybox: true,
+            source_path: None,
+            env_map: placeholder(),
+            irradiance: placeholder(),
+            prefiltered: placeholder(),
+            fullscreen: fullscreen_triangle(display),
+            skybox_program,
+        }
+    }
+
+    pub fn source_path(&self) -> Option<&str> {
+        self.source_path.as_deref()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.source_path.is_some()
+    }
+
+    pub fn unload(&mut self) {
+        self.source_path = None;
+    }
+
+    // loads an equirectangular .hdr/.exr image and precomputes the irradiance map and the
+    // prefiltered specular mip chain
+    pub fn load(
+        &mut self,
+        display: &Display,
+        file_path: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let image = image::open(file_path)?.into_rgb32f();
+        let dimensions = image.dimensions();
+        let raw = RawImage2d::from_raw_rgb_reversed(image.as_raw(), dimensions);
+        let env_map = Texture2d::with_format(
+            display,
+            raw,
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::AutoGeneratedMipmaps,
+        )?;
+
+        let irradiance = self.bake_irradiance(display, &env_map)?;
+        let prefiltered = self.bake_prefiltered(display, &env_map)?;
+
+        self.env_map = env_map;
+        self.irradiance = irradiance;
+        self.prefiltered = prefiltered;
+        self.source_path = Some(String::from(file_path));
+        Ok(())
+    }
+
+    fn bake_irradiance(
+        &self,
+        display: &Display,
+        env_map: &Texture2d,
+    ) -> Result<Texture2d, Box<dyn std::error::Error>> {
+        let program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            include_str!("shaders/env_irradiance.fs"),
+            None,
+        )?;
+        let irradiance = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::NoMipmap,
+            IRRADIANCE_SIZE.0,
+            IRRADIANCE_SIZE.1,
+        )?;
+
+        // the convolution is a wide blur, so a low resolution source level is enough
+        let source_lod = (env_map.width() as f32 / IRRADIANCE_SIZE.0 as f32)
+            .log2()
+            .max(0.0);
+
+        let mut target = SimpleFrameBuffer::new(display, &irradiance)?;
+        target.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &program,
+            &uniform! {
+                env_map: equirect_sampler(env_map),
+                source_lod: source_lod,
+            },
+            &Default::default(),
+        )?;
+        Ok(irradiance)
+    }
+
+    fn bake_prefiltered(
+        &self,
+        display: &Display,
+        env_map: &Texture2d,
+    ) -> Result<Texture2d, Box<dyn std::error::Error>> {
+        let program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            include_str!("shaders/env_prefilter.fs"),
+            None,
+        )?;
+        let prefiltered = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::EmptyMipmapsMax(PREFILTERED_LEVELS - 1),
+            PREFILTERED_SIZE.0,
+            PREFILTERED_SIZE.1,
+        )?;
+
+        for level in 0..PREFILTERED_LEVELS {
+            let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+            let mut target = SimpleFrameBuffer::new(display, prefiltered.mipmap(level).unwrap())?;
+            target.draw(
+                &self.fullscreen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &program,
+                &uniform! {
+                    env_map: equirect_sampler(env_map),
+                    env_map_size: [env_map.width() as f32, env_map.height() as f32],
+                    roughness: roughness,
+                },
+                &Default::default(),
+            )?;
+        }
+        Ok(prefiltered)
+    }
+
+    pub fn draw_skybox<S>(&self, frame: &mut S, camera: &Camera) -> Result<(), glium::DrawError>
+    where
+        S: Surface,
+    {
+        if !self.is_loaded() || !self.show_skybox {
+            return Ok(());
+        }
+
+        // only the camera orientation matters for the background
+        let mut view = camera.view_matrix();
+        view.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let inv_view_projection: Matrix4<f32> =
+            (camera.projection_matrix() * view).invert().unwrap();
+
+        frame.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.skybox_program,
+            &uniform! {
+                inv_view_projection: <Matrix4<f32> as Into<[[f32; 4]; 4]>>::into(inv_view_projection),
+                env_map: equirect_sampler(&self.env_map),
+                env_rotation: self.rotation_deg.to_radians(),
+                env_intensity: self.intensity,
+            },
+            &Default::default(),
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn add_uniforms<'a, T, R>(
+        &'a self,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> UniformsStorage<
+        'a,
+        f32,
+        UniformsStorage<
+            'a,
+            f32,
+            UniformsStorage<
+                'a,
+                f32,
+                UniformsStorage<
+                    'a,
+                    Sampler<'a, Texture2d>,
+                    UniformsStorage<
+                        'a,
+                        Sampler<'a, Texture2d>,
+                        UniformsStorage<'a, bool, UniformsStorage<'a, T, R>>,
+                    >,
+                >,
+            >,
+        >,
+    >
+    where
+        T: AsUniformValue,
+        R: Uniforms,
+    {
+        uniforms
+            .add("env_enabled", self.is_loaded())
+            .add("env_irradiance", equirect_sampler(&self.irradiance))
+            .add("env_prefiltered", equirect_sampler(&self.prefiltered))
+            .add("env_max_lod", (PREFILTERED_LEVELS - 1) as f32)
+            .add("env_rotation", self.rotation_deg.to_radians())
+            .add("env_intensity", self.intensity)
+    }
+}
+
+fn equirect_sampler(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    Sampler(
+        texture,
+        SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..Default::default()
+        },
+    )
+}
@@ -0,0 +1,779 @@
+use std::f32::consts::PI;
+
+use cgmath::{
+    Deg, Euler, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, Rotation3, SquareMatrix,
+    Transform, Vector3, Vector4,
+};
+use glium::{
+    glutin::event::{ElementState, MouseButton, WindowEvent},
+    index::{NoIndices, PrimitiveType},
+    uniform, Blend, Display, DrawParameters, Program, Surface, VertexBuffer,
+};
+
+use crate::{camera::Camera, scene::Scene};
+
+// gizmo arm length as a fraction of the viewport height
+const SCREEN_FRACTION: f32 = 0.15;
+const PICK_RADIUS_PX: f32 = 8.0;
+// horizontal drag distance that doubles the size with the uniform scale handle
+const UNIFORM_SCALE_PX: f32 = 150.0;
+const RING_SEGMENTS: usize = 64;
+const CONE_SEGMENTS: usize = 12;
+const CONE_LENGTH: f32 = 0.2;
+const CONE_RADIUS: f32 = 0.05;
+const PLANE_MIN: f32 = 0.25;
+const PLANE_MAX: f32 = 0.45;
+const SCALE_BOX: f32 = 0.05;
+const CENTER_BOX: f32 = 0.08;
+const MIN_SCALE: f32 = 0.001;
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.3, 0.85, 0.3, 1.0],
+    [0.25, 0.45, 1.0, 1.0],
+];
+const CENTER_COLOR: [f32; 4] = [0.85, 0.85, 0.85, 1.0];
+const PLANE_ALPHA: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub const ALL: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "translate",
+            GizmoMode::Rotate => "rotate",
+            GizmoMode::Scale => "scale",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoSpace {
+    World,
+    Local,
+}
+
+impl GizmoSpace {
+    pub const ALL: [GizmoSpace; 2] = [GizmoSpace::World, GizmoSpace::Local];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GizmoSpace::World => "world",
+            GizmoSpace::Local => "local",
+        }
+    }
+}
+
+// rotate mode only uses the axis handles (its rings), scale mode the axes and the center
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Handle {
+    Axis(usize),
+    // the plane whose normal is the given axis
+    Plane(usize),
+    Uniform,
+}
+
+impl Handle {
+    fn id(&self) -> i32 {
+        match self {
+            Handle::Axis(axis) => *axis as i32,
+            Handle::Plane(axis) => 3 + *axis as i32,
+            Handle::Uniform => 6,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct VertexGizmo {
+    position: [f32; 3],
+    color: [f32; 4],
+    handle: f32,
+}
+
+glium::implement_vertex!(VertexGizmo, position, color, handle);
+
+struct GizmoGeometry {
+    lines: VertexBuffer<VertexGizmo>,
+    triangles: VertexBuffer<VertexGizmo>,
+}
+
+// where the gizmo sits this frame: world origin, unit world axes and world arm length
+#[derive(Copy, Clone)]
+struct GizmoFrame {
+    object: usize,
+    origin: Point3<f32>,
+    axes: [Vector3<f32>; 3],
+    size: f32,
+}
+
+struct DragState {
+    handle: Handle,
+    frame: GizmoFrame,
+    translation: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    scale: Vector3<f32>,
+    // world matrix of whatever the object's local transform is relative to
+    parent: Matrix4<f32>,
+    // axis parameter, plane hit point or cursor position where the drag started
+    start: Vector3<f32>,
+}
+
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+    // holding ctrl inverts this while dragging
+    pub snap: bool,
+    pub translate_snap: f32,
+    pub rotate_snap_deg: f32,
+    pub scale_snap: f32,
+    program: Program,
+    geometry: [GizmoGeometry; 3],
+    cursor: Option<(f32, f32)>,
+    hovered: Option<Handle>,
+    drag: Option<DragState>,
+}
+
+impl Gizmo {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("shaders/gizmo.vs"),
+            include_str!("shaders/gizmo.fs"),
+            None,
+        )
+        .unwrap();
+
+        Gizmo {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snap: false,
+            translate_snap: 0.1,
+            rotate_snap_deg: 15.0,
+            scale_snap: 0.1,
+            program,
+            geometry: [
+                build_geometry(display, GizmoMode::Translate),
+                build_geometry(display, GizmoMode::Rotate),
+                build_geometry(display, GizmoMode::Scale),
+            ],
+            cursor: None,
+            hovered: None,
+            drag: None,
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    fn frame(&self, camera: &Camera, scene: &Scene) -> Option<GizmoFrame> {
+        let object = scene.selected?;
+        let world = scene.objects.get(object)?.world_matrix();
+        let origin = Point3::new(world.w.x, world.w.y, world.w.z);
+        // scale can't be expressed along world axes, so its handles always follow the object
+        let axes = if self.space == GizmoSpace::Local || self.mode == GizmoMode::Scale {
+            let rotation = orthonormal(world);
+            [rotation.x, rotation.y, rotation.z]
+        } else {
+            [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+        };
+
+        Some(GizmoFrame {
+            object,
+            origin,
+            axes,
+            size: camera.screen_scale(origin, SCREEN_FRACTION),
+        })
+    }
+
+    // returns true when the event was used by the gizmo and shouldn't move the camera
+    pub fn handle_event(
+        &mut self,
+        event: &WindowEvent,
+        camera: &Camera,
+        scene: &mut Scene,
+        window_size: (u32, u32),
+        ctrl: bool,
+        ui_wants_mouse: bool,
+    ) -> bool {
+        let window_size = (window_size.0 as f32, window_size.1 as f32);
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = (position.x as f32, position.y as f32);
+                self.cursor = Some(cursor);
+                if self.drag.is_some() {
+                    let snap = self.snap != ctrl;
+                    self.on_drag(camera, scene, window_size, cursor, snap);
+                } else {
+                    self.hovered = self.frame(camera, scene).and_then(|frame| {
+                        self.pick(camera, &frame, window_size, cursor)
+                            .filter(|_| !ui_wants_mouse)
+                    });
+                }
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.hovered = None;
+                false
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if !ui_wants_mouse => {
+                let (frame, cursor) = match (self.frame(camera, scene), self.cursor) {
+                    (Some(frame), Some(cursor)) => (frame, cursor),
+                    _ => return false,
+                };
+                let handle = match self.pick(camera, &frame, window_size, cursor) {
+                    Some(handle) => handle,
+                    None => return false,
+                };
+                let start = match self.drag_value(camera, &frame, handle, window_size, cursor) {
+                    Some(start) => start,
+                    None => return false,
+                };
+
+                let object = &scene.objects[frame.object];
+                let parent = object.world_matrix()
+                    * object
+                        .local_matrix()
+                        .invert()
+                        .unwrap_or_else(Matrix4::identity);
+                self.drag = Some(DragState {
+                    handle,
+                    frame,
+                    translation: object.translation,
+                    rotation: object.rotation,
+                    scale: object.scale,
+                    parent,
+                    start,
+                });
+                self.hovered = Some(handle);
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => self.drag.take().is_some(),
+            _ => false,
+        }
+    }
+
+    fn pick(
+        &self,
+        camera: &Camera,
+        frame: &GizmoFrame,
+        window_size: (f32, f32),
+        cursor: (f32, f32),
+    ) -> Option<Handle> {
+        let screen = |p: Point3<f32>| to_screen(camera, window_size, p);
+        let arm = |axis: usize, length: f32| frame.origin + frame.axes[axis] * frame.size * length;
+        let mut best: Option<(f32, Handle)> = None;
+        let mut consider = |distance: Option<f32>, handle: Handle| {
+            if let Some(distance) = distance.filter(|d| *d <= PICK_RADIUS_PX) {
+                if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                    best = Some((distance, handle));
+                }
+            }
+        };
+
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                let tip = if self.mode == GizmoMode::Translate {
+                    1.0 + CONE_LENGTH
+                } else {
+                    1.0 + SCALE_BOX
+                };
+                for axis in 0..3 {
+                    let distance = match (screen(frame.origin), screen(arm(axis, tip))) {
+                        (Some(a), Some(b)) => Some(segment_distance(cursor, a, b)),
+                        _ => None,
+                    };
+                    consider(distance, Handle::Axis(axis));
+                }
+            }
+            GizmoMode::Rotate => {
+                for axis in 0..3 {
+                    let (u, v) = (frame.axes[(axis + 1) % 3], frame.axes[(axis + 2) % 3]);
+                    let points = (0..=RING_SEGMENTS)
+                        .map(|k| {
+                            let angle = 2.0 * PI * k as f32 / RING_SEGMENTS as f32;
+                            screen(frame.origin + (u * angle.cos() + v * angle.sin()) * frame.size)
+                        })
+                        .collect::<Vec<_>>();
+                    let distance = points
+                        .windows(2)
+                        .filter_map(|pair| match (pair[0], pair[1]) {
+                            (Some(a), Some(b)) => Some(segment_distance(cursor, a, b)),
+                            _ => None,
+                        })
+                        .reduce(f32::min);
+                    consider(distance, Handle::Axis(axis));
+                }
+            }
+        }
+
+        match self.mode {
+            GizmoMode::Translate => {
+                for axis in 0..3 {
+                    let (u, v) = (frame.axes[(axis + 1) % 3], frame.axes[(axis + 2) % 3]);
+                    let corner =
+                        |a: f32, b: f32| screen(frame.origin + (u * a + v * b) * frame.size);
+                    let quad = [
+                        corner(PLANE_MIN, PLANE_MIN),
+                        corner(PLANE_MAX, PLANE_MIN),
+                        corner(PLANE_MAX, PLANE_MAX),
+                        corner(PLANE_MIN, PLANE_MAX),
+                    ];
+                    if quad.iter().all(Option::is_some) {
+                        let quad = quad.map(Option::unwrap);
+                        if inside_quad(cursor, &quad) {
+                            consider(Some(0.0), Handle::Plane(axis));
+                        }
+                    }
+                }
+            }
+            GizmoMode::Scale => {
+                // the center box wins over the axis lines that start inside it
+                if let Some(center) = screen(frame.origin) {
+                    let distance =
+                        ((cursor.0 - center.0).powi(2) + (cursor.1 - center.1).powi(2)).sqrt();
+                    if distance <= 2.0 * PICK_RADIUS_PX {
+                        consider(Some(-1.0), Handle::Uniform);
+                    }
+                }
+            }
+            GizmoMode::Rotate => {}
+        }
+        best.map(|(_, handle)| handle)
+    }
+
+    // the quantity a drag is measured in: the parameter along an axis, the hit point on a plane or
+    // the cursor position for the uniform scale handle
+    fn drag_value(
+        &self,
+        camera: &Camera,
+        frame: &GizmoFrame,
+        handle: Handle,
+        window_size: (f32, f32),
+        cursor: (f32, f32),
+    ) -> Option<Vector3<f32>> {
+        let ndc_x = cursor.0 / window_size.0 * 2.0 - 1.0;
+        let ndc_y = 1.0 - cursor.1 / window_size.1 * 2.0;
+        let (ray_origin, ray_dir) = camera.screen_ray(ndc_x, ndc_y);
+
+        match (self.mode, handle) {
+            (GizmoMode::Translate | GizmoMode::Scale, Handle::Axis(axis)) => {
+                // closest point on the axis line to the cursor ray
+                let a = frame.axes[axis];
+                let w = frame.origin - ray_origin;
+                let b = a.dot(ray_dir);
+                let denom = 1.0 - b * b;
+                if denom < 1e-4 {
+                    return None;
+                }
+                let s = (b * ray_dir.dot(w) - a.dot(w)) / denom;
+                Some(Vector3::new(s, 0.0, 0.0))
+            }
+            (GizmoMode::Translate | GizmoMode::Rotate, Handle::Plane(axis))
+            | (GizmoMode::Rotate, Handle::Axis(axis)) => {
+                let normal = frame.axes[axis];
+                let facing = normal.dot(ray_dir);
+                if facing.abs() < 1e-4 {
+                    return None;
+                }
+                let t = normal.dot(frame.origin - ray_origin) / facing;
+                Some(ray_origin + ray_dir * t - frame.origin)
+            }
+            (_, Handle::Uniform) => Some(Vector3::new(cursor.0, cursor.1, 0.0)),
+            _ => None,
+        }
+    }
+
+    fn on_drag(
+        &mut self,
+        camera: &Camera,
+        scene: &mut Scene,
+        window_size: (f32, f32),
+        cursor: (f32, f32),
+        snap: bool,
+    ) {
+        let drag = match &self.drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        let frame = drag.frame;
+        let current = match self.drag_value(camera, &frame, drag.handle, window_size, cursor) {
+            Some(current) => current,
+            None => return,
+        };
+        let snapped = |value: f32, step: f32| {
+            if snap && step > 0.0 {
+                (value / step).round() * step
+            } else {
+                value
+            }
+        };
+        let object = match scene.objects.get_mut(frame.object) {
+            Some(object) => object,
+            None => {
+                self.drag = None;
+                return;
+            }
+        };
+
+        match (self.mode, drag.handle) {
+            (GizmoMode::Translate, handle) => {
+                let world_delta = match handle {
+                    Handle::Axis(axis) => {
+                        frame.axes[axis] * snapped(current.x - drag.start.x, self.translate_snap)
+                    }
+                    Handle::Plane(axis) => {
+                        let delta = current - drag.start;
+                        let (u, v) = (frame.axes[(axis + 1) % 3], frame.axes[(axis + 2) % 3]);
+                        u * snapped(delta.dot(u), self.translate_snap)
+                            + v * snapped(delta.dot(v), self.translate_snap)
+                    }
+                    Handle::Uniform => return,
+                };
+                let parent_inverse = drag.parent.invert().unwrap_or_else(Matrix4::identity);
+                object.translation =
+                    drag.translation + parent_inverse.transform_vector(world_delta);
+            }
+            (GizmoMode::Rotate, Handle::Axis(axis)) => {
+                let normal = frame.axes[axis];
+                let (from, to) = (drag.start, current);
+                let angle = Rad(normal.dot(from.cross(to)).atan2(from.dot(to)));
+                let angle = Deg(snapped(Deg::from(angle).0, self.rotate_snap_deg));
+                // rotating about a world axis is rotating about that axis seen from the parent
+                let parent_rotation = Quaternion::from(orthonormal(drag.parent));
+                let parent_axis = (parent_rotation.conjugate() * normal).normalize();
+                object.rotation =
+                    (Quaternion::from_axis_angle(parent_axis, angle) * drag.rotation).normalize();
+            }
+            (GizmoMode::Scale, Handle::Axis(axis)) => {
+                if drag.start.x.abs() < 1e-4 {
+                    return;
+                }
+                let factor = current.x / drag.start.x;
+                let mut scale = drag.scale;
+                scale[axis] = snapped(scale[axis] * factor, self.scale_snap).max(MIN_SCALE);
+                object.scale = scale;
+            }
+            (GizmoMode::Scale, Handle::Uniform) => {
+                let factor = 2f32.powf((current.x - drag.start.x) / UNIFORM_SCALE_PX);
+                let factor = snapped(factor, self.scale_snap).max(MIN_SCALE);
+                object.scale = drag.scale * factor;
+            }
+            _ => {}
+        }
+    }
+
+    // drawn over the finished frame, without depth testing so it is never hidden
+    pub fn draw<S: Surface>(
+        &self,
+        frame: &mut S,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> Result<(), glium::DrawError> {
+        let gizmo = match self.drag.as_ref().map(|drag| drag.frame) {
+            // keep the orientation the drag started with, but follow the moving object
+            Some(drag_frame) => self.frame(camera, scene).map(|current| GizmoFrame {
+                axes: drag_frame.axes,
+                ..current
+            }),
+            None => self.frame(camera, scene),
+        };
+        let gizmo = match gizmo {
+            Some(gizmo) => gizmo,
+            None => return Ok(()),
+        };
+
+        let [x, y, z] = gizmo.axes;
+        let gizmo_matrix = Matrix4::from_translation(gizmo.origin - Point3::new(0.0, 0.0, 0.0))
+            * Matrix4::from_cols(
+                x.extend(0.0),
+                y.extend(0.0),
+                z.extend(0.0),
+                Vector4::unit_w(),
+            )
+            * Matrix4::from_scale(gizmo.size);
+        let active_handle = self
+            .drag
+            .as_ref()
+            .map(|drag| drag.handle)
+            .or(self.hovered)
+            .map_or(-1, |handle| handle.id());
+        let uniforms = camera.add_uniforms(uniform! {
+            gizmo_matrix: Into::<[[f32; 4]; 4]>::into(gizmo_matrix),
+            active_handle: active_handle,
+        });
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            line_width: Some(2.0),
+            ..Default::default()
+        };
+
+        let geometry = &self.geometry[GizmoMode::ALL
+            .iter()
+            .position(|mode| *mode == self.mode)
+            .unwrap()];
+        frame.draw(
+            &geometry.triangles,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.program,
+            &uniforms,
+            &params,
+        )?;
+        frame.draw(
+            &geometry.lines,
+            NoIndices(PrimitiveType::LinesList),
+            &self.program,
+            &uniforms,
+            &params,
+        )
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui, scene: &mut Scene) {
+        imgui::Window::new("Transform").build(ui, || {
+            let mut mode_index = GizmoMode::ALL
+                .iter()
+                .position(|mode| *mode == self.mode)
+                .unwrap();
+            if ui.combo("mode (T/R/Y)", &mut mode_index, &GizmoMode::ALL, |mode| {
+                mode.name().into()
+            }) {
+                self.mode = GizmoMode::ALL[mode_index];
+            }
+            let mut space_index = GizmoSpace::ALL
+                .iter()
+                .position(|space| *space == self.space)
+                .unwrap();
+            if ui.combo("space (L)", &mut space_index, &GizmoSpace::ALL, |space| {
+                space.name().into()
+            }) {
+                self.space = GizmoSpace::ALL[space_index];
+            }
+            ui.checkbox("snap (ctrl inverts)", &mut self.snap);
+            imgui::Slider::new("move step", 0.01, 1.0).build(ui, &mut self.translate_snap);
+            imgui::Slider::new("angle step", 1.0, 90.0).build(ui, &mut self.rotate_snap_deg);
+            imgui::Slider::new("scale step", 0.01, 1.0).build(ui, &mut self.scale_snap);
+
+            let object = match scene.selected_object_mut() {
+                Some(object) => object,
+                None => {
+                    ui.text("no object selected");
+                    return;
+                }
+            };
+            ui.separator();
+            ui.text(&object.name);
+
+            let mut translation: [f32; 3] = object.translation.into();
+            if imgui::Drag::new("translation")
+                .speed(0.01)
+                .build_array(ui, &mut translation)
+            {
+                object.translation = translation.into();
+            }
+            let euler = Euler::from(object.rotation);
+            let mut rotation = [
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ];
+            if imgui::Drag::new("rotation")
+                .speed(0.5)
+                .build_array(ui, &mut rotation)
+            {
+                object.rotation = Quaternion::from(Euler::new(
+                    Deg(rotation[0]),
+                    Deg(rotation[1]),
+                    Deg(rotation[2]),
+                ));
+            }
+            let mut scale: [f32; 3] = object.scale.into();
+            if imgui::Drag::new("scale")
+                .speed(0.01)
+                .build_array(ui, &mut scale)
+            {
+                object.scale = scale.into();
+            }
+
+            let world = object.world_matrix();
+            ui.text(format!(
+                "world position: {:.3} {:.3} {:.3}",
+                world.w.x, world.w.y, world.w.z
+            ));
+        });
+    }
+}
+
+// rotation part of an affine matrix with the scale (and any shear) removed
+fn orthonormal(matrix: Matrix4<f32>) -> Matrix3<f32> {
+    let x = matrix.x.truncate().normalize();
+    let y = (matrix.y.truncate() - x * x.dot(matrix.y.truncate())).normalize();
+    let z = x.cross(y);
+    let z = if z.dot(matrix.z.truncate()) < 0.0 {
+        -z
+    } else {
+        z
+    };
+    let rotation = Matrix3::from_cols(x, y, z);
+    if rotation.x.x.is_finite() && rotation.y.y.is_finite() && rotation.z.z.is_finite() {
+        rotation
+    } else {
+        Matrix3::identity()
+    }
+}
+
+// window pixel position of a world point, top-left origin like the cursor; None behind the camera
+fn to_screen(camera: &Camera, window_size: (f32, f32), point: Point3<f32>) -> Option<(f32, f32)> {
+    let clip = camera.projection_matrix() * camera.view_matrix() * point.to_homogeneous();
+    if clip.w <= 1e-6 {
+        return None;
+    }
+    Some((
+        (clip.x / clip.w * 0.5 + 0.5) * window_size.0,
+        (0.5 - clip.y / clip.w * 0.5) * window_size.1,
+    ))
+}
+
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let (apx, apy) = (p.0 - a.0, p.1 - a.1);
+    let length2 = abx * abx + aby * aby;
+    let t = if length2 > 0.0 {
+        ((apx * abx + apy * aby) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    ((apx - abx * t).powi(2) + (apy - aby * t).powi(2)).sqrt()
+}
+
+// works for either winding since the projected quad may be mirrored
+fn inside_quad(p: (f32, f32), quad: &[(f32, f32); 4]) -> bool {
+    let sides = (0..4)
+        .map(|i| {
+            let (a, b) = (quad[i], quad[(i + 1) % 4]);
+            (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+        })
+        .collect::<Vec<_>>();
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
+
+fn build_geometry(display: &Display, mode: GizmoMode) -> GizmoGeometry {
+    let mut lines = Vec::new();
+    let mut triangles = Vec::new();
+    let vertex = |position: Vector3<f32>, color: [f32; 4], handle: Handle| VertexGizmo {
+        position: position.into(),
+        color,
+        handle: handle.id() as f32,
+    };
+    let basis = |axis: usize| {
+        let mut unit = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+        unit.rotate_left(axis);
+        (unit[0], unit[1], unit[2])
+    };
+
+    for (axis, &color) in AXIS_COLORS.iter().enumerate() {
+        let (a, u, v) = basis(axis);
+        let handle = Handle::Axis(axis);
+        match mode {
+            GizmoMode::Translate => {
+                lines.push(vertex(Vector3::new(0.0, 0.0, 0.0), color, handle));
+                lines.push(vertex(a, color, handle));
+                let tip = a * (1.0 + CONE_LENGTH);
+                for k in 0..CONE_SEGMENTS {
+                    let angle = |k: usize| 2.0 * PI * k as f32 / CONE_SEGMENTS as f32;
+                    let rim = |angle: f32| a + (u * angle.cos() + v * angle.sin()) * CONE_RADIUS;
+                    triangles.push(vertex(tip, color, handle));
+                    triangles.push(vertex(rim(angle(k)), color, handle));
+                    triangles.push(vertex(rim(angle(k + 1)), color, handle));
+                }
+
+                let plane_color = [color[0], color[1], color[2], PLANE_ALPHA];
+                let plane = Handle::Plane(axis);
+                let corner = |s: f32, t: f32| u * s + v * t;
+                for (s, t) in [
+                    (PLANE_MIN, PLANE_MIN),
+                    (PLANE_MAX, PLANE_MIN),
+                    (PLANE_MAX, PLANE_MAX),
+                    (PLANE_MIN, PLANE_MIN),
+                    (PLANE_MAX, PLANE_MAX),
+                    (PLANE_MIN, PLANE_MAX),
+                ] {
+                    triangles.push(vertex(corner(s, t), plane_color, plane));
+                }
+            }
+            GizmoMode::Rotate => {
+                for k in 0..RING_SEGMENTS {
+                    for k in [k, k + 1] {
+                        let angle = 2.0 * PI * k as f32 / RING_SEGMENTS as f32;
+                        lines.push(vertex(u * angle.cos() + v * angle.sin(), color, handle));
+                    }
+                }
+            }
+            GizmoMode::Scale => {
+                lines.push(vertex(Vector3::new(0.0, 0.0, 0.0), color, handle));
+                lines.push(vertex(a, color, handle));
+                push_box(&mut triangles, a, SCALE_BOX, color, handle.id());
+            }
+        }
+    }
+    if mode == GizmoMode::Scale {
+        push_box(
+            &mut triangles,
+            Vector3::new(0.0, 0.0, 0.0),
+            CENTER_BOX,
+            CENTER_COLOR,
+            Handle::Uniform.id(),
+        );
+    }
+
+    GizmoGeometry {
+        lines: VertexBuffer::new(display, &lines).unwrap(),
+        triangles: VertexBuffer::new(display, &triangles).unwrap(),
+    }
+}
+
+fn push_box(
+    triangles: &mut Vec<VertexGizmo>,
+    center: Vector3<f32>,
+    half: f32,
+    color: [f32; 4],
+    handle: i32,
+) {
+    let corner = |i: usize| {
+        let sign = |bit: usize| if i & bit != 0 { half } else { -half };
+        center + Vector3::new(sign(1), sign(2), sign(4))
+    };
+    // two triangles per face, corners indexed by their x/y/z bits
+    const FACES: [[usize; 4]; 6] = [
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+    ];
+    for face in FACES {
+        for i in [0, 1, 2, 0, 2, 3] {
+            triangles.push(VertexGizmo {
+                position: corner(face[i]).into(),
+                color,
+                handle: handle as f32,
+            });
+        }
+    }
+}
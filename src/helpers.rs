@@ -0,0 +1,472 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use glium::{
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    index::{NoIndices, PrimitiveType},
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, UncompressedFloatFormat},
+    uniform,
+    uniforms::{DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter},
+    Blend, Depth, DepthTest, Display, DrawParameters, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::{
+    camera::Camera,
+    environment::{fullscreen_triangle, VertexP2},
+    light::{DirectionalLight, PointLight, SpotLight},
+    scene::Scene,
+};
+
+const SHADOW_MAP_SIZE: u32 = 2048;
+// the axes are drawn this far out, the fade hides where they end
+const AXIS_LENGTH: f32 = 1000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowCaster {
+    Directional,
+    Spot,
+}
+
+impl ShadowCaster {
+    pub const ALL: [ShadowCaster; 2] = [ShadowCaster::Directional, ShadowCaster::Spot];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadowCaster::Directional => "directional light",
+            ShadowCaster::Spot => "spot light",
+        }
+    }
+}
+
+pub struct HelperSettings {
+    pub show_grid: bool,
+    pub grid_spacing: f32,
+    pub show_axes: bool,
+    pub show_orientation: bool,
+    pub show_ground: bool,
+    pub ground_color: [f32; 3],
+    // height of both the grid and the ground plane
+    pub plane_height: f32,
+    // horizontal distance from the camera at which grid, axes and ground have faded out
+    pub fade_distance: f32,
+    pub shadows: bool,
+    pub shadow_caster: ShadowCaster,
+    pub shadow_strength: f32,
+    pub reflectivity: f32,
+}
+
+impl HelperSettings {
+    pub fn new() -> Self {
+        HelperSettings {
+            show_grid: true,
+            grid_spacing: 0.5,
+            show_axes: true,
+            show_orientation: true,
+            show_ground: false,
+            ground_color: [0.5, 0.5, 0.5],
+            plane_height: 0.0,
+            fade_distance: 50.0,
+            shadows: true,
+            shadow_caster: ShadowCaster::Spot,
+            shadow_strength: 0.8,
+            reflectivity: 0.2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct VertexPC {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+glium::implement_vertex!(VertexPC, position, color);
+
+const AXIS_COLORS: [[f32; 3]; 3] = [[0.9, 0.2, 0.2], [0.3, 0.85, 0.3], [0.25, 0.4, 0.95]];
+
+// viewport aids: a fading grid, the world axes, a ground plane with shadows and reflections and
+// an orientation widget in the corner
+pub struct Helpers {
+    pub settings: HelperSettings,
+    grid_program: Program,
+    ground_program: Program,
+    axes_program: Program,
+    shadow_program: Program,
+    fullscreen: VertexBuffer<VertexP2>,
+    axes: VertexBuffer<VertexPC>,
+    shadow_map: DepthTexture2d,
+    // world to the caster light's clip space, None while the ground receives no shadows
+    shadow_matrix: Option<Matrix4<f32>>,
+    reflection: Texture2d,
+    reflection_depth: DepthRenderBuffer,
+}
+
+impl Helpers {
+    pub fn new(display: &Display) -> Self {
+        let fullscreen_vs = include_str!("shaders/fullscreen.vs");
+        let grid_program = Program::from_source(
+            display,
+            fullscreen_vs,
+            include_str!("shaders/grid.fs"),
+            None,
+        )
+        .unwrap();
+        let ground_program = Program::from_source(
+            display,
+            fullscreen_vs,
+            include_str!("shaders/ground.fs"),
+            None,
+        )
+        .unwrap();
+        let axes_program = Program::from_source(
+            display,
+            include_str!("shaders/axes.vs"),
+            include_str!("shaders/axes.fs"),
+            None,
+        )
+        .unwrap();
+        let shadow_program = Program::from_source(
+            display,
+            include_str!("shaders/shadow_depth.vs"),
+            include_str!("shaders/shadow_depth.fs"),
+            None,
+        )
+        .unwrap();
+
+        let mut axes = Vec::new();
+        for (axis, color) in AXIS_COLORS.iter().enumerate() {
+            for sign in [-1.0, 1.0] {
+                let mut position = [0.0; 3];
+                position[axis] = sign * AXIS_LENGTH;
+                axes.push(VertexPC {
+                    position,
+                    color: *color,
+                });
+            }
+        }
+        let (reflection, reflection_depth) = Self::create_reflection(display, 1, 1);
+
+        Helpers {
+            settings: HelperSettings::new(),
+            grid_program,
+            ground_program,
+            axes_program,
+            shadow_program,
+            fullscreen: fullscreen_triangle(display),
+            axes: VertexBuffer::new(display, &axes).unwrap(),
+            shadow_map: DepthTexture2d::empty_with_format(
+                display,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                SHADOW_MAP_SIZE,
+                SHADOW_MAP_SIZE,
+            )
+            .unwrap(),
+            shadow_matrix: None,
+            reflection,
+            reflection_depth,
+        }
+    }
+
+    fn create_reflection(
+        display: &Display,
+        width: u32,
+        height: u32,
+    ) -> (Texture2d, DepthRenderBuffer) {
+        (
+            Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::F16F16F16F16,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .unwrap(),
+            DepthRenderBuffer::new(display, DepthFormat::F32, width, height).unwrap(),
+        )
+    }
+
+    pub fn wants_reflection(&self) -> bool {
+        self.settings.show_ground && self.settings.reflectivity > 0.0
+    }
+
+    // the camera to render the reflection with, see `reflection_target`
+    pub fn reflection_camera(&self, camera: &Camera) -> Camera {
+        camera.mirrored(self.settings.plane_height)
+    }
+
+    // where the scene seen from `reflection_camera` goes, at the size of the scene target
+    pub fn reflection_target(
+        &mut self,
+        display: &Display,
+        width: u32,
+        height: u32,
+    ) -> SimpleFrameBuffer<'_> {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.reflection.dimensions() != (width, height) {
+            let (reflection, reflection_depth) = Self::create_reflection(display, width, height);
+            self.reflection = reflection;
+            self.reflection_depth = reflection_depth;
+        }
+        SimpleFrameBuffer::with_depth_buffer(display, &self.reflection, &self.reflection_depth)
+            .unwrap()
+    }
+
+    // renders the depth of the visible objects as seen from the shadow caster; must run after the
+    // world matrices are updated
+    pub fn update_shadow_map(
+        &mut self,
+        display: &Display,
+        scene: &Scene,
+        spot_light: &SpotLight,
+        dir_light: &DirectionalLight,
+    ) -> Result<(), glium::DrawError> {
+        let sphere = scene.bounding_sphere();
+        self.shadow_matrix = match sphere {
+            Some((center, radius)) if self.settings.show_ground && self.settings.shadows => {
+                Some(match self.settings.shadow_caster {
+                    ShadowCaster::Directional => {
+                        directional_shadow_matrix(dir_light.direction(), center, radius)
+                    }
+                    ShadowCaster::Spot => spot_shadow_matrix(spot_light, center, radius),
+                })
+            }
+            _ => None,
+        };
+        let light_view_projection = match self.shadow_matrix {
+            Some(matrix) => matrix,
+            None => return Ok(()),
+        };
+
+        let mut target = SimpleFrameBuffer::depth_only(display, &self.shadow_map).unwrap();
+        target.clear_depth(1.0);
+        let uniforms = uniform! {
+            light_view_projection: <Matrix4<f32> as Into<[[f32; 4]; 4]>>::into(light_view_projection),
+        };
+        for object in scene.visible_objects() {
+            object.draw(
+                &mut target,
+                &self.shadow_program,
+                object.add_uniforms(uniforms),
+            )?;
+        }
+        Ok(())
+    }
+
+    // draws the ground, grid and axes into the scene target after the scene itself, so models
+    // occlude them through the depth buffer
+    pub fn draw<S: Surface>(
+        &self,
+        frame: &mut S,
+        camera: &Camera,
+        point_light: &PointLight,
+        spot_light: &SpotLight,
+        dir_light: &DirectionalLight,
+        ambient_light: [f32; 3],
+    ) -> Result<(), glium::DrawError> {
+        let settings = &self.settings;
+        let inv_view_projection: [[f32; 4]; 4] = (camera.projection_matrix()
+            * camera.view_matrix())
+        .invert()
+        .unwrap_or_else(Matrix4::identity)
+        .into();
+        let blended = |depth_write| DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: depth_write,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        if settings.show_ground {
+            let shadow_sampler = self
+                .shadow_map
+                .sampled()
+                .depth_texture_comparison(Some(DepthTextureComparison::LessOrEqual))
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear);
+            let shadow_caster = match (self.shadow_matrix, settings.shadow_caster) {
+                (None, _) => 0,
+                (Some(_), ShadowCaster::Directional) => 1,
+                (Some(_), ShadowCaster::Spot) => 2,
+            };
+            let shadow_matrix: [[f32; 4]; 4] =
+                self.shadow_matrix.unwrap_or_else(Matrix4::identity).into();
+            let uniforms = camera.add_uniforms(uniform! {
+                inv_view_projection: inv_view_projection,
+                plane_height: settings.plane_height,
+                ground_color: settings.ground_color,
+                fade_distance: settings.fade_distance,
+                ambient_light: ambient_light,
+                shadow_caster: shadow_caster,
+                shadow_matrix: shadow_matrix,
+                shadow_map: shadow_sampler,
+                shadow_strength: settings.shadow_strength,
+                shadow_bias: 0.002f32,
+                reflection_enabled: self.wants_reflection(),
+                reflection_map: self.reflection.sampled(),
+                reflectivity: settings.reflectivity,
+            });
+            frame.draw(
+                &self.fullscreen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.ground_program,
+                &dir_light
+                    .add_uniforms(spot_light.add_uniforms(point_light.add_uniforms(uniforms))),
+                &blended(true),
+            )?;
+        }
+
+        if settings.show_grid {
+            frame.draw(
+                &self.fullscreen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.grid_program,
+                &camera.add_uniforms(uniform! {
+                    inv_view_projection: inv_view_projection,
+                    plane_height: settings.plane_height,
+                    grid_spacing: settings.grid_spacing,
+                    grid_color: [0.6f32, 0.6, 0.6],
+                    fade_distance: settings.fade_distance,
+                }),
+                &blended(false),
+            )?;
+        }
+
+        if settings.show_axes {
+            frame.draw(
+                &self.axes,
+                NoIndices(PrimitiveType::LinesList),
+                &self.axes_program,
+                &camera.add_uniforms(uniform! {
+                    fade_distance: settings.fade_distance,
+                }),
+                &DrawParameters {
+                    line_width: Some(2.0),
+                    ..blended(false)
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    // a small axis tripod in the bottom left corner turning with the camera, drawn behind the
+    // windows of the ui
+    pub fn draw_orientation(&self, ui: &imgui::Ui, camera: &Camera) {
+        if !self.settings.show_orientation {
+            return;
+        }
+        let size = 36.0;
+        let [_, display_height] = ui.io().display_size;
+        let center = [12.0 + size, display_height - 12.0 - size];
+        let view = camera.view_matrix();
+
+        // farthest first, so the axes pointing at the viewer end up on top
+        let mut ends: Vec<(usize, f32, Vector4<f32>)> = (0..3)
+            .flat_map(|axis| [(axis, 1.0), (axis, -1.0)])
+            .map(|(axis, sign)| {
+                let mut direction = Vector4::new(0.0, 0.0, 0.0, 0.0);
+                direction[axis] = sign;
+                (axis, sign, view * direction)
+            })
+            .collect();
+        ends.sort_by(|a, b| a.2.z.partial_cmp(&b.2.z).unwrap());
+
+        let draw_list = ui.get_background_draw_list();
+        draw_list
+            .add_circle(center, size + 10.0, [0.1, 0.1, 0.1, 0.35])
+            .filled(true)
+            .build();
+        for (axis, sign, direction) in ends {
+            let [r, g, b] = AXIS_COLORS[axis];
+            let end = [
+                center[0] + direction.x * size,
+                center[1] - direction.y * size,
+            ];
+            if sign > 0.0 {
+                draw_list
+                    .add_line(center, end, [r, g, b, 1.0])
+                    .thickness(2.0)
+                    .build();
+                draw_list
+                    .add_circle(end, 7.0, [r, g, b, 1.0])
+                    .filled(true)
+                    .build();
+                let label = ["X", "Y", "Z"][axis];
+                draw_list.add_text([end[0] - 3.5, end[1] - 7.0], [0.0, 0.0, 0.0, 1.0], label);
+            } else {
+                draw_list
+                    .add_circle(end, 5.0, [r, g, b, 0.6])
+                    .thickness(1.5)
+                    .build();
+            }
+        }
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui) {
+        let settings = &mut self.settings;
+        imgui::Window::new("Helpers").build(ui, || {
+            ui.checkbox("grid (g)", &mut settings.show_grid);
+            imgui::Slider::new("grid spacing", 0.01, 10.0).build(ui, &mut settings.grid_spacing);
+            ui.checkbox("world axes", &mut settings.show_axes);
+            ui.checkbox("orientation widget", &mut settings.show_orientation);
+            imgui::Slider::new("fade distance", 1.0, 500.0).build(ui, &mut settings.fade_distance);
+            imgui::Drag::new("plane height")
+                .speed(0.01)
+                .build(ui, &mut settings.plane_height);
+
+            ui.separator();
+            ui.checkbox("ground plane", &mut settings.show_ground);
+            imgui::ColorEdit::new("ground color", &mut settings.ground_color).build(ui);
+            ui.checkbox("shadows", &mut settings.shadows);
+            let mut caster_index = ShadowCaster::ALL
+                .iter()
+                .position(|caster| *caster == settings.shadow_caster)
+                .unwrap();
+            if ui.combo(
+                "shadow caster",
+                &mut caster_index,
+                &ShadowCaster::ALL,
+                |caster| caster.name().into(),
+            ) {
+                settings.shadow_caster = ShadowCaster::ALL[caster_index];
+            }
+            imgui::Slider::new("shadow strength", 0.0, 1.0)
+                .build(ui, &mut settings.shadow_strength);
+            imgui::Slider::new("reflectivity", 0.0, 1.0).build(ui, &mut settings.reflectivity);
+        });
+    }
+}
+
+fn up_for(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+// an orthographic box around the scene's bounding sphere, long enough to reach the ground behind
+// it even for low lights
+fn directional_shadow_matrix(
+    direction: Vector3<f32>,
+    center: Point3<f32>,
+    radius: f32,
+) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    let eye = center - direction * 2.0 * radius;
+    let view = Matrix4::look_to_rh(eye, direction, up_for(direction));
+    let projection = cgmath::ortho(-radius, radius, -radius, radius, 0.0, 20.0 * radius);
+    projection * view
+}
+
+// a frustum along the spot light's cone
+fn spot_shadow_matrix(spot_light: &SpotLight, center: Point3<f32>, radius: f32) -> Matrix4<f32> {
+    let eye = Point3::from_vec(spot_light.point_light.position);
+    let direction = spot_light.direction();
+    let view = Matrix4::look_to_rh(eye, direction, up_for(direction));
+    let fovy = (2.0 * spot_light.total_width_deg()).clamp(1.0, 170.0);
+    let far = ((center - eye).magnitude() + radius) * 10.0;
+    let projection = cgmath::perspective(cgmath::Deg(fovy), 1.0, 0.05, far.max(1.0));
+    projection * view
+}
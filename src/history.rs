@@ -0,0 +1,1047 @@
+use cgmath::{Quaternion, Vector3};
+
+use crate::{
+    instance::Instance,
+    light::{DirectionalLight, PointLight, SpotLight},
+    mesh::{DiffuseMap, MaterialParams, TriangleMesh},
+    scene_file::SceneState,
+    scene_obj::SceneObject,
+};
+
+// oldest entries are dropped past this, along with any objects they kept for undoing a removal
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    translation: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    scale: Vector3<f32>,
+    parent: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Visibility {
+    visible: bool,
+    // visible and solo flags of every submesh
+    submeshes: Vec<(bool, bool)>,
+}
+
+// what undo covers of one object, cheap enough to capture every frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectSnapshot {
+    transform: Transform,
+    visibility: Visibility,
+    materials: Vec<MaterialParams>,
+    instances: Vec<Instance>,
+}
+
+impl ObjectSnapshot {
+    fn capture(object: &SceneObject) -> Self {
+        let mesh = object.mesh();
+        ObjectSnapshot {
+            transform: Transform {
+                translation: object.translation,
+                rotation: object.rotation,
+                scale: object.scale,
+                parent: object.parent,
+            },
+            visibility: Visibility {
+                visible: object.visible,
+                submeshes: mesh
+                    .submeshes()
+                    .iter()
+                    .map(|submesh| (submesh.visible, submesh.solo))
+                    .collect(),
+            },
+            materials: mesh
+                .submeshes()
+                .iter()
+                .map(|submesh| submesh.material().params())
+                .collect(),
+            instances: object.instances().to_vec(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lights {
+    point: PointLight,
+    spot: SpotLight,
+    directional: DirectionalLight,
+    ambient: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    objects: Vec<ObjectSnapshot>,
+    lights: Lights,
+}
+
+// an object as the history holds it while it is out of the document
+pub trait DocumentObject {
+    // swapped in and out whole when an object gets another model or a submesh another texture
+    type Mesh;
+    type DiffuseMap;
+
+    fn name(&self) -> &str;
+}
+
+// what the history reads and changes; the scene implements it, the tests use plain data
+pub trait Document {
+    type Object: DocumentObject;
+
+    fn snapshot(&self) -> Snapshot;
+    fn object_name(&self, index: usize) -> Option<&str>;
+    fn object_count(&self) -> usize;
+    fn set_transform(&mut self, object: usize, transform: &Transform);
+    fn set_parent(&mut self, object: usize, parent: Option<usize>);
+    fn set_visibility(&mut self, object: usize, visibility: &Visibility);
+    fn set_material(&mut self, object: usize, submesh: usize, params: MaterialParams);
+    fn set_instances(&mut self, object: usize, instances: Vec<Instance>);
+    fn lights(&self) -> Lights;
+    fn set_lights(&mut self, lights: Lights);
+    // both fix up the parent indices of the other objects
+    fn insert_object(&mut self, index: usize, object: Self::Object);
+    fn remove_object(&mut self, index: usize) -> Self::Object;
+    fn swap_mesh(&mut self, object: usize, mesh: &mut <Self::Object as DocumentObject>::Mesh);
+    fn swap_diffuse_map(
+        &mut self,
+        object: usize,
+        submesh: usize,
+        map: &mut <Self::Object as DocumentObject>::DiffuseMap,
+    );
+}
+
+impl DocumentObject for SceneObject {
+    type Mesh = TriangleMesh;
+    type DiffuseMap = DiffuseMap;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Document for SceneState<'_> {
+    type Object = SceneObject;
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            objects: self
+                .scene
+                .objects
+                .iter()
+                .map(ObjectSnapshot::capture)
+                .collect(),
+            lights: self.lights(),
+        }
+    }
+
+    fn object_name(&self, index: usize) -> Option<&str> {
+        self.scene.objects.get(index).map(|object| &object.name[..])
+    }
+
+    fn object_count(&self) -> usize {
+        self.scene.objects.len()
+    }
+
+    fn set_transform(&mut self, object: usize, transform: &Transform) {
+        if let Some(object) = self.scene.objects.get_mut(object) {
+            object.translation = transform.translation;
+            object.rotation = transform.rotation;
+            object.scale = transform.scale;
+            object.parent = transform.parent;
+        }
+    }
+
+    fn set_parent(&mut self, object: usize, parent: Option<usize>) {
+        if let Some(object) = self.scene.objects.get_mut(object) {
+            object.parent = parent;
+        }
+    }
+
+    fn set_visibility(&mut self, object: usize, visibility: &Visibility) {
+        if let Some(object) = self.scene.objects.get_mut(object) {
+            object.visible = visibility.visible;
+            let submeshes = object.mesh_mut().submeshes_mut();
+            for (submesh, (visible, solo)) in submeshes.iter_mut().zip(&visibility.submeshes) {
+                submesh.visible = *visible;
+                submesh.solo = *solo;
+            }
+        }
+    }
+
+    fn set_material(&mut self, object: usize, submesh: usize, params: MaterialParams) {
+        if let Some(submesh) = self
+            .scene
+            .objects
+            .get_mut(object)
+            .and_then(|object| object.mesh_mut().submeshes_mut().get_mut(submesh))
+        {
+            submesh.set_material_params(params);
+        }
+    }
+
+    fn set_instances(&mut self, object: usize, instances: Vec<Instance>) {
+        if let Some(object) = self.scene.objects.get_mut(object) {
+            object.set_instances(instances);
+        }
+    }
+
+    fn lights(&self) -> Lights {
+        Lights {
+            point: *self.point_light,
+            spot: *self.spot_light,
+            directional: *self.dir_light,
+            ambient: *self.ambient_light,
+        }
+    }
+
+    fn set_lights(&mut self, lights: Lights) {
+        *self.point_light = lights.point;
+        *self.spot_light = lights.spot;
+        *self.dir_light = lights.directional;
+        *self.ambient_light = lights.ambient;
+    }
+
+    fn insert_object(&mut self, index: usize, object: SceneObject) {
+        self.scene.insert(index, object);
+    }
+
+    fn remove_object(&mut self, index: usize) -> SceneObject {
+        self.scene.remove(index)
+    }
+
+    fn swap_mesh(&mut self, object: usize, mesh: &mut TriangleMesh) {
+        if let Some(object) = self.scene.objects.get_mut(object) {
+            object.swap_mesh(mesh);
+        }
+    }
+
+    fn swap_diffuse_map(&mut self, object: usize, submesh: usize, map: &mut DiffuseMap) {
+        if let Some(submesh) = self
+            .scene
+            .objects
+            .get_mut(object)
+            .and_then(|object| object.mesh_mut().submeshes_mut().get_mut(submesh))
+        {
+            submesh.swap_diffuse_map(map);
+        }
+    }
+}
+
+// one reversible change; object indices are valid at the point in the history the edit sits at
+enum Edit<O: DocumentObject> {
+    Transform {
+        object: usize,
+        before: Transform,
+        after: Transform,
+    },
+    Visibility {
+        object: usize,
+        before: Visibility,
+        after: Visibility,
+    },
+    Material {
+        object: usize,
+        submesh: usize,
+        before: MaterialParams,
+        after: MaterialParams,
+    },
+    Instances {
+        object: usize,
+        before: Vec<Instance>,
+        after: Vec<Instance>,
+    },
+    PointLight {
+        before: PointLight,
+        after: PointLight,
+    },
+    SpotLight {
+        before: SpotLight,
+        after: SpotLight,
+    },
+    DirectionalLight {
+        before: DirectionalLight,
+        after: DirectionalLight,
+    },
+    Ambient {
+        before: [f32; 3],
+        after: [f32; 3],
+    },
+    // the object is held here while the edit is undone
+    AddObject {
+        index: usize,
+        object: Option<O>,
+    },
+    // the object is held here while the edit is done; `children` were parented to it
+    RemoveObject {
+        index: usize,
+        object: Option<O>,
+        children: Vec<usize>,
+    },
+    // holds whichever mesh the object doesn't have at the moment
+    ReplaceMesh {
+        object: usize,
+        mesh: O::Mesh,
+    },
+    // holds whichever map the submesh doesn't have at the moment
+    DiffuseMap {
+        object: usize,
+        submesh: usize,
+        map: O::DiffuseMap,
+    },
+}
+
+impl<O: DocumentObject> Edit<O> {
+    // true if both edits change the same thing, so a drag can fold the second into the first
+    fn same_target(&self, other: &Edit<O>) -> bool {
+        match (self, other) {
+            (Edit::Transform { object: a, .. }, Edit::Transform { object: b, .. })
+            | (Edit::Visibility { object: a, .. }, Edit::Visibility { object: b, .. })
+            | (Edit::Instances { object: a, .. }, Edit::Instances { object: b, .. }) => a == b,
+            (
+                Edit::Material {
+                    object: a,
+                    submesh: i,
+                    ..
+                },
+                Edit::Material {
+                    object: b,
+                    submesh: j,
+                    ..
+                },
+            ) => a == b && i == j,
+            (Edit::PointLight { .. }, Edit::PointLight { .. })
+            | (Edit::SpotLight { .. }, Edit::SpotLight { .. })
+            | (Edit::DirectionalLight { .. }, Edit::DirectionalLight { .. })
+            | (Edit::Ambient { .. }, Edit::Ambient { .. }) => true,
+            _ => false,
+        }
+    }
+
+    // takes the `after` of a later edit to the same target
+    fn merge(&mut self, later: Edit<O>) {
+        match (self, later) {
+            (Edit::Transform { after, .. }, Edit::Transform { after: later, .. }) => *after = later,
+            (Edit::Visibility { after, .. }, Edit::Visibility { after: later, .. }) => {
+                *after = later
+            }
+            (Edit::Material { after, .. }, Edit::Material { after: later, .. }) => *after = later,
+            (Edit::Instances { after, .. }, Edit::Instances { after: later, .. }) => *after = later,
+            (Edit::PointLight { after, .. }, Edit::PointLight { after: later, .. }) => {
+                *after = later
+            }
+            (Edit::SpotLight { after, .. }, Edit::SpotLight { after: later, .. }) => *after = later,
+            (Edit::DirectionalLight { after, .. }, Edit::DirectionalLight { after: later, .. }) => {
+                *after = later
+            }
+            (Edit::Ambient { after, .. }, Edit::Ambient { after: later, .. }) => *after = later,
+            _ => {}
+        }
+    }
+
+    fn label<D: Document<Object = O>>(&self, document: &D) -> String {
+        let name = |index: usize| {
+            document
+                .object_name(index)
+                .map_or_else(|| format!("object {}", index), String::from)
+        };
+        match self {
+            Edit::Transform { object, .. } => format!("transform {}", name(*object)),
+            Edit::Visibility { object, .. } => format!("visibility of {}", name(*object)),
+            Edit::Material {
+                object, submesh, ..
+            } => format!("material of {} submesh {}", name(*object), submesh),
+            Edit::Instances { object, .. } => format!("instances of {}", name(*object)),
+            Edit::PointLight { .. } => String::from("point light"),
+            Edit::SpotLight { .. } => String::from("spot light"),
+            Edit::DirectionalLight { .. } => String::from("directional light"),
+            Edit::Ambient { .. } => String::from("ambient light"),
+            Edit::AddObject { index, .. } => format!("add {}", name(*index)),
+            Edit::RemoveObject { object, .. } => format!(
+                "remove {}",
+                object.as_ref().map_or("object", |object| object.name())
+            ),
+            Edit::ReplaceMesh { object, .. } => format!("change model of {}", name(*object)),
+            Edit::DiffuseMap {
+                object, submesh, ..
+            } => format!("diffuse map of {} submesh {}", name(*object), submesh),
+        }
+    }
+
+    fn apply<D: Document<Object = O>>(&mut self, document: &mut D, forward: bool) {
+        match self {
+            Edit::Transform {
+                object,
+                before,
+                after,
+            } => document.set_transform(*object, pick(forward, before, after)),
+            Edit::Visibility {
+                object,
+                before,
+                after,
+            } => document.set_visibility(*object, pick(forward, before, after)),
+            Edit::Material {
+                object,
+                submesh,
+                before,
+                after,
+            } => document.set_material(*object, *submesh, *pick(forward, before, after)),
+            Edit::Instances {
+                object,
+                before,
+                after,
+            } => document.set_instances(*object, pick(forward, before, after).clone()),
+            Edit::PointLight { before, after } => {
+                let mut lights = document.lights();
+                lights.point = *pick(forward, before, after);
+                document.set_lights(lights);
+            }
+            Edit::SpotLight { before, after } => {
+                let mut lights = document.lights();
+                lights.spot = *pick(forward, before, after);
+                document.set_lights(lights);
+            }
+            Edit::DirectionalLight { before, after } => {
+                let mut lights = document.lights();
+                lights.directional = *pick(forward, before, after);
+                document.set_lights(lights);
+            }
+            Edit::Ambient { before, after } => {
+                let mut lights = document.lights();
+                lights.ambient = *pick(forward, before, after);
+                document.set_lights(lights);
+            }
+            Edit::AddObject { index, object } => {
+                if forward {
+                    if let Some(object) = object.take() {
+                        document.insert_object(*index, object);
+                    }
+                } else if *index < document.object_count() {
+                    *object = Some(document.remove_object(*index));
+                }
+            }
+            Edit::RemoveObject {
+                index,
+                object,
+                children,
+            } => {
+                if forward {
+                    if *index < document.object_count() {
+                        *object = Some(document.remove_object(*index));
+                    }
+                } else if let Some(object) = object.take() {
+                    document.insert_object(*index, object);
+                    for child in children.iter() {
+                        document.set_parent(*child, Some(*index));
+                    }
+                }
+            }
+            // the same swap both ways
+            Edit::ReplaceMesh { object, mesh } => document.swap_mesh(*object, mesh),
+            Edit::DiffuseMap {
+                object,
+                submesh,
+                map,
+            } => document.swap_diffuse_map(*object, *submesh, map),
+        }
+    }
+}
+
+fn pick<T>(forward: bool, before: T, after: T) -> T {
+    if forward {
+        after
+    } else {
+        before
+    }
+}
+
+// every change between two snapshots of the same objects
+fn diff<O: DocumentObject>(before: &Snapshot, after: &Snapshot) -> Vec<Edit<O>> {
+    let mut edits = Vec::new();
+    for (object, (old, new)) in before.objects.iter().zip(&after.objects).enumerate() {
+        if old.transform != new.transform {
+            edits.push(Edit::Transform {
+                object,
+                before: old.transform,
+                after: new.transform,
+            });
+        }
+        // a mesh replaced behind the history's back has other submeshes, its old flags and
+        // materials don't apply
+        if old.materials.len() != new.materials.len() {
+            continue;
+        }
+        if old.visibility != new.visibility {
+            edits.push(Edit::Visibility {
+                object,
+                before: old.visibility.clone(),
+                after: new.visibility.clone(),
+            });
+        }
+        for (submesh, (old, new)) in old.materials.iter().zip(&new.materials).enumerate() {
+            if old != new {
+                edits.push(Edit::Material {
+                    object,
+                    submesh,
+                    before: *old,
+                    after: *new,
+                });
+            }
+        }
+        if old.instances != new.instances {
+            edits.push(Edit::Instances {
+                object,
+                before: old.instances.clone(),
+                after: new.instances.clone(),
+            });
+        }
+    }
+    let (before, after) = (before.lights, after.lights);
+    if before.point != after.point {
+        edits.push(Edit::PointLight {
+            before: before.point,
+            after: after.point,
+        });
+    }
+    if before.spot != after.spot {
+        edits.push(Edit::SpotLight {
+            before: before.spot,
+            after: after.spot,
+        });
+    }
+    if before.directional != after.directional {
+        edits.push(Edit::DirectionalLight {
+            before: before.directional,
+            after: after.directional,
+        });
+    }
+    if before.ambient != after.ambient {
+        edits.push(Edit::Ambient {
+            before: before.ambient,
+            after: after.ambient,
+        });
+    }
+    edits
+}
+
+struct Entry<O: DocumentObject> {
+    label: String,
+    edits: Vec<Edit<O>>,
+    // still collecting the edits of a drag
+    open: bool,
+}
+
+// undo/redo of scene edits; most edits are found by comparing the scene with a snapshot once a
+// frame, adding and removing objects and replacing meshes and diffuse maps has to go through
+// `record_added`, `remove_object`, `replace_mesh` and `replace_diffuse_maps`
+pub struct History<O: DocumentObject> {
+    entries: Vec<Entry<O>>,
+    // entries before this are done, the rest are undone and can be redone
+    position: usize,
+    snapshot: Option<Snapshot>,
+}
+
+impl<O: DocumentObject> History<O> {
+    pub fn new() -> Self {
+        History {
+            entries: Vec::new(),
+            position: 0,
+            snapshot: None,
+        }
+    }
+
+    // forgets every entry, e.g. once a scene file replaced the whole scene
+    pub fn clear<D: Document<Object = O>>(&mut self, document: &D) {
+        self.entries.clear();
+        self.position = 0;
+        self.snapshot = Some(document.snapshot());
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.entries.len()
+    }
+
+    // records whatever changed since the last call; while `dragging` the changes keep merging
+    // into one entry
+    pub fn record<D: Document<Object = O>>(&mut self, document: &D, dragging: bool) {
+        let snapshot = document.snapshot();
+        let edits = match &self.snapshot {
+            Some(before) if before.objects.len() == snapshot.objects.len() => {
+                diff(before, &snapshot)
+            }
+            // the scene changed behind the history's back, nothing sensible to undo to
+            _ => Vec::new(),
+        };
+        self.snapshot = Some(snapshot);
+
+        let merge = dragging
+            && self.position == self.entries.len()
+            && self.entries.last().is_some_and(|entry| entry.open);
+        if merge {
+            let entry = self.entries.last_mut().unwrap();
+            for edit in edits {
+                match entry
+                    .edits
+                    .iter_mut()
+                    .find(|other| other.same_target(&edit))
+                {
+                    Some(other) => other.merge(edit),
+                    None => entry.edits.push(edit),
+                }
+            }
+        } else {
+            if let Some(entry) = self.entries.last_mut() {
+                entry.open = false;
+            }
+            if !edits.is_empty() {
+                self.push(document, edits, dragging);
+            }
+        }
+    }
+
+    // call right after adding the object at `index`
+    pub fn record_added<D: Document<Object = O>>(&mut self, document: &D, index: usize) {
+        // changes from earlier this frame go first, the added object isn't in the snapshot yet
+        let snapshot = document.snapshot();
+        let mut edits = match &self.snapshot {
+            Some(before) if before.objects.len() + 1 == snapshot.objects.len() => {
+                diff(before, &snapshot)
+            }
+            _ => Vec::new(),
+        };
+        edits.push(Edit::AddObject {
+            index,
+            object: None,
+        });
+        self.push(document, edits, false);
+        self.snapshot = Some(snapshot);
+    }
+
+    // removes the object so the removal can be undone
+    pub fn remove_object<D: Document<Object = O>>(&mut self, document: &mut D, index: usize) {
+        if index >= document.object_count() {
+            return;
+        }
+        self.record(document, false);
+        let children = self.snapshot.as_ref().map_or_else(Vec::new, |snapshot| {
+            (0..snapshot.objects.len())
+                .filter(|child| snapshot.objects[*child].transform.parent == Some(index))
+                .collect()
+        });
+        let object = document.remove_object(index);
+        let edit = Edit::RemoveObject {
+            index,
+            object: Some(object),
+            children,
+        };
+        self.push(document, vec![edit], false);
+        self.snapshot = Some(document.snapshot());
+    }
+
+    // gives the object at `index` another mesh so the change can be undone
+    pub fn replace_mesh<D: Document<Object = O>>(
+        &mut self,
+        document: &mut D,
+        index: usize,
+        mut mesh: O::Mesh,
+    ) {
+        if index >= document.object_count() {
+            return;
+        }
+        self.record(document, false);
+        document.swap_mesh(index, &mut mesh);
+        let edit = Edit::ReplaceMesh {
+            object: index,
+            mesh,
+        };
+        self.push(document, vec![edit], false);
+        self.snapshot = Some(document.snapshot());
+    }
+
+    // gives submeshes of the object at `index` other diffuse maps as one undoable entry
+    pub fn replace_diffuse_maps<D: Document<Object = O>>(
+        &mut self,
+        document: &mut D,
+        index: usize,
+        maps: Vec<(usize, O::DiffuseMap)>,
+    ) {
+        if index >= document.object_count() || maps.is_empty() {
+            return;
+        }
+        self.record(document, false);
+        let edits = maps
+            .into_iter()
+            .map(|(submesh, mut map)| {
+                document.swap_diffuse_map(index, submesh, &mut map);
+                Edit::DiffuseMap {
+                    object: index,
+                    submesh,
+                    map,
+                }
+            })
+            .collect();
+        self.push(document, edits, false);
+        self.snapshot = Some(document.snapshot());
+    }
+
+    fn push<D: Document<Object = O>>(&mut self, document: &D, edits: Vec<Edit<O>>, open: bool) {
+        let label = match edits.len() {
+            1 => edits[0].label(document),
+            n => format!("{} (+{} more)", edits[0].label(document), n - 1),
+        };
+        self.entries.truncate(self.position);
+        self.entries.push(Entry { label, edits, open });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.position = self.entries.len();
+    }
+
+    pub fn undo<D: Document<Object = O>>(&mut self, document: &mut D) {
+        if !self.can_undo() {
+            return;
+        }
+        self.position -= 1;
+        let entry = &mut self.entries[self.position];
+        entry.open = false;
+        for edit in entry.edits.iter_mut().rev() {
+            edit.apply(document, false);
+        }
+        self.snapshot = Some(document.snapshot());
+    }
+
+    pub fn redo<D: Document<Object = O>>(&mut self, document: &mut D) {
+        if !self.can_redo() {
+            return;
+        }
+        for edit in self.entries[self.position].edits.iter_mut() {
+            edit.apply(document, true);
+        }
+        self.position += 1;
+        self.snapshot = Some(document.snapshot());
+    }
+
+    pub fn build_ui<D: Document<Object = O>>(&mut self, ui: &imgui::Ui, document: &mut D) {
+        imgui::Window::new("History").build(ui, || {
+            if ui.button("undo (ctrl+z)") {
+                self.undo(document);
+            }
+            ui.same_line();
+            if ui.button("redo (ctrl+y)") {
+                self.redo(document);
+            }
+            ui.separator();
+
+            // clicking a row undoes or redoes up to and including it
+            let mut target = None;
+            if imgui::Selectable::new("(start)")
+                .selected(self.position == 0)
+                .build(ui)
+            {
+                target = Some(0);
+            }
+            for (index, entry) in self.entries.iter().enumerate() {
+                let _id = ui.push_id(index as i32);
+                let done = index < self.position;
+                let label = if done {
+                    entry.label.clone()
+                } else {
+                    format!("({})", entry.label)
+                };
+                if imgui::Selectable::new(&label)
+                    .selected(index + 1 == self.position)
+                    .build(ui)
+                {
+                    target = Some(index + 1);
+                }
+            }
+            if let Some(target) = target {
+                while self.position > target {
+                    self.undo(document);
+                }
+                while self.position < target {
+                    self.redo(document);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::One;
+
+    use super::*;
+
+    struct TestObject {
+        name: String,
+        snapshot: ObjectSnapshot,
+        mesh: u32,
+        diffuse_map: u32,
+    }
+
+    impl DocumentObject for TestObject {
+        type Mesh = u32;
+        type DiffuseMap = u32;
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    // the parent bookkeeping of `Scene::insert` and `Scene::remove` without any meshes
+    struct TestDocument {
+        objects: Vec<TestObject>,
+        lights: Lights,
+    }
+
+    impl TestDocument {
+        fn new(parents: &[Option<usize>]) -> Self {
+            TestDocument {
+                objects: parents
+                    .iter()
+                    .enumerate()
+                    .map(|(index, parent)| object(&format!("object {}", index), *parent))
+                    .collect(),
+                lights: Lights {
+                    point: PointLight::new(),
+                    spot: SpotLight::new(),
+                    directional: DirectionalLight::new(),
+                    ambient: [0.0; 3],
+                },
+            }
+        }
+
+        fn names(&self) -> Vec<&str> {
+            self.objects.iter().map(|object| &object.name[..]).collect()
+        }
+
+        fn parents(&self) -> Vec<Option<usize>> {
+            self.objects
+                .iter()
+                .map(|object| object.snapshot.transform.parent)
+                .collect()
+        }
+    }
+
+    fn object(name: &str, parent: Option<usize>) -> TestObject {
+        TestObject {
+            name: String::from(name),
+            snapshot: ObjectSnapshot {
+                transform: Transform {
+                    translation: Vector3::new(0.0, 0.0, 0.0),
+                    rotation: Quaternion::one(),
+                    scale: Vector3::new(1.0, 1.0, 1.0),
+                    parent,
+                },
+                visibility: Visibility {
+                    visible: true,
+                    submeshes: vec![(true, false)],
+                },
+                materials: vec![MaterialParams {
+                    ns: 10.0,
+                    ka: Vector3::new(0.1, 0.1, 0.1),
+                    kd: None,
+                    ks: Vector3::new(0.5, 0.5, 0.5),
+                }],
+                instances: Vec::new(),
+            },
+            mesh: 0,
+            diffuse_map: 0,
+        }
+    }
+
+    impl Document for TestDocument {
+        type Object = TestObject;
+
+        fn snapshot(&self) -> Snapshot {
+            Snapshot {
+                objects: self.objects.iter().map(|o| o.snapshot.clone()).collect(),
+                lights: self.lights,
+            }
+        }
+
+        fn object_name(&self, index: usize) -> Option<&str> {
+            self.objects.get(index).map(|object| &object.name[..])
+        }
+
+        fn object_count(&self) -> usize {
+            self.objects.len()
+        }
+
+        fn set_transform(&mut self, object: usize, transform: &Transform) {
+            self.objects[object].snapshot.transform = *transform;
+        }
+
+        fn set_parent(&mut self, object: usize, parent: Option<usize>) {
+            self.objects[object].snapshot.transform.parent = parent;
+        }
+
+        fn set_visibility(&mut self, object: usize, visibility: &Visibility) {
+            self.objects[object].snapshot.visibility = visibility.clone();
+        }
+
+        fn set_material(&mut self, object: usize, submesh: usize, params: MaterialParams) {
+            self.objects[object].snapshot.materials[submesh] = params;
+        }
+
+        fn set_instances(&mut self, object: usize, instances: Vec<Instance>) {
+            self.objects[object].snapshot.instances = instances;
+        }
+
+        fn lights(&self) -> Lights {
+            self.lights
+        }
+
+        fn set_lights(&mut self, lights: Lights) {
+            self.lights = lights;
+        }
+
+        fn insert_object(&mut self, index: usize, object: TestObject) {
+            for other in self.objects.iter_mut() {
+                let parent = &mut other.snapshot.transform.parent;
+                *parent = parent.map(|p| if p >= index { p + 1 } else { p });
+            }
+            self.objects.insert(index, object);
+        }
+
+        fn remove_object(&mut self, index: usize) -> TestObject {
+            let removed = self.objects.remove(index);
+            let shift_down = |p: usize| if p > index { p - 1 } else { p };
+            for other in self.objects.iter_mut() {
+                let parent = &mut other.snapshot.transform.parent;
+                *parent = match *parent {
+                    Some(p) if p == index => removed.snapshot.transform.parent.map(shift_down),
+                    Some(p) => Some(shift_down(p)),
+                    None => None,
+                };
+            }
+            removed
+        }
+
+        fn swap_mesh(&mut self, object: usize, mesh: &mut u32) {
+            std::mem::swap(&mut self.objects[object].mesh, mesh);
+        }
+
+        // one submesh per object
+        fn swap_diffuse_map(&mut self, object: usize, _submesh: usize, map: &mut u32) {
+            std::mem::swap(&mut self.objects[object].diffuse_map, map);
+        }
+    }
+
+    fn start(document: &TestDocument) -> History<TestObject> {
+        let mut history = History::new();
+        history.clear(document);
+        history
+    }
+
+    #[test]
+    fn drag_merges_into_one_entry() {
+        let mut document = TestDocument::new(&[None]);
+        let mut history = start(&document);
+        for step in 1..=5 {
+            document.objects[0].snapshot.transform.translation.x = step as f32;
+            history.record(&document, true);
+        }
+        history.record(&document, false);
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].edits.len(), 1);
+
+        history.undo(&mut document);
+        assert_eq!(document.objects[0].snapshot.transform.translation.x, 0.0);
+        history.redo(&mut document);
+        assert_eq!(document.objects[0].snapshot.transform.translation.x, 5.0);
+    }
+
+    #[test]
+    fn add_is_undone_and_redone() {
+        let mut document = TestDocument::new(&[None]);
+        let mut history = start(&document);
+        document.objects.push(object("added", None));
+        history.record_added(&document, 1);
+
+        history.undo(&mut document);
+        assert_eq!(document.names(), ["object 0"]);
+        history.redo(&mut document);
+        assert_eq!(document.names(), ["object 0", "added"]);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undone_removal_restores_children() {
+        // 1 is the parent of 2 and 3, and a child of 0
+        let mut document = TestDocument::new(&[None, Some(0), Some(1), Some(1)]);
+        let mut history = start(&document);
+        history.remove_object(&mut document, 1);
+        assert_eq!(document.names(), ["object 0", "object 2", "object 3"]);
+        assert_eq!(document.parents(), [None, Some(0), Some(0)]);
+
+        history.undo(&mut document);
+        assert_eq!(
+            document.names(),
+            ["object 0", "object 1", "object 2", "object 3"]
+        );
+        assert_eq!(document.parents(), [None, Some(0), Some(1), Some(1)]);
+
+        history.redo(&mut document);
+        assert_eq!(document.parents(), [None, Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn new_edit_after_undo_truncates() {
+        let mut document = TestDocument::new(&[None]);
+        let mut history = start(&document);
+        for x in [1.0, 2.0] {
+            document.objects[0].snapshot.transform.translation.x = x;
+            history.record(&document, false);
+        }
+        history.undo(&mut document);
+        assert!(history.can_redo());
+
+        document.lights.ambient = [0.5; 3];
+        history.record(&document, false);
+        assert_eq!(history.entries.len(), 2);
+        assert!(!history.can_redo());
+        assert_eq!(history.entries[1].label, "ambient light");
+
+        history.undo(&mut document);
+        history.undo(&mut document);
+        assert_eq!(document.objects[0].snapshot.transform.translation.x, 0.0);
+        assert_eq!(document.lights.ambient, [0.0; 3]);
+    }
+
+    #[test]
+    fn replaced_mesh_is_one_undoable_entry() {
+        let mut document = TestDocument::new(&[None]);
+        let mut history = start(&document);
+        history.replace_mesh(&mut document, 0, 7);
+        assert_eq!(document.objects[0].mesh, 7);
+        assert_eq!(history.entries.len(), 1);
+        // nothing else changed, the next frame records nothing
+        history.record(&document, false);
+        assert_eq!(history.entries.len(), 1);
+
+        history.undo(&mut document);
+        assert_eq!(document.objects[0].mesh, 0);
+        history.redo(&mut document);
+        assert_eq!(document.objects[0].mesh, 7);
+    }
+
+    #[test]
+    fn replaced_diffuse_map_is_undoable() {
+        let mut document = TestDocument::new(&[None]);
+        let mut history = start(&document);
+        history.replace_diffuse_maps(&mut document, 0, vec![(0, 3)]);
+        assert_eq!(document.objects[0].diffuse_map, 3);
+
+        history.undo(&mut document);
+        assert_eq!(document.objects[0].diffuse_map, 0);
+        history.redo(&mut document);
+        assert_eq!(document.objects[0].diffuse_map, 3);
+    }
+}
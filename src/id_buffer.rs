@@ -0,0 +1,149 @@
+use glium::{
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    texture::{DepthFormat, MipmapsOption, UncompressedUintFormat, UnsignedTexture2d},
+    uniform, Depth, DepthTest, Display, DrawParameters, Program, Rect, Surface,
+};
+
+use crate::{camera::Camera, scene::Scene};
+
+// what the id pass rendered at one pixel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IdPixel {
+    pub object: usize,
+    pub submesh: usize,
+    pub triangle: usize,
+    pub instance: usize,
+}
+
+// renders object, submesh, triangle and instance ids of the visible scene into an integer
+// texture, so a pick reads back exactly the triangle that was drawn at the cursor
+pub struct IdBuffer {
+    program: Program,
+    ids: UnsignedTexture2d,
+    depth_buffer: DepthRenderBuffer,
+}
+
+impl IdBuffer {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("shaders/id_buffer.vs"),
+            include_str!("shaders/id_buffer.fs"),
+            None,
+        )
+        .unwrap();
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (ids, depth_buffer) = Self::create_buffers(display, width, height);
+
+        IdBuffer {
+            program,
+            ids,
+            depth_buffer,
+        }
+    }
+
+    fn create_buffers(
+        display: &Display,
+        width: u32,
+        height: u32,
+    ) -> (UnsignedTexture2d, DepthRenderBuffer) {
+        let (width, height) = (width.max(1), height.max(1));
+        (
+            UnsignedTexture2d::empty_with_format(
+                display,
+                UncompressedUintFormat::U32U32U32U32,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .unwrap(),
+            DepthRenderBuffer::new(display, DepthFormat::F32, width, height).unwrap(),
+        )
+    }
+
+    pub fn resize(&mut self, display: &Display, width: u32, height: u32) {
+        if self.ids.dimensions() != (width.max(1), height.max(1)) {
+            let (ids, depth_buffer) = Self::create_buffers(display, width, height);
+            self.ids = ids;
+            self.depth_buffer = depth_buffer;
+        }
+    }
+
+    // renders the ids of every visible object and reads back the pixel at `(x, y)`, counted from
+    // the bottom left; only that pixel is rasterized, the camera must match the scene's viewport
+    pub fn read(
+        &self,
+        display: &Display,
+        camera: &Camera,
+        scene: &Scene,
+        (x, y): (u32, u32),
+    ) -> Result<Option<IdPixel>, glium::DrawError> {
+        let (width, height) = self.ids.dimensions();
+        if x >= width || y >= height {
+            return Ok(None);
+        }
+        let pixel = Rect {
+            left: x,
+            bottom: y,
+            width: 1,
+            height: 1,
+        };
+
+        let image = self
+            .ids
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap();
+        image.raw_clear_buffer([0u32, 0, 0, 0]);
+        let mut target =
+            SimpleFrameBuffer::with_depth_buffer(display, &self.ids, &self.depth_buffer).unwrap();
+        target.clear_depth(1.0);
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            scissor: Some(pixel),
+            ..Default::default()
+        };
+
+        for (index, object) in scene.objects.iter().enumerate() {
+            if !scene.is_visible(index) {
+                continue;
+            }
+            let instances = match object.instance_slice(0..object.instance_count()) {
+                Some(instances) => instances,
+                None => continue,
+            };
+            let mesh = object.mesh();
+            for submesh in 0..mesh.submeshes().len() {
+                if !mesh.is_submesh_drawn(submesh) {
+                    continue;
+                }
+                let uniforms = camera.add_uniforms(object.add_uniforms(uniform! {
+                    object_id: index as u32 + 1,
+                    submesh_id: submesh as u32,
+                }));
+                mesh.draw_submesh(
+                    &mut target,
+                    submesh,
+                    &instances,
+                    &self.program,
+                    uniforms,
+                    &params,
+                )?;
+            }
+        }
+
+        let read: Vec<Vec<(u32, u32, u32, u32)>> = image.raw_read(&pixel);
+        let (object, submesh, triangle, instance) = read[0][0];
+        Ok((object > 0).then_some(IdPixel {
+            object: object as usize - 1,
+            submesh: submesh as usize,
+            triangle: triangle as usize,
+            instance: instance as usize,
+        }))
+    }
+}
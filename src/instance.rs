@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+
+use cgmath::{Matrix, Matrix4, One, Quaternion, Rad, Rotation3, SquareMatrix, Vector3};
+
+use crate::scene::Scene;
+
+// one copy of an object's mesh, placed relative to the object itself
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+    // multiplies the diffuse color
+    pub tint: [f32; 3],
+}
+
+impl Instance {
+    pub fn new() -> Self {
+        Instance {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            tint: [1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    pub fn attributes(&self) -> InstanceAttr {
+        let matrix = self.matrix();
+        InstanceAttr {
+            instance_matrix: matrix.into(),
+            instance_normal_matrix: matrix
+                .invert()
+                .unwrap_or_else(Matrix4::identity)
+                .transpose()
+                .into(),
+            instance_tint: self.tint,
+        }
+    }
+}
+
+// per-instance vertex attributes, after the mesh's own position, normal and texcoord
+#[derive(Copy, Clone, Debug)]
+pub struct InstanceAttr {
+    pub instance_matrix: [[f32; 4]; 4],
+    pub instance_normal_matrix: [[f32; 4]; 4],
+    pub instance_tint: [f32; 3],
+}
+
+glium::implement_vertex!(
+    InstanceAttr,
+    instance_matrix,
+    instance_normal_matrix,
+    instance_tint
+);
+
+// xorshift32, enough to make scatters repeatable from a seed
+struct Rng(u32);
+
+impl Rng {
+    fn new(seed: u32) -> Self {
+        // xorshift gets stuck at zero
+        Rng(seed.wrapping_mul(0x9e37_79b9) | 1)
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+// places random copies of the selected object on a disk in its xz plane
+pub struct ScatterTool {
+    pub count: i32,
+    pub radius: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    pub random_yaw: bool,
+    // how far each tint channel may drop below white
+    pub tint_variation: f32,
+    pub seed: i32,
+}
+
+impl ScatterTool {
+    pub fn new() -> Self {
+        ScatterTool {
+            count: 100,
+            radius: 5.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            random_yaw: true,
+            tint_variation: 0.5,
+            seed: 1,
+        }
+    }
+
+    pub fn scatter(&self) -> Vec<Instance> {
+        let mut rng = Rng::new(self.seed as u32);
+        (0..self.count.max(0))
+            .map(|_| {
+                // uniform over the disk's area rather than its radius
+                let distance = self.radius * rng.next_f32().sqrt();
+                let angle = rng.range(0.0, 2.0 * PI);
+                let yaw = if self.random_yaw {
+                    rng.range(0.0, 2.0 * PI)
+                } else {
+                    0.0
+                };
+                let scale = rng.range(self.min_scale, self.max_scale);
+                let mut tint = [0.0; 3];
+                for channel in tint.iter_mut() {
+                    *channel = 1.0 - rng.range(0.0, self.tint_variation);
+                }
+                Instance {
+                    translation: Vector3::new(distance * angle.cos(), 0.0, distance * angle.sin()),
+                    rotation: Quaternion::from_angle_y(Rad(yaw)),
+                    scale: Vector3::new(scale, scale, scale),
+                    tint,
+                }
+            })
+            .collect()
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui, scene: &mut Scene) {
+        imgui::Window::new("Scatter").build(ui, || {
+            let object = match scene.selected_object_mut() {
+                Some(object) => object,
+                None => {
+                    ui.text("select an object to scatter");
+                    return;
+                }
+            };
+            ui.text(format!(
+                "{}: {} instances",
+                object.name,
+                object.instances().len()
+            ));
+            imgui::Slider::new("count", 1, 2000).build(ui, &mut self.count);
+            imgui::Slider::new("radius", 0.1, 50.0).build(ui, &mut self.radius);
+            imgui::Slider::new("min scale", 0.05, 4.0).build(ui, &mut self.min_scale);
+            imgui::Slider::new("max scale", 0.05, 4.0).build(ui, &mut self.max_scale);
+            self.max_scale = self.max_scale.max(self.min_scale);
+            ui.checkbox("random yaw", &mut self.random_yaw);
+            imgui::Slider::new("tint variation", 0.0, 1.0).build(ui, &mut self.tint_variation);
+            ui.input_int("seed", &mut self.seed).build();
+
+            if ui.button("scatter") {
+                object.set_instances(self.scatter());
+            }
+            ui.same_line();
+            if ui.button("clear instances") {
+                object.set_instances(Vec::new());
+            }
+        });
+    }
+}
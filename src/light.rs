@@ -1,6 +1,7 @@
 use cgmath::Vector3;
 use glium::uniforms::{AsUniformValue, Uniforms, UniformsStorage};
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PointLight {
     pub position: Vector3<f32>,
     pub intensity: Vector3<f32>,
@@ -28,6 +29,7 @@ impl PointLight {
         }
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn add_uniforms<'a, T, R>(
         &self,
         uniforms: UniformsStorage<'a, T, R>,
@@ -50,6 +52,7 @@ impl PointLight {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct SpotLight {
     pub point_light: PointLight,
     direction: Vector3<f32>,
@@ -82,6 +85,7 @@ impl SpotLight {
         }
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn add_uniforms<'a, T, R>(
         &self,
         uniforms: UniformsStorage<'a, T, R>,
@@ -113,8 +117,35 @@ impl SpotLight {
             .add("cutoff_start", self.cutoff_start_deg)
             .add("total_width", self.total_width_deg)
     }
+
+    pub fn new_pdw(
+        point_light: PointLight,
+        direction: Vector3<f32>,
+        cutoff_start_deg: f32,
+        total_width_deg: f32,
+    ) -> Self {
+        SpotLight {
+            point_light,
+            direction,
+            cutoff_start_deg,
+            total_width_deg,
+        }
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.direction
+    }
+
+    pub fn cutoff_start_deg(&self) -> f32 {
+        self.cutoff_start_deg
+    }
+
+    pub fn total_width_deg(&self) -> f32 {
+        self.total_width_deg
+    }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DirectionalLight {
     direction: Vector3<f32>,
     radiance: Vector3<f32>,
@@ -138,6 +169,22 @@ impl DirectionalLight {
         }
     }
 
+    pub fn new_dr(direction: Vector3<f32>, radiance: Vector3<f32>) -> Self {
+        DirectionalLight {
+            direction,
+            radiance,
+        }
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.direction
+    }
+
+    pub fn radiance(&self) -> Vector3<f32> {
+        self.radiance
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn add_uniforms<'a, T, R>(
         &self,
         uniforms: UniformsStorage<'a, T, R>,
@@ -151,3 +198,55 @@ impl DirectionalLight {
             .add("dir_light_radiance", self.radiance.into())
     }
 }
+
+fn drag_vector(ui: &imgui::Ui, label: &str, value: &mut Vector3<f32>, speed: f32) {
+    let mut array: [f32; 3] = (*value).into();
+    if imgui::Drag::new(label)
+        .speed(speed)
+        .build_array(ui, &mut array)
+    {
+        *value = array.into();
+    }
+}
+
+pub fn build_lights_ui(
+    ui: &imgui::Ui,
+    point_light: &mut PointLight,
+    spot_light: &mut SpotLight,
+    dir_light: &mut DirectionalLight,
+    ambient_light: &mut [f32; 3],
+) {
+    imgui::Window::new("Lights").build(ui, || {
+        {
+            let _id = ui.push_id("point");
+            ui.text("point light");
+            drag_vector(ui, "position", &mut point_light.position, 0.01);
+            drag_vector(ui, "intensity", &mut point_light.intensity, 0.01);
+        }
+
+        ui.separator();
+        {
+            let _id = ui.push_id("spot");
+            ui.text("spot light (arrow keys move it)");
+            drag_vector(ui, "position", &mut spot_light.point_light.position, 0.01);
+            drag_vector(ui, "intensity", &mut spot_light.point_light.intensity, 0.01);
+            drag_vector(ui, "direction", &mut spot_light.direction, 0.01);
+            imgui::Slider::new("cutoff start", 0.0, 90.0)
+                .build(ui, &mut spot_light.cutoff_start_deg);
+            imgui::Slider::new("total width", 0.0, 90.0).build(ui, &mut spot_light.total_width_deg);
+            spot_light.total_width_deg =
+                spot_light.total_width_deg.max(spot_light.cutoff_start_deg);
+        }
+
+        ui.separator();
+        {
+            let _id = ui.push_id("directional");
+            ui.text("directional light");
+            drag_vector(ui, "direction", &mut dir_light.direction, 0.01);
+            drag_vector(ui, "radiance", &mut dir_light.radiance, 0.01);
+        }
+
+        ui.separator();
+        imgui::ColorEdit::new("ambient", ambient_light).build(ui);
+    });
+}
@@ -0,0 +1,214 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use glium::Display;
+
+use crate::{
+    mesh::{MeshData, TriangleMesh},
+    scene::model_name,
+};
+
+// where the bundled models are, independent of the working directory; ends in a separator so file
+// dialogs open inside it
+pub const MODELS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/models/");
+
+// shared between a loading thread and the ui; counters only ever grow
+pub struct LoadProgress {
+    bytes_parsed: AtomicUsize,
+    bytes_total: AtomicUsize,
+    textures_decoded: AtomicUsize,
+    textures_total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    pub fn new() -> Self {
+        LoadProgress {
+            bytes_parsed: AtomicUsize::new(0),
+            bytes_total: AtomicUsize::new(0),
+            textures_decoded: AtomicUsize::new(0),
+            textures_total: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn start_parsing(&self, bytes_total: usize) {
+        self.bytes_total.store(bytes_total, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_parsed(&self, bytes: usize) {
+        self.bytes_parsed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_textures(&self, count: usize) {
+        self.textures_total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn texture_decoded(&self) {
+        self.textures_decoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // lines are counted with a newline the last one may not have, hence the clamp
+    fn parsed_fraction(&self) -> f32 {
+        let total = self.bytes_total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.bytes_parsed.load(Ordering::Relaxed) as f32 / total as f32).min(1.0)
+    }
+
+    fn textures(&self) -> (usize, usize) {
+        (
+            self.textures_decoded.load(Ordering::Relaxed),
+            self.textures_total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+// what a file is used for when dropped onto the window, by its extension
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Model,
+    Scene,
+    Image,
+    Environment,
+}
+
+impl FileKind {
+    pub fn of(file_path: &str) -> Option<FileKind> {
+        let extension = Path::new(file_path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "obj" | "stl" => Some(FileKind::Model),
+            "toml" => Some(FileKind::Scene),
+            "png" | "jpg" | "jpeg" | "bmp" | "tga" => Some(FileKind::Image),
+            "hdr" | "exr" => Some(FileKind::Environment),
+            _ => None,
+        }
+    }
+}
+
+// what to do with a mesh once it has loaded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadTarget {
+    AddObject,
+    // replaces the mesh of the object with this id, wherever it is by the time the load finishes
+    ReplaceMesh(u64),
+    // fills in the placeholder mesh of an object a scene file created; not an edit to undo
+    Placeholder(u64),
+}
+
+pub struct LoadedModel {
+    pub file_path: String,
+    pub target: LoadTarget,
+    pub mesh: Result<TriangleMesh, String>,
+}
+
+struct PendingLoad {
+    file_path: String,
+    target: LoadTarget,
+    progress: Arc<LoadProgress>,
+    receiver: Receiver<Result<MeshData, String>>,
+}
+
+// parses models and decodes their textures on worker threads; the gpu upload happens in `poll`
+// on the main thread
+pub struct ModelLoader {
+    pending: Vec<PendingLoad>,
+}
+
+impl ModelLoader {
+    pub fn new() -> Self {
+        ModelLoader {
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self, file_path: &str, normalize: bool, target: LoadTarget) {
+        let progress = Arc::new(LoadProgress::new());
+        let (sender, receiver) = mpsc::channel();
+        let thread_progress = progress.clone();
+        let thread_path = String::from(file_path);
+        thread::spawn(move || {
+            let data = MeshData::parse(&thread_path, normalize, &thread_progress)
+                .map_err(|e| e.to_string());
+            // the receiver is gone if the load was cancelled
+            let _ = sender.send(data);
+        });
+
+        self.pending.push(PendingLoad {
+            file_path: String::from(file_path),
+            target,
+            progress,
+            receiver,
+        });
+    }
+
+    // uploads every model whose parsing finished since the last call
+    pub fn poll(&mut self, display: &Display) -> Vec<LoadedModel> {
+        let mut loaded = Vec::new();
+        self.pending.retain(|load| {
+            let mesh = match load.receiver.try_recv() {
+                Ok(data) => data.map(|data| TriangleMesh::upload(display, data)),
+                Err(TryRecvError::Empty) => return true,
+                // only if the thread panicked, malformed files come back as errors
+                Err(TryRecvError::Disconnected) => Err(String::from("the loading thread failed")),
+            };
+            loaded.push(LoadedModel {
+                file_path: load.file_path.clone(),
+                target: load.target,
+                mesh,
+            });
+            false
+        });
+        loaded
+    }
+
+    // e.g. before a scene file replaces the objects the loads were meant for
+    pub fn cancel_all(&mut self) {
+        for load in self.pending.drain(..) {
+            load.progress.cancel();
+        }
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui) {
+        if self.pending.is_empty() {
+            return;
+        }
+        imgui::Window::new("Loading").build(ui, || {
+            let mut cancelled = None;
+            for (index, load) in self.pending.iter().enumerate() {
+                let _id = ui.push_id(index as i32);
+                ui.text(model_name(&load.file_path));
+                let fraction = load.progress.parsed_fraction();
+                imgui::ProgressBar::new(fraction)
+                    .overlay_text(format!("parsed {:.0}%", fraction * 100.0))
+                    .build(ui);
+                let (decoded, total) = load.progress.textures();
+                ui.text(format!("textures decoded: {}/{}", decoded, total));
+                if ui.button("cancel") {
+                    cancelled = Some(index);
+                }
+                ui.separator();
+            }
+            if let Some(index) = cancelled {
+                // the thread notices the flag and stops, whatever it sends is dropped
+                self.pending.remove(index).progress.cancel();
+            }
+        });
+    }
+}
@@ -1,32 +1,85 @@
-use camera::Camera;
+use bookmark::BookmarkEditor;
+use camera::{Camera, Projection};
+use cgmath::EuclideanSpace;
+use controller::{CameraMode, FlyController, OrbitController};
 
 use glium::{
     self,
     glutin::event::{ElementState, VirtualKeyCode},
-    uniform, DrawParameters, Program, Surface,
+    uniform, Program, Surface,
 };
 
-use imgui;
-use imgui_glium_renderer;
-use imgui_winit_support;
-
-use tinyfiledialogs;
-
-use light::{DirectionalLight, PointLight, SpotLight};
-use mesh::TriangleMesh;
+use deferred::{is_forward_only, DeferredRenderer, GBufferView, RenderPath, VOLUME_CUTOFF};
+use environment::Environment;
+use gizmo::{Gizmo, GizmoMode, GizmoSpace};
+use helpers::Helpers;
+use history::History;
+use instance::ScatterTool;
+use light::{build_lights_ui, DirectionalLight, PointLight, SpotLight};
+use loader::{FileKind, LoadTarget, ModelLoader, MODELS_DIR};
+use mesh::{decode_diffuse_map, DiffuseMap, TriangleMesh};
+use pick::Picker;
+use post::PostStack;
+use scene::{model_name, Scene};
+use scene_file::{SceneFile, SceneState};
 use scene_obj::{SceneLight, SceneObject};
+use settings::{RenderSettings, MAX_RENDER_SCALE, MIN_RENDER_SCALE};
+use shading::{ShadingMode, ShadingPrograms};
+use ssao::Ssao;
+use toon::{OutlineMode, ToonOutline, ToonSettings};
 
+mod bookmark;
+mod bvh;
 mod camera;
+mod controller;
+mod deferred;
+mod environment;
+mod gizmo;
+mod helpers;
+mod history;
+mod id_buffer;
+mod instance;
 mod light;
+mod loader;
 mod mesh;
+mod pick;
+mod post;
+mod scene;
+mod scene_file;
 mod scene_obj;
+mod settings;
+mod shading;
+mod ssao;
+mod toon;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut render_settings = RenderSettings::from_args(args.clone()).unwrap_or_else(|e| {
+        eprintln!("invalid render settings: {}", e);
+        std::process::exit(1);
+    });
+    // `--scene <path>` replaces the default model, lights and camera; flags still win over the
+    // scene's render settings
+    let scene_path = args
+        .iter()
+        .position(|arg| arg == "--scene")
+        .and_then(|index| args.get(index + 1))
+        .cloned();
+    let scene_file = scene_path.as_ref().map(|file_path| {
+        let scene_file = SceneFile::load(file_path).unwrap_or_else(|e| {
+            eprintln!("failed to load scene {}: {}", file_path, e);
+            std::process::exit(1);
+        });
+        scene_file.apply_render_settings(&mut render_settings);
+        render_settings.apply_args(&args).unwrap();
+        scene_file
+    });
+
     let event_loop = glium::glutin::event_loop::EventLoop::new();
     let window_builder = glium::glutin::window::WindowBuilder::new()
         .with_inner_size(glium::glutin::dpi::LogicalSize::new(1024.0, 768.0))
         .with_title("Hello World");
-    let context_builder = glium::glutin::ContextBuilder::new();
+    let context_builder = glium::glutin::ContextBuilder::new().with_vsync(render_settings.vsync);
 
     let display = glium::Display::new(window_builder, context_builder, &event_loop).unwrap();
     let mut imgui_context = imgui::Context::create();
@@ -46,16 +99,28 @@ fn main() {
 
     let mut last_frame = std::time::Instant::now();
 
-    let mesh = TriangleMesh::new(&display, "models/Ivysaur/Ivysaur.obj", true).unwrap();
-    let mut scene_obj = SceneObject::new(mesh);
+    let mut scene = Scene::new();
+    if scene_file
+        .as_ref()
+        .is_none_or(|scene_file| scene_file.models.is_empty())
+    {
+        let mesh = TriangleMesh::new(
+            &display,
+            &format!("{}Ivysaur/Ivysaur.obj", MODELS_DIR),
+            true,
+        )
+        .unwrap();
+        scene.add(SceneObject::new("Ivysaur", mesh));
+    }
 
-    let program = Program::from_source(
-        &display,
-        include_str!("shaders/phong_shading.vs"),
-        include_str!("shaders/phong_shading.fs"),
-        None,
-    )
-    .unwrap();
+    let shading_programs = ShadingPrograms::new(&display);
+    let mut shading_mode = ShadingMode::Phong;
+    let mut toon_settings = ToonSettings::new();
+    let mut toon_outline = ToonOutline::new(&display);
+    let mut render_path = RenderPath::Forward;
+    let mut deferred_renderer = DeferredRenderer::new(&display);
+    let mut post_stack = PostStack::new(&display, render_settings.msaa_samples);
+    let mut ssao = Ssao::new(&display);
     let light_program = Program::from_source(
         &display,
         include_str!("shaders/point_light.vs"),
@@ -64,23 +129,82 @@ fn main() {
     )
     .unwrap();
 
-    let (framebuffer_width, framebuffer_height) = display.get_framebuffer_dimensions();
-    let aspect_ratio = framebuffer_width as f32 / framebuffer_height as f32;
-
     let mut cur_rotation_y: f32 = 0.0;
     let rotate_dir_y: f32 = 1.0;
     let rotate_step: f32 = 0.5;
+    let mut turntable = true;
 
-    let mut camera = Camera::new(aspect_ratio);
-    let point_light = PointLight::new();
+    let mut camera = Camera::new(display.get_framebuffer_dimensions());
+    let mut orbit_controller = OrbitController::new(&camera, cgmath::Point3::new(0.0, 0.0, 0.0));
+    let mut fly_controller = FlyController::new(&camera);
+    let mut camera_mode = CameraMode::Orbit;
+    let mut modifiers = glium::glutin::event::ModifiersState::empty();
+    // arrow keys held down to move the spot light
+    let mut light_keys = std::collections::HashSet::new();
+    let mut frame_requested = false;
+    let mut bookmark_editor = BookmarkEditor::new();
+    let mut gizmo = Gizmo::new(&display);
+    let mut picker = Picker::new(&display);
+    let mut scatter_tool = ScatterTool::new();
+    let mut history = History::new();
+    let mut helpers = Helpers::new(&display);
+    let mut model_loader = ModelLoader::new();
+    let mut point_light = PointLight::new();
     let mut spot_light = SpotLight::new();
-    let dir_light = DirectionalLight::new();
+    let mut dir_light = DirectionalLight::new();
 
-    let ambient_light: [f32; 3] = [0.005, 0.005, 0.005];
+    let mut ambient_light: [f32; 3] = [0.005, 0.005, 0.005];
+    let mut environment = Environment::new(&display);
 
-    let uniforms = uniform! {
-        ambient_light: ambient_light,
-    };
+    macro_rules! scene_state {
+        () => {
+            SceneState {
+                scene: &mut scene,
+                point_light: &mut point_light,
+                spot_light: &mut spot_light,
+                dir_light: &mut dir_light,
+                ambient_light: &mut ambient_light,
+                environment: &mut environment,
+                camera: &mut camera,
+                bookmarks: &mut bookmark_editor.bookmarks,
+                render_settings: &mut render_settings,
+                shading_mode: &mut shading_mode,
+                render_path: &mut render_path,
+            }
+        };
+    }
+
+    // from the load button or a dropped file
+    macro_rules! load_scene {
+        ($file_path:expr) => {
+            match SceneFile::load($file_path) {
+                Ok(scene_file) => {
+                    model_loader.cancel_all();
+                    scene_file.apply(&display, $file_path, &mut scene_state!(), &mut model_loader);
+                    scene_file.apply_render_settings(&mut render_settings);
+                    // flags still win, as they do at startup
+                    render_settings.apply_args(&args).unwrap();
+                    history.clear(&scene_state!());
+                    post_stack.set_msaa_samples(&display, render_settings.msaa_samples);
+                    orbit_controller.sync(&camera);
+                    fly_controller.reset(&camera);
+                    // show the scene as authored
+                    turntable = false;
+                    cur_rotation_y = 0.0;
+                }
+                Err(e) => eprintln!("failed to load scene {}: {}", $file_path, e),
+            }
+        };
+    }
+
+    if let (Some(scene_file), Some(file_path)) = (&scene_file, &scene_path) {
+        scene_file.apply(&display, file_path, &mut scene_state!(), &mut model_loader);
+        orbit_controller.sync(&camera);
+        fly_controller.reset(&camera);
+        // show the scene as authored
+        turntable = false;
+    }
+    history.clear(&scene_state!());
 
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -102,50 +226,459 @@ fn main() {
             glium::glutin::event::Event::RedrawRequested(_) => {
                 let ui = imgui_context.frame();
 
-                // Added this line to try to render some text
-                if ui.button("change model") {
+                if ui.button("load scene") {
                     if let Some(file_path) = tinyfiledialogs::open_file_dialog(
-                        "Choose a Model",
-                        "./models",
-                        Some((&["*.obj"], "obj model")),
+                        "Choose a Scene",
+                        ".",
+                        Some((&["*.toml"], "scene file")),
+                    ) {
+                        load_scene!(&file_path);
+                    }
+                }
+                ui.same_line();
+                if ui.button("save scene") {
+                    if let Some(file_path) =
+                        tinyfiledialogs::save_file_dialog("Save Scene", "scene.toml")
+                    {
+                        if let Err(e) = SceneFile::capture(&scene_state!()).save(&file_path) {
+                            eprintln!("failed to save scene {}: {}", file_path, e);
+                        }
+                    }
+                }
+
+                // Added this line to try to render some text
+                for (label, replace) in [("add model", false), ("change model", true)] {
+                    if ui.button(label) {
+                        if let Some(file_path) = tinyfiledialogs::open_file_dialog(
+                            "Choose a Model",
+                            MODELS_DIR,
+                            Some((&["*.obj", "*.stl"], "obj or stl model")),
+                        ) {
+                            let target = match scene.selected_object() {
+                                Some(object) if replace => LoadTarget::ReplaceMesh(object.id()),
+                                _ => LoadTarget::AddObject,
+                            };
+                            model_loader.start(&file_path, true, target);
+                        }
+                    }
+                    ui.same_line();
+                }
+                for loaded in model_loader.poll(&display) {
+                    match loaded.mesh {
+                        Ok(mut mesh) => match loaded.target {
+                            LoadTarget::ReplaceMesh(id) => match scene.index_of(id) {
+                                Some(index) => {
+                                    history.replace_mesh(&mut scene_state!(), index, mesh);
+                                    // only a change to what is being looked at moves the camera
+                                    frame_requested |= scene.selected == Some(index);
+                                }
+                                None => eprintln!(
+                                    "{} was loaded for an object that has been removed",
+                                    loaded.file_path
+                                ),
+                            },
+                            LoadTarget::Placeholder(id) => {
+                                if let Some(index) = scene.index_of(id) {
+                                    scene.objects[index].swap_mesh(&mut mesh);
+                                }
+                            }
+                            LoadTarget::AddObject => {
+                                let index = scene.add_beside(SceneObject::new(
+                                    &model_name(&loaded.file_path),
+                                    mesh,
+                                ));
+                                history.record_added(&scene_state!(), index);
+                                // added objects are selected, so this frames the new one
+                                frame_requested = true;
+                            }
+                        },
+                        Err(e) => eprintln!("failed to load {}: {}", loaded.file_path, e),
+                    }
+                }
+                model_loader.build_ui(&ui);
+                if ui.button("frame object") {
+                    frame_requested = true;
+                }
+
+                let mut mode_index = ShadingMode::ALL
+                    .iter()
+                    .position(|mode| *mode == shading_mode)
+                    .unwrap();
+                if ui.combo("shading", &mut mode_index, &ShadingMode::ALL, |mode| {
+                    mode.name().into()
+                }) {
+                    shading_mode = ShadingMode::ALL[mode_index];
+                }
+
+                ui.checkbox("turntable", &mut turntable);
+                ui.text(format!(
+                    "camera: {} (F to toggle, arrow keys move the spot light)",
+                    camera_mode.name()
+                ));
+                let mut projection_index = Projection::ALL
+                    .iter()
+                    .position(|projection| *projection == camera.projection)
+                    .unwrap();
+                if ui.combo(
+                    "projection",
+                    &mut projection_index,
+                    &Projection::ALL,
+                    |projection| projection.name().into(),
+                ) {
+                    camera.projection = Projection::ALL[projection_index];
+                }
+                imgui::Slider::new("orbit damping", 0.0, 0.95)
+                    .build(&ui, &mut orbit_controller.damping);
+                imgui::Slider::new("fly speed", 0.1, 20.0).build(&ui, &mut fly_controller.speed);
+                imgui::Slider::new("render scale", MIN_RENDER_SCALE, MAX_RENDER_SCALE)
+                    .build(&ui, &mut render_settings.render_scale);
+
+                let mut path_index = RenderPath::ALL
+                    .iter()
+                    .position(|path| *path == render_path)
+                    .unwrap();
+                if ui.combo("render path", &mut path_index, &RenderPath::ALL, |path| {
+                    path.name().into()
+                }) {
+                    render_path = RenderPath::ALL[path_index];
+                }
+                if render_path == RenderPath::Deferred {
+                    let mut view_index = GBufferView::ALL
+                        .iter()
+                        .position(|view| *view == deferred_renderer.view)
+                        .unwrap();
+                    if ui.combo(
+                        "g-buffer view",
+                        &mut view_index,
+                        &GBufferView::ALL,
+                        |view| view.name().into(),
                     ) {
-                        let mesh = TriangleMesh::new(&display, &file_path, true).unwrap();
-                        scene_obj = SceneObject::new(mesh);
+                        deferred_renderer.view = GBufferView::ALL[view_index];
+                    }
+                    ui.text_disabled("gouraud objects are drawn forward, outside the g-buffer");
+                    ui.text_disabled(format!(
+                        "point and spot lights end below 1/{} irradiance, forward ones never do",
+                        (1.0 / VOLUME_CUTOFF) as u32
+                    ));
+                }
+
+                imgui::Window::new("Environment").build(&ui, || {
+                    if ui.button("load environment") {
+                        if let Some(file_path) = tinyfiledialogs::open_file_dialog(
+                            "Choose an Environment Map",
+                            MODELS_DIR,
+                            Some((&["*.hdr", "*.exr"], "equirectangular image")),
+                        ) {
+                            if let Err(e) = environment.load(&display, &file_path) {
+                                eprintln!("failed to load environment {}: {}", file_path, e);
+                            }
+                        }
+                    }
+                    match environment.source_path() {
+                        Some(path) => {
+                            ui.text_wrapped(path);
+                            if ui.button("unload environment") {
+                                environment.unload();
+                            }
+                        }
+                        None => ui.text("no environment loaded"),
+                    }
+                    ui.checkbox("show skybox", &mut environment.show_skybox);
+                    imgui::Slider::new("rotation", 0.0, 360.0)
+                        .build(&ui, &mut environment.rotation_deg);
+                    imgui::Slider::new("intensity", 0.0, 4.0)
+                        .build(&ui, &mut environment.intensity);
+                });
+
+                imgui::Window::new("Toon").build(&ui, || {
+                    imgui::Slider::new("bands", 1, 8).build(&ui, &mut toon_settings.bands);
+                    imgui::Slider::new("specular threshold", 0.0, 1.0)
+                        .build(&ui, &mut toon_settings.specular_threshold);
+                    let mut outline_index = OutlineMode::ALL
+                        .iter()
+                        .position(|mode| *mode == toon_settings.outline_mode)
+                        .unwrap();
+                    if ui.combo("outline", &mut outline_index, &OutlineMode::ALL, |mode| {
+                        mode.name().into()
+                    }) {
+                        toon_settings.outline_mode = OutlineMode::ALL[outline_index];
+                    }
+                    imgui::ColorEdit::new("outline color", &mut toon_settings.outline_color)
+                        .build(&ui);
+                    imgui::Slider::new("outline width", 0.5, 8.0)
+                        .build(&ui, &mut toon_settings.outline_width);
+                });
+
+                post_stack.build_ui(&ui, &display);
+                ssao.build_ui(&ui);
+                helpers.build_ui(&ui);
+                if let Some(index) = scene.build_ui(&ui) {
+                    history.remove_object(&mut scene_state!(), index);
+                }
+                gizmo.build_ui(&ui, &mut scene);
+                scatter_tool.build_ui(&ui, &mut scene);
+                picker.build_ui(
+                    &ui,
+                    &mut scene,
+                    cgmath::Point3::from_vec(point_light.position),
+                );
+                build_lights_ui(
+                    &ui,
+                    &mut point_light,
+                    &mut spot_light,
+                    &mut dir_light,
+                    &mut ambient_light,
+                );
+                history.build_ui(&ui, &mut scene_state!());
+                // a drag in any widget or of the gizmo, or a held light key, becomes a single undo
+                // step
+                history.record(
+                    &scene_state!(),
+                    ui.is_any_item_active() || gizmo.is_dragging() || !light_keys.is_empty(),
+                );
+                if bookmark_editor.build_ui(&ui, &mut camera) {
+                    orbit_controller.sync(&camera);
+                    fly_controller.reset(&camera);
+                }
+
+                if frame_requested {
+                    // frames the selected object, or the whole scene when nothing is selected
+                    let sphere = match scene.selected_object() {
+                        Some(object) => Some(object.bounding_sphere()),
+                        None => scene.bounding_sphere(),
+                    };
+                    if let Some((center, radius)) = sphere {
+                        match camera_mode {
+                            CameraMode::Orbit => {
+                                orbit_controller.frame(center, radius, &mut camera)
+                            }
+                            CameraMode::Fly => fly_controller.frame(center, radius, &mut camera),
+                        }
+                    }
+                    frame_requested = false;
+                }
+                let mut path_frame = false;
+                if bookmark_editor.player.playing {
+                    path_frame = bookmark_editor.update(ui.io().delta_time, &mut camera);
+                    if !bookmark_editor.player.playing {
+                        // hand the camera back to the controllers where the path ended
+                        orbit_controller.sync(&camera);
+                        fly_controller.reset(&camera);
+                    }
+                } else {
+                    // taken every frame so loads, removals and the turntable are followed
+                    let bounds = scene.bounding_sphere();
+                    match camera_mode {
+                        CameraMode::Orbit => {
+                            orbit_controller.update(ui.io().delta_time, &mut camera, bounds)
+                        }
+                        CameraMode::Fly => {
+                            fly_controller.update(ui.io().delta_time, &mut camera, bounds)
+                        }
                     }
                 }
 
                 let gl_window = display.gl_window();
                 let mut frame = display.draw();
-                frame.clear_color(0.44, 0.57, 0.75, 1.0);
-                frame.clear_depth(1.0);
+                let (width, height) = render_settings.scaled_dimensions(frame.get_dimensions());
+                camera.set_viewport(width, height);
+                let mut target = post_stack.scene_target(&display, width, height);
+                target.clear_color(0.44, 0.57, 0.75, 1.0);
+                target.clear_depth(1.0);
+                environment.draw_skybox(&mut target, &camera).unwrap();
+                let uniforms = uniform! {
+                    ambient_light: ambient_light,
+                };
                 {
-                    cur_rotation_y += rotate_dir_y * rotate_step;
+                    // the turntable holds still while an object is being dragged
+                    if turntable && !gizmo.is_dragging() {
+                        cur_rotation_y += rotate_dir_y * rotate_step;
+                    }
                     let s = cgmath::Matrix4::from_scale(1.0);
                     let r = cgmath::Matrix4::from_angle_y(cgmath::Deg(cur_rotation_y));
                     let world_matrix = s * r;
 
-                    scene_obj.set_world_matrix(world_matrix);
-                    camera.set_world_matrix(world_matrix);
+                    scene.update_world_matrices(world_matrix);
+                    scene.update_instance_buffers(&display);
+                    picker
+                        .resolve_pending(
+                            &display,
+                            &camera,
+                            &mut scene,
+                            frame.get_dimensions(),
+                            (width, height),
+                        )
+                        .unwrap();
+                    helpers
+                        .update_shadow_map(&display, &scene, &spot_light, &dir_light)
+                        .unwrap();
+                    if helpers.wants_reflection() {
+                        let mirrored = helpers.reflection_camera(&camera);
+                        let mut reflection = helpers.reflection_target(&display, width, height);
+                        reflection.clear_color_and_depth((0.44, 0.57, 0.75, 1.0), 1.0);
+                        environment.draw_skybox(&mut reflection, &mirrored).unwrap();
+                        // the ambient occlusion buffer belongs to the main view
+                        let uniforms = mirrored.add_uniforms(uniforms.add("ssao_enabled", false));
+                        let uniforms = point_light.add_uniforms(uniforms);
+                        let uniforms = spot_light.add_uniforms(uniforms);
+                        let uniforms = dir_light.add_uniforms(uniforms);
+                        let uniforms = environment.add_uniforms(uniforms);
+                        let uniforms = toon_settings.add_uniforms(uniforms);
+                        for scene_obj in scene.visible_objects() {
+                            let program = shading_programs
+                                .get(scene_obj.shading_override.unwrap_or(shading_mode));
+                            scene_obj
+                                .draw(&mut reflection, program, scene_obj.add_uniforms(uniforms))
+                                .unwrap();
+                        }
+                    }
+
+                    match render_path {
+                        RenderPath::Forward => {
+                            if ssao.settings.enabled {
+                                ssao.resize(&display, width, height);
+                                ssao.draw_geometry(
+                                    &display,
+                                    scene.visible_objects(),
+                                    camera.add_uniforms(uniforms),
+                                )
+                                .unwrap();
+                                ssao.compute_from_prepass(&display, &camera).unwrap();
+                            }
 
-                    let uniforms = scene_obj.add_uniforms(uniforms);
-                    let uniforms = camera.add_uniforms(uniforms);
-                    let uniforms = point_light.add_uniforms(uniforms);
-                    let uniforms = spot_light.add_uniforms(uniforms);
-                    let uniforms = dir_light.add_uniforms(uniforms);
+                            let uniforms = camera.add_uniforms(uniforms);
+                            let uniforms = point_light.add_uniforms(uniforms);
+                            let uniforms = spot_light.add_uniforms(uniforms);
+                            let uniforms = dir_light.add_uniforms(uniforms);
+                            let uniforms = environment.add_uniforms(uniforms);
+                            let uniforms = toon_settings.add_uniforms(uniforms);
+                            let uniforms = ssao.add_uniforms(uniforms);
 
-                    scene_obj.draw(&display, &mut frame, &program, uniforms).unwrap();
+                            for scene_obj in scene.visible_objects() {
+                                let uniforms = scene_obj.add_uniforms(uniforms);
+                                let object_shading =
+                                    scene_obj.shading_override.unwrap_or(shading_mode);
+                                let program = shading_programs.get(object_shading);
+                                scene_obj.draw(&mut target, program, uniforms).unwrap();
+                                if object_shading == ShadingMode::Toon {
+                                    toon_outline
+                                        .draw(
+                                            &display,
+                                            &mut target,
+                                            scene_obj,
+                                            uniforms,
+                                            &toon_settings,
+                                        )
+                                        .unwrap();
+                                }
+                            }
+                        }
+                        RenderPath::Deferred => {
+                            deferred_renderer.resize(&display, width, height);
+
+                            deferred_renderer
+                                .draw_geometry(
+                                    &display,
+                                    scene.visible_objects(),
+                                    shading_mode,
+                                    camera.add_uniforms(uniforms),
+                                )
+                                .unwrap();
+                            if ssao.settings.enabled {
+                                ssao.resize(&display, width, height);
+                                ssao.compute(
+                                    &display,
+                                    &camera,
+                                    deferred_renderer.gbuffer_position(),
+                                    deferred_renderer.gbuffer_normal(),
+                                )
+                                .unwrap();
+                            }
+                            let uniforms = toon_settings.add_uniforms(uniforms);
+                            deferred_renderer
+                                .draw_lighting(
+                                    &mut target,
+                                    camera.add_uniforms(ssao.add_uniforms(uniforms)),
+                                    &[&point_light],
+                                    &[&spot_light],
+                                    &dir_light,
+                                    &environment,
+                                )
+                                .unwrap();
+                            // the lighting restored the scene depth the forward-only objects and the
+                            // outlines test against; ambient occlusion only covers the g-buffer
+                            if deferred_renderer.view == GBufferView::Lit {
+                                let uniforms =
+                                    camera.add_uniforms(uniforms.add("ssao_enabled", false));
+                                let uniforms = point_light.add_uniforms(uniforms);
+                                let uniforms = spot_light.add_uniforms(uniforms);
+                                let uniforms = dir_light.add_uniforms(uniforms);
+                                let uniforms = environment.add_uniforms(uniforms);
+                                for scene_obj in scene.visible_objects() {
+                                    let mode = scene_obj.shading_override.unwrap_or(shading_mode);
+                                    if is_forward_only(mode) {
+                                        scene_obj
+                                            .draw(
+                                                &mut target,
+                                                shading_programs.get(mode),
+                                                scene_obj.add_uniforms(uniforms),
+                                            )
+                                            .unwrap();
+                                    }
+                                }
+                            }
+                            let uniforms = camera.add_uniforms(uniforms);
+                            for scene_obj in scene.visible_objects() {
+                                if scene_obj.shading_override.unwrap_or(shading_mode)
+                                    == ShadingMode::Toon
+                                {
+                                    toon_outline
+                                        .draw(
+                                            &display,
+                                            &mut target,
+                                            scene_obj,
+                                            scene_obj.add_uniforms(uniforms),
+                                            &toon_settings,
+                                        )
+                                        .unwrap();
+                                }
+                            }
+                        }
+                    }
+                    helpers
+                        .draw(
+                            &mut target,
+                            &camera,
+                            &point_light,
+                            &spot_light,
+                            &dir_light,
+                            ambient_light,
+                        )
+                        .unwrap();
                 }
                 {
                     let point_light_scene = SceneLight::new(&display, &point_light);
                     let spot_light_scene = SceneLight::new(&display, &spot_light.point_light);
 
                     point_light_scene
-                        .draw(&mut frame, &light_program, uniforms)
+                        .draw(&mut target, &light_program, camera.add_uniforms(uniforms))
                         .unwrap();
                     spot_light_scene
-                        .draw(&mut frame, &light_program, uniforms)
+                        .draw(&mut target, &light_program, camera.add_uniforms(uniforms))
                         .unwrap();
                 }
+                if ssao.settings.enabled && ssao.settings.show_buffer {
+                    ssao.draw_debug(&mut target).unwrap();
+                }
+                drop(target);
+                if path_frame {
+                    bookmark_editor.capture_frame(&display, &post_stack, frame.get_dimensions());
+                }
+                post_stack.apply(&display, &mut frame).unwrap();
+                picker.draw(&display, &mut frame, &camera, &scene).unwrap();
+                gizmo.draw(&mut frame, &camera, &scene).unwrap();
+
+                helpers.draw_orientation(&ui, &camera);
                 platform.prepare_render(&ui, gl_window.window());
                 let draw_data = ui.render();
                 imgui_renderer
@@ -162,30 +695,207 @@ fn main() {
             }
 
             glium::glutin::event::Event::WindowEvent {
-                event: glium::glutin::event::WindowEvent::KeyboardInput { input, .. },
+                event: glium::glutin::event::WindowEvent::DroppedFile(path),
                 ..
             } => {
-                if input.virtual_keycode.is_some() && input.state == ElementState::Pressed {
-                    let step = 0.05;
-                    match input.virtual_keycode.unwrap() {
-                        VirtualKeyCode::W => spot_light.point_light.shift(0.0, step, 0.0),
-                        VirtualKeyCode::A => spot_light.point_light.shift(-step, 0.0, 0.0),
-                        VirtualKeyCode::S => spot_light.point_light.shift(0.0, -step, 0.0),
-                        VirtualKeyCode::D => spot_light.point_light.shift(step, 0.0, 0.0),
-                        _ => {}
+                let file_path = path.to_string_lossy().into_owned();
+                match FileKind::of(&file_path) {
+                    Some(FileKind::Model) => {
+                        model_loader.start(&file_path, true, LoadTarget::AddObject)
                     }
+                    Some(FileKind::Scene) => load_scene!(&file_path),
+                    Some(FileKind::Image) => match decode_diffuse_map(&path) {
+                        Ok(pixels) => match scene.selected {
+                            Some(index) => {
+                                // without a picked submesh the whole object gets the map
+                                let submeshes = match picker.hit(&scene) {
+                                    Some(hit) => vec![hit.submesh],
+                                    None => {
+                                        (0..scene.objects[index].mesh().submeshes().len()).collect()
+                                    }
+                                };
+                                let maps = submeshes
+                                    .into_iter()
+                                    .map(|submesh| {
+                                        DiffuseMap::new(&display, pixels.clone())
+                                            .map(|map| (submesh, map))
+                                    })
+                                    .collect::<Result<Vec<_>, _>>();
+                                match maps {
+                                    Ok(maps) => history.replace_diffuse_maps(
+                                        &mut scene_state!(),
+                                        index,
+                                        maps,
+                                    ),
+                                    Err(e) => eprintln!("failed to load {}: {}", file_path, e),
+                                }
+                            }
+                            None => eprintln!("select an object to apply {} to", file_path),
+                        },
+                        Err(e) => eprintln!("failed to load {}: {}", file_path, e),
+                    },
+                    Some(FileKind::Environment) => {
+                        if let Err(e) = environment.load(&display, &file_path) {
+                            eprintln!("failed to load environment {}: {}", file_path, e);
+                        }
+                    }
+                    None => eprintln!("unsupported file {}", file_path),
                 }
             }
 
             glium::glutin::event::Event::WindowEvent {
-                event: glium::glutin::event::WindowEvent::Resized(new_window_size),
+                event: glium::glutin::event::WindowEvent::KeyboardInput { input, .. },
                 ..
-            } => camera
-                .set_aspect_ratio(new_window_size.width as f32 / new_window_size.height as f32),
+            } => {
+                let ui_wants_keyboard = imgui_context.io().want_capture_keyboard;
+                if let Some(keycode) = input.virtual_keycode {
+                    if input.state == ElementState::Released || !ui_wants_keyboard {
+                        fly_controller.handle_key(keycode, input.state);
+                    }
+                }
+                if let Some(
+                    keycode @ (VirtualKeyCode::Up
+                    | VirtualKeyCode::Left
+                    | VirtualKeyCode::Down
+                    | VirtualKeyCode::Right),
+                ) = input.virtual_keycode
+                {
+                    match input.state {
+                        ElementState::Pressed if !ui_wants_keyboard => {
+                            light_keys.insert(keycode);
+                        }
+                        ElementState::Released => {
+                            light_keys.remove(&keycode);
+                        }
+                        _ => {}
+                    }
+                }
+                if let (Some(keycode), ElementState::Pressed, false) =
+                    (input.virtual_keycode, input.state, ui_wants_keyboard)
+                {
+                    let step = 0.05;
+                    let next_mode = match (keycode, camera_mode) {
+                        (VirtualKeyCode::F, CameraMode::Orbit) => Some(CameraMode::Fly),
+                        (VirtualKeyCode::F | VirtualKeyCode::Escape, CameraMode::Fly) => {
+                            Some(CameraMode::Orbit)
+                        }
+                        _ => None,
+                    };
+                    if let Some(mode) = next_mode {
+                        match mode {
+                            CameraMode::Orbit => orbit_controller.sync(&camera),
+                            CameraMode::Fly => fly_controller.reset(&camera),
+                        }
+                        let gl_window = display.gl_window();
+                        let window = gl_window.window();
+                        let flying = mode == CameraMode::Fly;
+                        if let Err(e) = window.set_cursor_grab(flying) {
+                            eprintln!("failed to capture the cursor: {}", e);
+                        }
+                        window.set_cursor_visible(!flying);
+                        camera_mode = mode;
+                    }
+
+                    // numpad views: 1 front, 3 right, 7 top, with ctrl for the opposite side
+                    let opposite = modifiers.ctrl();
+                    let view = match keycode {
+                        VirtualKeyCode::Numpad1 if opposite => Some((180.0, 0.0)),
+                        VirtualKeyCode::Numpad1 => Some((0.0, 0.0)),
+                        VirtualKeyCode::Numpad3 if opposite => Some((-90.0, 0.0)),
+                        VirtualKeyCode::Numpad3 => Some((90.0, 0.0)),
+                        VirtualKeyCode::Numpad7 if opposite => Some((0.0, -90.0)),
+                        VirtualKeyCode::Numpad7 => Some((0.0, 90.0)),
+                        _ => None,
+                    };
+                    if let (Some((yaw_deg, pitch_deg)), CameraMode::Orbit) = (view, camera_mode) {
+                        orbit_controller.snap_view(yaw_deg, pitch_deg);
+                    }
+
+                    match keycode {
+                        VirtualKeyCode::NumpadDecimal => frame_requested = true,
+                        VirtualKeyCode::T => gizmo.mode = GizmoMode::Translate,
+                        VirtualKeyCode::R => gizmo.mode = GizmoMode::Rotate,
+                        VirtualKeyCode::Z if modifiers.ctrl() && modifiers.shift() => {
+                            history.redo(&mut scene_state!())
+                        }
+                        VirtualKeyCode::Z if modifiers.ctrl() => history.undo(&mut scene_state!()),
+                        VirtualKeyCode::Y if modifiers.ctrl() => history.redo(&mut scene_state!()),
+                        VirtualKeyCode::Y => gizmo.mode = GizmoMode::Scale,
+                        VirtualKeyCode::G => {
+                            helpers.settings.show_grid = !helpers.settings.show_grid
+                        }
+                        VirtualKeyCode::L => {
+                            gizmo.space = match gizmo.space {
+                                GizmoSpace::World => GizmoSpace::Local,
+                                GizmoSpace::Local => GizmoSpace::World,
+                            };
+                        }
+                        VirtualKeyCode::Numpad5 => {
+                            camera.projection = match camera.projection {
+                                Projection::Perspective => Projection::Orthographic,
+                                Projection::Orthographic => Projection::Perspective,
+                            };
+                        }
+                        VirtualKeyCode::Up => spot_light.point_light.shift(0.0, step, 0.0),
+                        VirtualKeyCode::Left => spot_light.point_light.shift(-step, 0.0, 0.0),
+                        VirtualKeyCode::Down => spot_light.point_light.shift(0.0, -step, 0.0),
+                        VirtualKeyCode::Right => spot_light.point_light.shift(step, 0.0, 0.0),
+                        _ => {}
+                    }
+                }
+            }
 
             event => {
                 let gl_window = display.gl_window();
                 platform.handle_event(imgui_context.io_mut(), gl_window.window(), &event);
+                if let glium::glutin::event::Event::WindowEvent {
+                    event: glium::glutin::event::WindowEvent::ModifiersChanged(state),
+                    ..
+                } = &event
+                {
+                    modifiers = *state;
+                }
+                // releases that happen while unfocused never arrive
+                if let glium::glutin::event::Event::WindowEvent {
+                    event: glium::glutin::event::WindowEvent::Focused(false),
+                    ..
+                } = &event
+                {
+                    light_keys.clear();
+                }
+                match (&event, camera_mode) {
+                    (glium::glutin::event::Event::WindowEvent { event, .. }, CameraMode::Orbit) => {
+                        let ui_wants_mouse = imgui_context.io().want_capture_mouse;
+                        if !gizmo.handle_event(
+                            event,
+                            &camera,
+                            &mut scene,
+                            display.get_framebuffer_dimensions(),
+                            modifiers.ctrl(),
+                            ui_wants_mouse,
+                        ) {
+                            picker.handle_event(
+                                event,
+                                &camera,
+                                &mut scene,
+                                display.get_framebuffer_dimensions(),
+                                ui_wants_mouse,
+                            );
+                            orbit_controller.handle_event(event, ui_wants_mouse);
+                        }
+                    }
+                    (glium::glutin::event::Event::WindowEvent { event, .. }, CameraMode::Fly) => {
+                        fly_controller.handle_event(event);
+                    }
+                    (
+                        glium::glutin::event::Event::DeviceEvent {
+                            event: glium::glutin::event::DeviceEvent::MouseMotion { delta },
+                            ..
+                        },
+                        CameraMode::Fly,
+                    ) => fly_controller.handle_mouse_motion(delta.0 as f32, delta.1 as f32),
+                    _ => {}
+                }
             }
         }
     });
@@ -1,35 +1,108 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{collections::HashMap, fs, ops::Range, path::Path};
 
-use cgmath::{vec3, Vector2, Vector3};
+use cgmath::{vec3, EuclideanSpace, InnerSpace, Matrix4, Point3, Vector2, Vector3};
 use glium::{
     uniforms::{AsUniformValue, MagnifySamplerFilter, Sampler, Uniforms, UniformsStorage},
+    vertex::VertexBufferSlice,
     Display, DrawParameters, IndexBuffer, Program, Surface, Texture2d, VertexBuffer,
 };
 use image::io::Reader;
 
+use crate::{
+    bvh::{Bvh, TriangleRef},
+    instance::InstanceAttr,
+    loader::LoadProgress,
+};
+
+pub type LoadError = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Copy, Clone, Debug)]
-struct VertexPTN {
-    position: [f32; 3],
-    texcoord: [f32; 2],
-    normal: [f32; 3],
+pub struct VertexPTN {
+    pub position: [f32; 3],
+    pub texcoord: [f32; 2],
+    pub normal: [f32; 3],
 }
 
 glium::implement_vertex!(VertexPTN, position, normal, texcoord);
 
+// one inner vec per column of the image, the layout the shaders' diffuse map lookup expects
+pub type Pixels = Vec<Vec<(u8, u8, u8)>>;
+
 #[derive(Clone, Debug)]
-struct Material {
-    ns: f32,
-    ka: Vector3<f32>,
-    kd: Option<Vector3<f32>>,
-    ks: Vector3<f32>,
-    mapkd: Vec<Vec<(u8, u8, u8)>>,
+pub struct Material {
+    pub ns: f32,
+    pub ka: Vector3<f32>,
+    pub kd: Option<Vector3<f32>>,
+    pub ks: Vector3<f32>,
+    mapkd: Pixels,
+}
+
+impl Material {
+    // for formats without materials
+    fn plain() -> Material {
+        Material {
+            ns: 32.0,
+            ka: vec3(0.2, 0.2, 0.2),
+            kd: Some(vec3(0.8, 0.8, 0.8)),
+            ks: vec3(0.3, 0.3, 0.3),
+            mapkd: Vec::new(),
+        }
+    }
+
+    // what an mtl file defined for one material, which has to include Ns, Ka and Ks
+    fn from_mtl(
+        name: &str,
+        ns: Option<f32>,
+        ka: Option<Vector3<f32>>,
+        kd: Option<Vector3<f32>>,
+        ks: Option<Vector3<f32>>,
+        mapkd: Pixels,
+    ) -> Result<Material, LoadError> {
+        let missing = |what: &str| format!("material '{}' has no {}", name, what);
+        Ok(Material {
+            ns: ns.ok_or_else(|| missing("Ns"))?,
+            ka: ka.ok_or_else(|| missing("Ka"))?,
+            kd,
+            ks: ks.ok_or_else(|| missing("Ks"))?,
+            mapkd,
+        })
+    }
+
+    pub fn has_mapkd(&self) -> bool {
+        !self.mapkd.is_empty()
+    }
+
+    pub fn params(&self) -> MaterialParams {
+        MaterialParams {
+            ns: self.ns,
+            ka: self.ka,
+            kd: self.kd,
+            ks: self.ks,
+        }
+    }
+}
+
+// the editable part of a material, everything but the diffuse map
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MaterialParams {
+    pub ns: f32,
+    pub ka: Vector3<f32>,
+    pub kd: Option<Vector3<f32>>,
+    pub ks: Vector3<f32>,
 }
 
-struct SubMesh {
+pub struct SubMesh {
     name: Option<String>,
+    material_name: String,
     vertex_buffer: VertexBuffer<VertexPTN>,
     index_buffer: IndexBuffer<u32>,
+    // cpu copy of the index buffer for picking
+    indices: Vec<u32>,
     material: Material,
+    mapkd: Texture2d,
+    pub visible: bool,
+    // while any submesh of the mesh is soloed only the soloed ones are drawn
+    pub solo: bool,
 }
 
 impl SubMesh {
@@ -37,40 +110,91 @@ impl SubMesh {
         display: &Display,
         vertices: &[VertexPTN],
         vertex_indices: &[u32],
+        material_name: &str,
         material: Material,
         group_name: Option<&str>,
     ) -> SubMesh {
-        let name = match group_name {
-            Some(name) => Some(String::from(name)),
-            None => None,
-        };
+        let name = group_name.map(String::from);
 
-        let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+        let vertex_buffer = VertexBuffer::new(display, vertices).unwrap();
         let index_buffer = IndexBuffer::new(
             display,
             glium::index::PrimitiveType::TrianglesList,
-            &vertex_indices,
+            vertex_indices,
         )
         .unwrap();
+        // materials without a diffuse map still need something bound to the sampler
+        let mapkd = if !material.mapkd.is_empty() {
+            Texture2d::new(display, material.mapkd.clone()).unwrap()
+        } else {
+            Texture2d::new(display, vec![vec![(255u8, 255u8, 255u8)]]).unwrap()
+        };
 
         SubMesh {
             name,
+            material_name: String::from(material_name),
             vertex_buffer,
             index_buffer,
+            indices: vertex_indices.to_vec(),
             material,
+            mapkd,
+            visible: true,
+            solo: false,
         }
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn material_name(&self) -> &str {
+        &self.material_name
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material_params(&mut self, params: MaterialParams) {
+        self.material.ns = params.ns;
+        self.material.ka = params.ka;
+        self.material.kd = params.kd;
+        self.material.ks = params.ks;
+    }
+
+    // trades diffuse maps with the caller, which keeps the old one e.g. for undo
+    pub fn swap_diffuse_map(&mut self, map: &mut DiffuseMap) {
+        std::mem::swap(&mut self.material.mapkd, &mut map.pixels);
+        std::mem::swap(&mut self.mapkd, &mut map.texture);
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    #[allow(clippy::type_complexity)]
     fn add_uniforms<'a, T, R>(
-        &self,
+        &'a self,
         uniforms: UniformsStorage<'a, T, R>,
     ) -> UniformsStorage<
         'a,
-        [f32; 3],
+        Sampler<'a, Texture2d>,
         UniformsStorage<
             'a,
-            [f32; 3],
-            UniformsStorage<'a, [f32; 3], UniformsStorage<'a, f32, UniformsStorage<'a, T, R>>>,
+            bool,
+            UniformsStorage<
+                'a,
+                [f32; 3],
+                UniformsStorage<
+                    'a,
+                    [f32; 3],
+                    UniformsStorage<
+                        'a,
+                        [f32; 3],
+                        UniformsStorage<'a, f32, UniformsStorage<'a, T, R>>,
+                    >,
+                >,
+            >,
         >,
     >
     where
@@ -84,72 +208,134 @@ impl SubMesh {
             Some(v) => <Vector3<f32> as Into<[f32; 3]>>::into(v),
             None => [0.0, 0.0, 0.0],
         };
+        let mapkd = self
+            .mapkd
+            .sampled()
+            .magnify_filter(MagnifySamplerFilter::Linear);
 
         uniforms
             .add("ns", ns)
             .add("ka", ka)
             .add("kd", kd)
             .add("ks", ks)
+            .add("has_mapkd", !self.material.mapkd.is_empty())
+            .add("mapkd", mapkd)
     }
 
+    // one instanced draw call for every instance in `instances`
     fn draw<S, T, R>(
         &self,
-        display: &Display,
         frame: &mut S,
+        instances: &VertexBufferSlice<InstanceAttr>,
         program: &Program,
         uniforms: UniformsStorage<T, R>,
+        params: &DrawParameters,
     ) -> Result<(), glium::DrawError>
     where
         S: Surface,
         T: AsUniformValue,
         R: Uniforms,
     {
-        use std::time::Instant;
-
-        let now = Instant::now();
-        let mapkd = if !self.material.mapkd.is_empty() {
-            Texture2d::new(display, self.material.mapkd.clone()).unwrap()
-        } else {
-            Texture2d::empty(display, 0, 0).unwrap()
-        };
-        let mapkd = mapkd
-            .sampled()
-            .magnify_filter(MagnifySamplerFilter::Linear);
-        let elasped = now.elapsed();
-        dbg!(elasped);
-
-        let uniforms = uniforms.add("mapkd", mapkd);
-        dbg!(mapkd);
-
         frame.draw(
-            &self.vertex_buffer,
+            (&self.vertex_buffer, instances.per_instance().unwrap()),
             &self.index_buffer,
-            &program,
+            program,
             &self.add_uniforms(uniforms),
-            &DrawParameters {
-                depth: glium::Depth {
-                    test: glium::DepthTest::IfLess,
-                    write: true,
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
+            params,
         )
     }
 }
 
+pub fn decode_diffuse_map(file_path: &Path) -> Result<Pixels, LoadError> {
+    let texture_image = Reader::open(file_path)?.decode()?;
+    let texture_image = texture_image.into_rgb8();
+
+    let mut buffer: Pixels = Vec::new();
+    for x in 0..texture_image.width() {
+        buffer.push(Vec::new());
+        for y in 0..texture_image.height() {
+            let pixel = *texture_image.get_pixel(x, y);
+            buffer
+                .last_mut()
+                .unwrap()
+                .push((pixel.0[0], pixel.0[1], pixel.0[2]));
+        }
+    }
+    Ok(buffer)
+}
+
+// a diffuse map along with the pixels the material keeps, so it can be swapped in whole
+pub struct DiffuseMap {
+    pixels: Pixels,
+    texture: Texture2d,
+}
+
+impl DiffuseMap {
+    // `pixels` as returned by `decode_diffuse_map`
+    pub fn new(
+        display: &Display,
+        pixels: Pixels,
+    ) -> Result<DiffuseMap, Box<dyn std::error::Error>> {
+        let texture = Texture2d::new(display, pixels.clone())?;
+        Ok(DiffuseMap { pixels, texture })
+    }
+}
+
 pub struct TriangleMesh {
+    file_path: String,
+    normalized: bool,
     obj_center: Vector3<f32>,
     obj_extent: Vector3<f32>,
+    // cpu copy of the vertices shared by every submesh, for picking
+    vertices: Vec<VertexPTN>,
     submeshes: Vec<SubMesh>,
+    // over every submesh's triangles, refit to the owning object's world matrix
+    bvh: Bvh,
 }
 
-impl TriangleMesh {
-    pub fn new(
-        display: &Display,
+struct SubMeshData {
+    name: Option<String>,
+    material_name: String,
+    material: Material,
+    indices: Range<usize>,
+}
+
+// an obj and its materials parsed and decoded on the cpu, everything but the gpu upload, so it can
+// be built off the main thread
+pub struct MeshData {
+    file_path: String,
+    normalized: bool,
+    obj_center: Vector3<f32>,
+    obj_extent: Vector3<f32>,
+    vertices: Vec<VertexPTN>,
+    vertex_indices: Vec<u32>,
+    submeshes: Vec<SubMeshData>,
+    bvh: Bvh,
+}
+
+impl MeshData {
+    // an obj with its mtl files and textures, or an ascii or binary stl; reports the bytes parsed
+    // and textures decoded to `progress` and gives up once it is cancelled
+    pub fn parse(
         file_path: &str,
         normalize: bool,
-    ) -> Result<TriangleMesh, Box<dyn std::error::Error>> {
+        progress: &LoadProgress,
+    ) -> Result<MeshData, LoadError> {
+        let is_stl = Path::new(file_path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("stl"));
+        if is_stl {
+            Self::parse_stl(file_path, normalize, progress)
+        } else {
+            Self::parse_obj(file_path, normalize, progress)
+        }
+    }
+
+    fn parse_obj(
+        file_path: &str,
+        normalize: bool,
+        progress: &LoadProgress,
+    ) -> Result<MeshData, LoadError> {
         let mut vertices = Vec::new();
         let mut vertex_indices = Vec::new();
         let mut prev_index: usize = 0;
@@ -165,67 +351,57 @@ impl TriangleMesh {
         let mut group_name: Option<&str> = None;
         let mut mtl_name: Option<&str> = None;
 
-        let parent_path = std::path::Path::new(file_path).parent().unwrap();
+        let parent_path = Path::new(file_path).parent().unwrap_or(Path::new(""));
         let file = fs::read_to_string(file_path)?;
+        progress.start_parsing(file.len());
 
-        for mut line in file.lines() {
+        for (line_index, mut line) in file.lines().enumerate() {
+            progress.add_bytes_parsed(line.len() + 1);
+            if line_index % 4096 == 0 && progress.is_cancelled() {
+                return Err("cancelled".into());
+            }
             if let Some(index) = line.find('#') {
                 line = &line[0..index];
             }
             line = line.trim();
 
-            let mut data = line.split_whitespace();
+            let mut data = Tokens::new(line, line_index);
             if let Some(first_word) = data.next() {
                 match first_word {
-                    "v" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        let y: f32 = data.next().unwrap().parse().unwrap();
-                        let z: f32 = data.next().unwrap().parse().unwrap();
-                        positions.push(cgmath::vec3(x, y, z));
-                    }
-                    "vt" => {
-                        let u: f32 = data.next().unwrap().parse().unwrap();
-                        let v: f32 = data.next().unwrap().parse().unwrap();
-                        texcoords.push(cgmath::vec2(u, v));
-                    }
-                    "vn" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        let y: f32 = data.next().unwrap().parse().unwrap();
-                        let z: f32 = data.next().unwrap().parse().unwrap();
-                        normals.push(cgmath::vec3(x, y, z));
-                    }
+                    "v" => positions.push(data.vec3()?),
+                    "vt" => texcoords.push(cgmath::vec2(data.number()?, data.number()?)),
+                    "vn" => normals.push(data.vec3()?),
                     "f" => {
                         let mut vertices_count = 0;
-                        for v in data {
+                        while let Some(v) = data.next() {
                             vertices_count += 1;
 
                             let mut indices = v.split('/');
-                            let p_index = translate_index(
-                                positions.len(),
-                                indices.next().unwrap().parse::<i32>().unwrap(),
-                            );
-                            let uv_index = translate_index(
-                                texcoords.len(),
-                                indices.next().unwrap().parse::<i32>().unwrap(),
-                            );
-                            let n_index = translate_index(
-                                normals.len(),
-                                indices.next().unwrap().parse::<i32>().unwrap(),
-                            );
+                            let mut corner = |size: usize| {
+                                let index = indices.next().and_then(|i| i.parse::<i64>().ok());
+                                // 1-based, negative counts back from the last one defined so far
+                                let index = match index {
+                                    Some(index) if index < 0 => size as i64 + index,
+                                    Some(index) => index - 1,
+                                    None => -1,
+                                };
+                                usize::try_from(index)
+                                    .ok()
+                                    .filter(|index| *index < size)
+                                    .ok_or_else(|| data.error(&format!("bad face vertex '{}'", v)))
+                            };
+                            let p_index = corner(positions.len())?;
+                            let uv_index = corner(texcoords.len())?;
+                            let n_index = corner(normals.len())?;
 
                             vertices.push(VertexPTN {
-                                position: positions[p_index as usize].into(),
-                                normal: normals[n_index as usize].into(),
-                                texcoord: texcoords[uv_index as usize].into(),
+                                position: positions[p_index].into(),
+                                normal: normals[n_index].into(),
+                                texcoord: texcoords[uv_index].into(),
                             });
-
-                            fn translate_index(size: usize, index: i32) -> u32 {
-                                if index < 0 {
-                                    (size as i32 + index).try_into().unwrap()
-                                } else {
-                                    (index - 1).try_into().unwrap()
-                                }
-                            }
+                        }
+                        if vertices_count < 3 {
+                            return Err(data.error("a face needs at least 3 vertices"));
                         }
                         for i in 2..vertices_count {
                             vertex_indices.push((vertices.len() - vertices_count) as u32);
@@ -236,26 +412,18 @@ impl TriangleMesh {
                         cur_index += (vertices_count - 2) * 3;
                     }
                     "mtllib" => {
-                        let mtl_file_name = data.next().unwrap();
-                        Self::load_mtl(
-                            parent_path.join(mtl_file_name).to_str().unwrap(),
-                            &mut materials,
-                        )?;
+                        let mtl_file_name = data.word()?;
+                        Self::load_mtl(&parent_path.join(mtl_file_name), &mut materials, progress)?;
                     }
                     "g" => {
-                        group_name = Some(data.next().unwrap());
+                        group_name = Some(data.word()?);
                     }
                     "usemtl" => {
-                        // todo!()
-                        if mtl_name.is_some() {
-                            submesh_attr.push((
-                                mtl_name.unwrap(),
-                                group_name,
-                                [prev_index, cur_index],
-                            ));
+                        if let Some(mtl_name) = mtl_name {
+                            submesh_attr.push((mtl_name, group_name, [prev_index, cur_index]));
                         }
 
-                        mtl_name = Some(data.next().unwrap());
+                        mtl_name = Some(data.word()?);
                         prev_index = cur_index;
                     }
                     _ => {
@@ -265,11 +433,143 @@ impl TriangleMesh {
             }
         }
 
-        submesh_attr.push((mtl_name.unwrap(), group_name, [prev_index, cur_index]));
+        let mtl_name = mtl_name.ok_or("the model doesn't use any material")?;
+        submesh_attr.push((mtl_name, group_name, [prev_index, cur_index]));
+
+        let submeshes = submesh_attr
+            .iter()
+            .map(|attr| {
+                let material = materials
+                    .get(attr.0)
+                    .ok_or_else(|| format!("material '{}' isn't defined", attr.0))?;
+                Ok(SubMeshData {
+                    name: attr.1.map(String::from),
+                    material_name: String::from(attr.0),
+                    material: material.clone(),
+                    indices: attr.2[0]..attr.2[1],
+                })
+            })
+            .collect::<Result<_, LoadError>>()?;
+
+        Self::assemble(
+            file_path,
+            normalize,
+            &positions,
+            vertices,
+            vertex_indices,
+            submeshes,
+        )
+    }
+
+    // binary files are an 80 byte header, the triangle count and 50 bytes per triangle; anything
+    // else is read as ascii. the mesh gets one submesh with a plain material
+    fn parse_stl(
+        file_path: &str,
+        normalize: bool,
+        progress: &LoadProgress,
+    ) -> Result<MeshData, LoadError> {
+        let bytes = fs::read(file_path)?;
+        progress.start_parsing(bytes.len());
+
+        // normal and corners of every triangle
+        let mut facets = Vec::<[Vector3<f32>; 4]>::new();
+        let binary_count = bytes
+            .get(80..84)
+            .map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize)
+            .filter(|count| {
+                count.checked_mul(50).and_then(|size| size.checked_add(84)) == Some(bytes.len())
+            });
+        if let Some(count) = binary_count {
+            for (index, record) in bytes[84..].chunks_exact(50).take(count).enumerate() {
+                progress.add_bytes_parsed(50);
+                if index % 4096 == 0 && progress.is_cancelled() {
+                    return Err("cancelled".into());
+                }
+                let value = |i: usize| {
+                    f32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]])
+                };
+                let vector = |i: usize| vec3(value(i), value(i + 4), value(i + 8));
+                facets.push([vector(0), vector(12), vector(24), vector(36)]);
+            }
+        } else {
+            let file =
+                std::str::from_utf8(&bytes).map_err(|_| "neither a binary stl nor an ascii one")?;
+            let mut normal = vec3(0.0, 0.0, 0.0);
+            let mut corners = Vec::new();
+            for (line_index, line) in file.lines().enumerate() {
+                progress.add_bytes_parsed(line.len() + 1);
+                if line_index % 4096 == 0 && progress.is_cancelled() {
+                    return Err("cancelled".into());
+                }
+
+                let mut data = Tokens::new(line.trim(), line_index);
+                match data.next() {
+                    Some("facet") => {
+                        if data.word()? != "normal" {
+                            return Err(data.error("expected 'normal'"));
+                        }
+                        normal = data.vec3()?;
+                        corners.clear();
+                    }
+                    Some("vertex") => corners.push(data.vec3()?),
+                    Some("endfacet") => match corners[..] {
+                        [a, b, c] => facets.push([normal, a, b, c]),
+                        _ => return Err(data.error("a facet needs exactly 3 vertices")),
+                    },
+                    // solid, outer loop, endloop, endsolid
+                    _ => {}
+                }
+            }
+        }
+
+        let mut positions = Vec::with_capacity(facets.len() * 3);
+        let mut vertices = Vec::with_capacity(facets.len() * 3);
+        for [normal, a, b, c] in facets {
+            // some exporters leave the normals zero
+            let normal = if normal.magnitude2() > 0.0 {
+                normal.normalize()
+            } else {
+                (b - a).cross(c - a).normalize()
+            };
+            for corner in [a, b, c] {
+                positions.push(corner);
+                vertices.push(VertexPTN {
+                    position: corner.into(),
+                    texcoord: [0.0, 0.0],
+                    normal: normal.into(),
+                });
+            }
+        }
+        let vertex_indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+        let submeshes = vec![SubMeshData {
+            name: None,
+            material_name: String::from("default"),
+            material: Material::plain(),
+            indices: 0..vertex_indices.len(),
+        }];
+
+        Self::assemble(
+            file_path,
+            normalize,
+            &positions,
+            vertices,
+            vertex_indices,
+            submeshes,
+        )
+    }
 
+    // centers and scales the vertices when normalizing and builds the bvh
+    fn assemble(
+        file_path: &str,
+        normalize: bool,
+        positions: &[Vector3<f32>],
+        mut vertices: Vec<VertexPTN>,
+        vertex_indices: Vec<u32>,
+        submeshes: Vec<SubMeshData>,
+    ) -> Result<MeshData, LoadError> {
         // calculate center and extent
-        let mut min_extent = positions.first().unwrap().clone();
-        let mut max_extent = positions.first().unwrap().clone();
+        let mut min_extent = *positions.first().ok_or("the model has no vertices")?;
+        let mut max_extent = min_extent;
         for vp in positions.iter() {
             min_extent.x = min_extent.x.min(vp.x);
             min_extent.y = min_extent.y.min(vp.y);
@@ -284,116 +584,97 @@ impl TriangleMesh {
 
         if normalize {
             let max_length = max_extent.x.max(max_extent.y).max(max_extent.z);
-            for mut v in vertices.iter_mut() {
+            for v in vertices.iter_mut() {
                 v.position = ((Vector3::from(v.position) - obj_center) / max_length).into();
             }
             obj_center = Vector3::new(0.0, 0.0, 0.0);
             obj_extent /= max_length;
         }
 
-        let mut submeshes: Vec<SubMesh> = Vec::new();
-        for attr in &submesh_attr {
-            submeshes.push(SubMesh::new(
-                display,
-                &vertices[..],
-                &vertex_indices[attr.2[0]..attr.2[1]],
-                materials[attr.0].clone(),
-                attr.1,
-            ));
-        }
-
-        Ok(TriangleMesh {
+        let triangles = submeshes
+            .iter()
+            .enumerate()
+            .flat_map(|(submesh, data)| {
+                vertex_indices[data.indices.clone()]
+                    .chunks_exact(3)
+                    .enumerate()
+                    .map(move |(triangle, indices)| (TriangleRef { submesh, triangle }, indices))
+            })
+            .map(|(triangle_ref, indices)| {
+                let corner = |i: usize| Point3::from(vertices[indices[i] as usize].position);
+                (triangle_ref, [corner(0), corner(1), corner(2)])
+            })
+            .collect();
+        let bvh = Bvh::new(triangles);
+
+        Ok(MeshData {
+            file_path: String::from(file_path),
+            normalized: normalize,
             obj_center,
             obj_extent,
+            vertices,
+            vertex_indices,
             submeshes,
+            bvh,
         })
     }
 
     fn load_mtl(
-        file_path: &str,
+        file_path: &Path,
         materials: &mut HashMap<String, Material>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let file = fs::read_to_string(file_path)?;
+        progress: &LoadProgress,
+    ) -> Result<(), LoadError> {
+        let file =
+            fs::read_to_string(file_path).map_err(|e| format!("{}: {}", file_path.display(), e))?;
+        progress.add_textures(
+            file.lines()
+                .filter(|line| line.trim_start().starts_with("map_Kd"))
+                .count(),
+        );
 
         let mut mtl_name: Option<&str> = None;
         let mut ns: Option<f32> = None;
         let mut ka: Option<Vector3<f32>> = None;
         let mut kd: Option<Vector3<f32>> = None;
         let mut ks: Option<Vector3<f32>> = None;
-        let mut mapkd: Vec<Vec<(u8, u8, u8)>> = Vec::new();
+        let mut mapkd: Pixels = Vec::new();
 
-        for mut line in file.lines() {
+        // ns, ka and ks carry over to the next material when it doesn't set them
+        for (line_index, mut line) in file.lines().enumerate() {
             if let Some(index) = line.find('#') {
                 line = &line[0..index];
             }
             line = line.trim();
 
-            let mut data = line.split_whitespace();
+            let mut data = Tokens::new(line, line_index);
 
             if let Some(first_word) = data.next() {
                 match first_word {
                     "newmtl" => {
-                        if mtl_name.is_some() {
+                        if let Some(mtl_name) = mtl_name {
                             materials.insert(
-                                String::from(mtl_name.unwrap()),
-                                Material {
-                                    ns: ns.unwrap(),
-                                    ka: ks.unwrap(),
-                                    kd,
-                                    ks: ks.unwrap(),
-                                    mapkd,
-                                },
+                                String::from(mtl_name),
+                                Material::from_mtl(mtl_name, ns, ka, kd, ks, mapkd)?,
                             );
                             kd = None;
                             mapkd = Vec::new();
                         }
 
-                        mtl_name = Some(data.next().unwrap());
-                    }
-                    "Ns" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        ns = Some(x);
-                    }
-                    "Ka" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        let y: f32 = data.next().unwrap().parse().unwrap();
-                        let z: f32 = data.next().unwrap().parse().unwrap();
-                        ka = Some(vec3(x, y, z))
-                    }
-                    "Kd" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        let y: f32 = data.next().unwrap().parse().unwrap();
-                        let z: f32 = data.next().unwrap().parse().unwrap();
-                        kd = Some(vec3(x, y, z))
-                    }
-                    "Ks" => {
-                        let x: f32 = data.next().unwrap().parse().unwrap();
-                        let y: f32 = data.next().unwrap().parse().unwrap();
-                        let z: f32 = data.next().unwrap().parse().unwrap();
-                        ks = Some(vec3(x, y, z))
+                        mtl_name = Some(data.word()?);
                     }
+                    "Ns" => ns = Some(data.number()?),
+                    "Ka" => ka = Some(data.vec3()?),
+                    "Kd" => kd = Some(data.vec3()?),
+                    "Ks" => ks = Some(data.vec3()?),
                     "map_Kd" => {
-                        let texture_path = data.next().unwrap();
-                        let mut parent_path = Path::new(file_path).to_path_buf();
-                        parent_path.pop();
-                        let texture_path = parent_path.join(texture_path);
-                        dbg!(&texture_path);
-                        let texture_image = Reader::open(texture_path)?.decode()?;
-                        let texture_image = texture_image.into_rgb8();
-
-                        let mut buffer: Vec<Vec<(u8, u8, u8)>> = Vec::new();
-                        for x in 0..texture_image.width() {
-                            buffer.push(Vec::new());
-                            for y in 0..texture_image.height() {
-                                let pixel = texture_image.get_pixel(x, y).clone();
-                                buffer
-                                    .last_mut()
-                                    .unwrap()
-                                    .push((pixel.0[0], pixel.0[1], pixel.0[2]));
-                            }
+                        let texture_path = data.word()?;
+                        let texture_path = file_path.with_file_name(texture_path);
+                        mapkd = decode_diffuse_map(&texture_path)
+                            .map_err(|e| format!("{}: {}", texture_path.display(), e))?;
+                        progress.texture_decoded();
+                        if progress.is_cancelled() {
+                            return Err("cancelled".into());
                         }
-
-                        mapkd = buffer;
                     }
                     _ => {
                         // don't care
@@ -403,34 +684,262 @@ impl TriangleMesh {
             }
         }
 
+        let mtl_name =
+            mtl_name.ok_or_else(|| format!("{} has no materials", file_path.display()))?;
         materials.insert(
-            String::from(mtl_name.unwrap()),
-            Material {
-                ns: ns.unwrap(),
-                ka: ka.unwrap(),
-                kd,
-                ks: ks.unwrap(),
-                mapkd,
-            },
+            String::from(mtl_name),
+            Material::from_mtl(mtl_name, ns, ka, kd, ks, mapkd)?,
         );
 
         Ok(())
     }
+}
+
+// the whitespace separated words of one obj or mtl line, with errors that say where they happened
+struct Tokens<'a> {
+    words: std::str::SplitWhitespace<'a>,
+    keyword: &'a str,
+    line_index: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str, line_index: usize) -> Self {
+        Tokens {
+            words: line.split_whitespace(),
+            keyword: "",
+            line_index,
+        }
+    }
+
+    // the first call yields the keyword
+    fn next(&mut self) -> Option<&'a str> {
+        let word = self.words.next();
+        if self.keyword.is_empty() {
+            self.keyword = word.unwrap_or_default();
+        }
+        word
+    }
+
+    fn error(&self, message: &str) -> LoadError {
+        format!(
+            "line {} ('{}'): {}",
+            self.line_index + 1,
+            self.keyword,
+            message
+        )
+        .into()
+    }
+
+    fn word(&mut self) -> Result<&'a str, LoadError> {
+        self.next().ok_or_else(|| self.error("missing a value"))
+    }
+
+    fn number(&mut self) -> Result<f32, LoadError> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| self.error(&format!("'{}' is not a number", word)))
+    }
+
+    fn vec3(&mut self) -> Result<Vector3<f32>, LoadError> {
+        Ok(vec3(self.number()?, self.number()?, self.number()?))
+    }
+}
+
+impl TriangleMesh {
+    pub fn new(
+        display: &Display,
+        file_path: &str,
+        normalize: bool,
+    ) -> Result<TriangleMesh, Box<dyn std::error::Error>> {
+        let data = MeshData::parse(file_path, normalize, &LoadProgress::new())
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        Ok(Self::upload(display, data))
+    }
+
+    // no triangles at all, standing in for `file_path` while it loads; saved like the real mesh
+    pub fn placeholder(file_path: &str, normalize: bool) -> TriangleMesh {
+        TriangleMesh {
+            file_path: String::from(file_path),
+            normalized: normalize,
+            obj_center: vec3(0.0, 0.0, 0.0),
+            obj_extent: vec3(0.0, 0.0, 0.0),
+            vertices: Vec::new(),
+            submeshes: Vec::new(),
+            bvh: Bvh::new(Vec::new()),
+        }
+    }
+
+    // creates the gpu buffers and textures, which has to happen on the main thread
+    pub fn upload(display: &Display, data: MeshData) -> TriangleMesh {
+        let submeshes = data
+            .submeshes
+            .into_iter()
+            .map(|submesh| {
+                SubMesh::new(
+                    display,
+                    &data.vertices,
+                    &data.vertex_indices[submesh.indices],
+                    &submesh.material_name,
+                    submesh.material,
+                    submesh.name.as_deref(),
+                )
+            })
+            .collect();
+
+        TriangleMesh {
+            file_path: data.file_path,
+            normalized: data.normalized,
+            obj_center: data.obj_center,
+            obj_extent: data.obj_extent,
+            vertices: data.vertices,
+            submeshes,
+            bvh: data.bvh,
+        }
+    }
+
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    pub fn is_normalized(&self) -> bool {
+        self.normalized
+    }
+
+    pub fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    pub fn submeshes_mut(&mut self) -> &mut [SubMesh] {
+        &mut self.submeshes
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    // moves the bvh's triangles from model space by `matrix`
+    pub fn refit_bvh(&mut self, matrix: Matrix4<f32>) {
+        self.bvh.refit(matrix);
+    }
+
+    // the three corners of a triangle in model space
+    pub fn triangle(&self, submesh: usize, triangle: usize) -> Option<[VertexPTN; 3]> {
+        let indices = self
+            .submeshes
+            .get(submesh)?
+            .indices
+            .get(triangle * 3..triangle * 3 + 3)?;
+        Some([
+            self.vertices[indices[0] as usize],
+            self.vertices[indices[1] as usize],
+            self.vertices[indices[2] as usize],
+        ])
+    }
+
+    // false for hidden submeshes and for unsoloed ones while another submesh is soloed
+    pub fn is_submesh_drawn(&self, index: usize) -> bool {
+        let any_solo = self.submeshes.iter().any(|submesh| submesh.solo);
+        self.submeshes
+            .get(index)
+            .is_some_and(|submesh| submesh.visible && (submesh.solo || !any_solo))
+    }
+
+    // solos only `index`, showing it even if it was hidden
+    pub fn isolate_submesh(&mut self, index: usize) {
+        for (i, submesh) in self.submeshes.iter_mut().enumerate() {
+            submesh.solo = i == index;
+        }
+        if let Some(submesh) = self.submeshes.get_mut(index) {
+            submesh.visible = true;
+        }
+    }
+
+    pub fn hide_other_submeshes(&mut self, index: usize) {
+        for (i, submesh) in self.submeshes.iter_mut().enumerate() {
+            submesh.visible = i == index;
+        }
+    }
+
+    pub fn show_all_submeshes(&mut self) {
+        for submesh in self.submeshes.iter_mut() {
+            submesh.visible = true;
+            submesh.solo = false;
+        }
+    }
+
+    // sphere around the axis-aligned bounds, in model space
+    pub fn bounding_sphere(&self) -> (Point3<f32>, f32) {
+        (
+            Point3::from_vec(self.obj_center),
+            self.obj_extent.magnitude() / 2.0,
+        )
+    }
+
+    pub fn draw_submesh<S, T, R>(
+        &self,
+        frame: &mut S,
+        index: usize,
+        instances: &VertexBufferSlice<InstanceAttr>,
+        program: &Program,
+        uniforms: UniformsStorage<T, R>,
+        params: &DrawParameters,
+    ) -> Result<(), glium::DrawError>
+    where
+        S: Surface,
+        T: AsUniformValue,
+        R: Uniforms,
+    {
+        match self.submeshes.get(index) {
+            Some(submesh) => submesh.draw(frame, instances, program, uniforms, params),
+            None => Ok(()),
+        }
+    }
 
     pub fn draw<S, T, R>(
         &self,
-        display: &Display,
         frame: &mut S,
+        instances: &VertexBufferSlice<InstanceAttr>,
+        program: &Program,
+        uniforms: UniformsStorage<T, R>,
+    ) -> Result<(), glium::DrawError>
+    where
+        S: Surface,
+        T: AsUniformValue + Clone,
+        R: Uniforms + Clone,
+    {
+        self.draw_with_parameters(
+            frame,
+            instances,
+            program,
+            uniforms,
+            &DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLess,
+                    write: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn draw_with_parameters<S, T, R>(
+        &self,
+        frame: &mut S,
+        instances: &VertexBufferSlice<InstanceAttr>,
         program: &Program,
         uniforms: UniformsStorage<T, R>,
+        params: &DrawParameters,
     ) -> Result<(), glium::DrawError>
     where
         S: Surface,
         T: AsUniformValue + Clone,
         R: Uniforms + Clone,
     {
-        for submesh in &self.submeshes {
-            submesh.draw(display, frame, program, uniforms.clone())?;
+        for (index, submesh) in self.submeshes.iter().enumerate() {
+            if self.is_submesh_drawn(index) {
+                submesh.draw(frame, instances, program, uniforms.clone(), params)?;
+            }
         }
         Ok(())
     }
@@ -0,0 +1,589 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use glium::{
+    glutin::event::{ElementState, MouseButton, WindowEvent},
+    index::{NoIndices, PrimitiveType},
+    uniform, Blend, Display, DrawParameters, PolygonMode, Program, Surface, VertexBuffer,
+};
+
+use crate::{
+    bvh::{Aabb, NearestPoint},
+    camera::Camera,
+    id_buffer::{IdBuffer, IdPixel},
+    mesh::{TriangleMesh, VertexPTN},
+    scene::Scene,
+    scene_obj::SceneObject,
+};
+
+// cursor travel between press and release that still counts as a click rather than an orbit
+const CLICK_SLOP_PX: f32 = 4.0;
+const SUBMESH_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 0.25];
+const TRIANGLE_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 0.6];
+const EDGE_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+const POINT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// keeps secondary rays from hitting the surface they start on
+const RAY_EPSILON: f32 = 1e-4;
+
+#[derive(Copy, Clone, Debug)]
+struct VertexP {
+    position: [f32; 3],
+}
+
+glium::implement_vertex!(VertexP, position);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PickMethod {
+    // cpu ray cast against every object's bvh, right when the click happens
+    Ray,
+    // gpu id pass over the visible scene, resolved on the next frame
+    IdBuffer,
+}
+
+impl PickMethod {
+    pub const ALL: [PickMethod; 2] = [PickMethod::Ray, PickMethod::IdBuffer];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PickMethod::Ray => "ray cast",
+            PickMethod::IdBuffer => "id buffer",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PickHit {
+    pub object: usize,
+    pub instance: usize,
+    pub submesh: usize,
+    pub triangle: usize,
+    // weights of the triangle's three corners at the hit point
+    pub barycentric: Vector3<f32>,
+    // along the world-space ray, which has unit length
+    pub distance: f32,
+}
+
+// closest drawn triangle of any visible object along a world-space ray with unit direction
+pub fn pick(scene: &Scene, origin: Point3<f32>, direction: Vector3<f32>) -> Option<PickHit> {
+    let mut best: Option<PickHit> = None;
+    for (index, object) in scene.objects.iter().enumerate() {
+        if !scene.is_visible(index) {
+            continue;
+        }
+        let drawn = drawn_submeshes(object.mesh());
+        for instance in 0..object.instance_count() {
+            let t_max = best.map_or(f32::INFINITY, |hit| hit.distance);
+            // the ray isn't renormalized, so its parameter is still a world-space distance
+            let (bvh_origin, bvh_direction) = ray_to_bvh(object, instance, origin, direction);
+            if let Some(hit) =
+                object
+                    .mesh()
+                    .bvh()
+                    .closest_hit(bvh_origin, bvh_direction, t_max, |t| drawn[t.submesh])
+            {
+                best = Some(PickHit {
+                    object: index,
+                    instance,
+                    submesh: hit.triangle.submesh,
+                    triangle: hit.triangle.triangle,
+                    barycentric: hit.barycentric,
+                    distance: hit.distance,
+                });
+            }
+        }
+    }
+    best
+}
+
+// maps an instance of the object onto its bvh, which is refit to the object's world matrix
+fn bvh_from_instance(object: &SceneObject, instance: usize) -> Matrix4<f32> {
+    if object.instances().is_empty() {
+        return Matrix4::identity();
+    }
+    let world = object.world_matrix();
+    world
+        * object
+            .instance_matrix(instance)
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+        * world.invert().unwrap_or_else(Matrix4::identity)
+}
+
+fn ray_to_bvh(
+    object: &SceneObject,
+    instance: usize,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> (Point3<f32>, Vector3<f32>) {
+    let matrix = bvh_from_instance(object, instance);
+    (
+        matrix.transform_point(origin),
+        matrix.transform_vector(direction),
+    )
+}
+
+// true if a drawn triangle of a visible object lies between the two points
+pub fn occluded(scene: &Scene, from: Point3<f32>, to: Point3<f32>) -> bool {
+    let offset = to - from;
+    let length = offset.magnitude();
+    if length <= 2.0 * RAY_EPSILON {
+        return false;
+    }
+    let direction = offset / length;
+    // both ends usually sit on a surface, keep them from hitting it
+    let origin = from + direction * RAY_EPSILON;
+    scene.objects.iter().enumerate().any(|(index, object)| {
+        let drawn = drawn_submeshes(object.mesh());
+        scene.is_visible(index)
+            && (0..object.instance_count()).any(|instance| {
+                let (bvh_origin, bvh_direction) = ray_to_bvh(object, instance, origin, direction);
+                object.mesh().bvh().any_hit(
+                    bvh_origin,
+                    bvh_direction,
+                    length - 2.0 * RAY_EPSILON,
+                    |t| drawn[t.submesh],
+                )
+            })
+    })
+}
+
+// closest point on any other visible object, with that object's index; on instances scaled
+// unevenly the point is only close to the nearest, as distances don't carry over into the bvh
+pub fn nearest_object(
+    scene: &Scene,
+    point: Point3<f32>,
+    exclude: usize,
+) -> Option<(usize, NearestPoint)> {
+    let mut best: Option<(usize, NearestPoint)> = None;
+    for (index, object) in scene.objects.iter().enumerate() {
+        if index == exclude || !scene.is_visible(index) {
+            continue;
+        }
+        let drawn = drawn_submeshes(object.mesh());
+        for instance in 0..object.instance_count() {
+            let to_bvh = bvh_from_instance(object, instance);
+            let from_bvh = to_bvh.invert().unwrap_or_else(Matrix4::identity);
+            let max_distance = match best {
+                Some((_, nearest)) if object.instances().is_empty() => nearest.distance,
+                _ => f32::INFINITY,
+            };
+            if let Some(mut nearest) = object.mesh().bvh().nearest_point(
+                to_bvh.transform_point(point),
+                max_distance,
+                |t| drawn[t.submesh],
+            ) {
+                nearest.point = from_bvh.transform_point(nearest.point);
+                nearest.distance = (nearest.point - point).magnitude();
+                if best.is_none_or(|(_, best)| nearest.distance < best.distance) {
+                    best = Some((index, nearest));
+                }
+            }
+        }
+    }
+    best
+}
+
+// the triangle the id pass found, with the hit point where the ray through its pixel meets the
+// triangle's plane; at the edges that can be just outside the triangle, as the rasterizer saw it
+fn hit_from_id(
+    scene: &Scene,
+    id: IdPixel,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<PickHit> {
+    let object = scene.objects.get(id.object)?;
+    let corners = object.mesh().triangle(id.submesh, id.triangle)?;
+    let matrix = object.instance_world_matrix(id.instance);
+    let [a, b, c] = corners.map(|vertex| matrix.transform_point(vertex.position.into()));
+    let normal = (b - a).cross(c - a);
+    let facing = direction.dot(normal);
+    let distance = if facing.abs() > 1e-12 {
+        (a - origin).dot(normal) / facing
+    } else {
+        // seen edge-on, any point of the triangle is as good
+        (Point3::centroid(&[a, b, c]) - origin).dot(direction)
+    };
+    let p = origin + direction * distance;
+    // signed sub-triangle areas against the full one
+    let area = normal.magnitude2().max(f32::EPSILON);
+    let weight = |u: Point3<f32>, v: Point3<f32>| (u - p).cross(v - p).dot(normal) / area;
+    Some(PickHit {
+        object: id.object,
+        instance: id.instance,
+        submesh: id.submesh,
+        triangle: id.triangle,
+        barycentric: Vector3::new(weight(b, c), weight(c, a), weight(a, b)),
+        distance,
+    })
+}
+
+fn drawn_submeshes(mesh: &TriangleMesh) -> Vec<bool> {
+    (0..mesh.submeshes().len())
+        .map(|index| mesh.is_submesh_drawn(index))
+        .collect()
+}
+
+// click-to-select in the viewport, with the picked submesh and triangle highlighted
+pub struct Picker {
+    pub highlight_submesh: bool,
+    // half size of the box around the hit whose triangles are counted
+    pub query_radius: f32,
+    pub method: PickMethod,
+    program: Program,
+    id_buffer: IdBuffer,
+    cursor: Option<(f32, f32)>,
+    press: Option<(f32, f32)>,
+    // window position of a click waiting for the next id pass
+    pending: Option<(f32, f32)>,
+    hit: Option<PickHit>,
+}
+
+impl Picker {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("shaders/highlight.vs"),
+            include_str!("shaders/highlight.fs"),
+            None,
+        )
+        .unwrap();
+
+        Picker {
+            highlight_submesh: true,
+            query_radius: 0.05,
+            method: PickMethod::Ray,
+            program,
+            id_buffer: IdBuffer::new(display),
+            cursor: None,
+            press: None,
+            pending: None,
+            hit: None,
+        }
+    }
+
+    // the hit, as long as its object is still the selected one
+    pub fn hit(&self, scene: &Scene) -> Option<PickHit> {
+        self.hit.filter(|hit| {
+            scene.selected == Some(hit.object)
+                && scene
+                    .objects
+                    .get(hit.object)
+                    .filter(|object| hit.instance < object.instance_count())
+                    .and_then(|object| object.mesh().triangle(hit.submesh, hit.triangle))
+                    .is_some()
+        })
+    }
+
+    // never consumes the event, a click without motion doesn't move the orbit camera anyway
+    pub fn handle_event(
+        &mut self,
+        event: &WindowEvent,
+        camera: &Camera,
+        scene: &mut Scene,
+        window_size: (u32, u32),
+        ui_wants_mouse: bool,
+    ) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.press = None;
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.press = self.cursor.filter(|_| !ui_wants_mouse);
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let (press, cursor) = match (self.press.take(), self.cursor) {
+                    (Some(press), Some(cursor)) => (press, cursor),
+                    _ => return,
+                };
+                if (cursor.0 - press.0).hypot(cursor.1 - press.1) > CLICK_SLOP_PX {
+                    return;
+                }
+                match self.method {
+                    PickMethod::Ray => {
+                        let ndc_x = cursor.0 / window_size.0 as f32 * 2.0 - 1.0;
+                        let ndc_y = 1.0 - cursor.1 / window_size.1 as f32 * 2.0;
+                        let (origin, direction) = camera.screen_ray(ndc_x, ndc_y);
+                        self.hit = pick(scene, origin, direction);
+                        scene.selected = self.hit.map(|hit| hit.object);
+                    }
+                    PickMethod::IdBuffer => self.pending = Some(cursor),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // runs the id pass for a pending click; call once the frame's world matrices and camera
+    // viewport are final, so the ids match what is drawn
+    pub fn resolve_pending(
+        &mut self,
+        display: &Display,
+        camera: &Camera,
+        scene: &mut Scene,
+        window_size: (u32, u32),
+        (width, height): (u32, u32),
+    ) -> Result<(), glium::DrawError> {
+        let cursor = match self.pending.take() {
+            Some(cursor) => cursor,
+            None => return Ok(()),
+        };
+        // the scene may be rendered at a different scale than the window
+        let fraction_x = (cursor.0 / window_size.0 as f32).clamp(0.0, 1.0);
+        let fraction_y = (1.0 - cursor.1 / window_size.1 as f32).clamp(0.0, 1.0);
+        let x = ((fraction_x * width as f32) as u32).min(width.saturating_sub(1));
+        let y = ((fraction_y * height as f32) as u32).min(height.saturating_sub(1));
+
+        self.id_buffer.resize(display, width, height);
+        let id = self.id_buffer.read(display, camera, scene, (x, y))?;
+        let ndc_x = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+        let ndc_y = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+        let (origin, direction) = camera.screen_ray(ndc_x, ndc_y);
+        self.hit = id.and_then(|id| hit_from_id(scene, id, origin, direction));
+        scene.selected = self.hit.map(|hit| hit.object);
+        Ok(())
+    }
+
+    pub fn draw<S: Surface>(
+        &self,
+        display: &Display,
+        frame: &mut S,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> Result<(), glium::DrawError> {
+        let hit = match self.hit(scene) {
+            Some(hit) => hit,
+            None => return Ok(()),
+        };
+        let object = &scene.objects[hit.object];
+        let corners = object.mesh().triangle(hit.submesh, hit.triangle).unwrap();
+        let point = interpolate(&corners, hit.barycentric, |vertex| vertex.position.into());
+        let vertices = corners
+            .iter()
+            .map(|vertex| VertexP {
+                position: vertex.position,
+            })
+            .chain(std::iter::once(VertexP {
+                position: point.into(),
+            }))
+            .collect::<Vec<_>>();
+        let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+
+        // drawn over the post-processed image, so there's no scene depth to test against
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            line_width: Some(2.0),
+            point_size: Some(8.0),
+            ..Default::default()
+        };
+        // the highlight program ignores instance attributes, it follows the picked copy instead
+        let world_matrix: [[f32; 4]; 4] = object.instance_world_matrix(hit.instance).into();
+        let uniforms = |color: [f32; 4]| {
+            camera.add_uniforms(uniform! {
+                highlight_color: color,
+                world_matrix: world_matrix,
+            })
+        };
+
+        let instance = object.instance_slice(hit.instance..hit.instance + 1);
+        if let (true, Some(instance)) = (self.highlight_submesh, instance) {
+            object.mesh().draw_submesh(
+                frame,
+                hit.submesh,
+                &instance,
+                &self.program,
+                uniforms(SUBMESH_COLOR),
+                &DrawParameters {
+                    polygon_mode: PolygonMode::Line,
+                    line_width: Some(1.0),
+                    ..params.clone()
+                },
+            )?;
+        }
+        frame.draw(
+            vertex_buffer.slice(0..3).unwrap(),
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.program,
+            &uniforms(TRIANGLE_COLOR),
+            &params,
+        )?;
+        frame.draw(
+            vertex_buffer.slice(0..3).unwrap(),
+            NoIndices(PrimitiveType::LineLoop),
+            &self.program,
+            &uniforms(EDGE_COLOR),
+            &params,
+        )?;
+        frame.draw(
+            vertex_buffer.slice(3..4).unwrap(),
+            NoIndices(PrimitiveType::Points),
+            &self.program,
+            &uniforms(POINT_COLOR),
+            &params,
+        )
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui, scene: &mut Scene, light_position: Point3<f32>) {
+        imgui::Window::new("Pick").build(ui, || {
+            ui.checkbox("highlight submesh", &mut self.highlight_submesh);
+            let mut method_index = PickMethod::ALL
+                .iter()
+                .position(|method| *method == self.method)
+                .unwrap();
+            if ui.combo("method", &mut method_index, &PickMethod::ALL, |method| {
+                method.name().into()
+            }) {
+                self.method = PickMethod::ALL[method_index];
+            }
+            let hit = match self.hit(scene) {
+                Some(hit) => hit,
+                None => {
+                    ui.text("click an object to pick it");
+                    return;
+                }
+            };
+            let object = &scene.objects[hit.object];
+            let mesh = object.mesh();
+            let submesh = &mesh.submeshes()[hit.submesh];
+            let material = submesh.material();
+            let corners = mesh.triangle(hit.submesh, hit.triangle).unwrap();
+
+            ui.text(format!("object: {}", object.name));
+            ui.text(format!(
+                "submesh {}: {}",
+                hit.submesh,
+                submesh.name().unwrap_or("(unnamed)")
+            ));
+            ui.text(format!("triangle: {}", hit.triangle));
+            if !object.instances().is_empty() {
+                ui.text(format!("instance: {}", hit.instance));
+            }
+            // follows the object if it moved since the click
+            let position =
+                object
+                    .instance_world_matrix(hit.instance)
+                    .transform_point(Point3::from_vec(interpolate(
+                        &corners,
+                        hit.barycentric,
+                        |vertex| vertex.position.into(),
+                    )));
+            ui.text(format!(
+                "hit: {} at distance {:.3}",
+                format_vector(position.into()),
+                hit.distance
+            ));
+            ui.text(format!(
+                "barycentric: ({:.3}, {:.3}, {:.3})",
+                hit.barycentric.x, hit.barycentric.y, hit.barycentric.z
+            ));
+
+            ui.separator();
+            ui.text(format!("material: {}", submesh.material_name()));
+            // edited on a copy, written back once the rest of the window is done reading
+            let mut params = material.params();
+            imgui::Slider::new("ns", 0.0, 1000.0).build(ui, &mut params.ns);
+            edit_color(ui, "ka", &mut params.ka);
+            match &mut params.kd {
+                Some(kd) => edit_color(ui, "kd", kd),
+                None => ui.text("kd: none"),
+            }
+            edit_color(ui, "ks", &mut params.ks);
+            ui.text(format!("diffuse map: {}", material.has_mapkd()));
+
+            ui.separator();
+            for (i, vertex) in corners.iter().enumerate() {
+                build_vertex_ui(ui, &format!("vertex {}", i), vertex);
+            }
+            let normal = interpolate(&corners, hit.barycentric, |vertex| vertex.normal.into());
+            let texcoord = interpolate(&corners, hit.barycentric, |vertex| {
+                Vector3::new(vertex.texcoord[0], vertex.texcoord[1], 0.0)
+            });
+            ui.text("at hit:");
+            ui.text(format!(
+                "  normal {}",
+                format_vector(normal.normalize().into())
+            ));
+            ui.text(format!("  uv ({:.3}, {:.3})", texcoord.x, texcoord.y));
+
+            ui.separator();
+            ui.text(format!("bvh: {} nodes", mesh.bvh().node_count()));
+            let shadowed = occluded(scene, position, light_position);
+            ui.text(format!(
+                "point light: {}",
+                if shadowed { "blocked" } else { "visible" }
+            ));
+            match nearest_object(scene, position, hit.object) {
+                Some((index, nearest)) => {
+                    ui.text(format!(
+                        "nearest object: {} at {:.3}",
+                        scene.objects[index].name, nearest.distance
+                    ));
+                    ui.text(format!(
+                        "  point {} on submesh {} triangle {}",
+                        format_vector(nearest.point.into()),
+                        nearest.triangle.submesh,
+                        nearest.triangle.triangle
+                    ));
+                }
+                None => ui.text("nearest object: none"),
+            }
+            imgui::Slider::new("query radius", 0.001, 0.5).build(ui, &mut self.query_radius);
+            // mapped into the bvh's space like the other queries; for rotated instances that is the
+            // bounds of the rotated box
+            let query = Aabb::from_center(
+                position,
+                Vector3::new(self.query_radius, self.query_radius, self.query_radius),
+            )
+            .transformed(bvh_from_instance(&scene.objects[hit.object], hit.instance));
+            ui.text(format!(
+                "triangles in box: {}",
+                mesh.bvh().overlapping(&query).len()
+            ));
+
+            if params != material.params() {
+                scene.objects[hit.object].mesh_mut().submeshes_mut()[hit.submesh]
+                    .set_material_params(params);
+            }
+        });
+    }
+}
+
+fn interpolate(
+    corners: &[VertexPTN; 3],
+    barycentric: Vector3<f32>,
+    attribute: fn(&VertexPTN) -> Vector3<f32>,
+) -> Vector3<f32> {
+    attribute(&corners[0]) * barycentric.x
+        + attribute(&corners[1]) * barycentric.y
+        + attribute(&corners[2]) * barycentric.z
+}
+
+fn build_vertex_ui(ui: &imgui::Ui, label: &str, vertex: &VertexPTN) {
+    ui.text(format!("{}:", label));
+    ui.text(format!("  position {}", format_vector(vertex.position)));
+    ui.text(format!("  normal {}", format_vector(vertex.normal)));
+    ui.text(format!(
+        "  uv ({:.3}, {:.3})",
+        vertex.texcoord[0], vertex.texcoord[1]
+    ));
+}
+
+fn edit_color(ui: &imgui::Ui, label: &str, color: &mut Vector3<f32>) {
+    let mut array: [f32; 3] = (*color).into();
+    if imgui::ColorEdit::new(label, &mut array).build(ui) {
+        *color = array.into();
+    }
+}
+
+fn format_vector(v: [f32; 3]) -> String {
+    format!("({:.3}, {:.3}, {:.3})", v[0], v[1], v[2])
+}
@@ -0,0 +1,707 @@
+use std::{borrow::Cow, fs};
+
+use glium::{
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    index::{NoIndices, PrimitiveType},
+    texture::{
+        ClientFormat, DepthFormat, DepthTexture2dMultisample, MipmapsOption, RawImage3d,
+        Texture2dMultisample, Texture3d, UncompressedFloatFormat,
+    },
+    uniform,
+    uniforms::{
+        MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction,
+        Uniforms,
+    },
+    Display, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::environment::{fullscreen_triangle, VertexP2};
+
+const IDENTITY_LUT_SIZE: u32 = 16;
+const MAX_LUT_SIZE: u32 = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneOperator {
+    Reinhard,
+    Aces,
+    Clamp,
+}
+
+impl ToneOperator {
+    pub const ALL: [ToneOperator; 3] = [
+        ToneOperator::Reinhard,
+        ToneOperator::Aces,
+        ToneOperator::Clamp,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneOperator::Reinhard => "reinhard",
+            ToneOperator::Aces => "aces",
+            ToneOperator::Clamp => "clamp",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PostEffect {
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        blur_passes: i32,
+    },
+    Fxaa {
+        span_max: f32,
+        reduce_mul: f32,
+    },
+    Vignette {
+        strength: f32,
+        radius: f32,
+        softness: f32,
+    },
+    ColorGrading {
+        intensity: f32,
+    },
+    ToneMapping {
+        operator: ToneOperator,
+        exposure: f32,
+        gamma: f32,
+    },
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom { .. } => "bloom",
+            PostEffect::Fxaa { .. } => "fxaa",
+            PostEffect::Vignette { .. } => "vignette",
+            PostEffect::ColorGrading { .. } => "color grading",
+            PostEffect::ToneMapping { .. } => "tone mapping",
+        }
+    }
+}
+
+pub struct EffectSlot {
+    pub enabled: bool,
+    pub effect: PostEffect,
+}
+
+impl EffectSlot {
+    // every effect in its usual order, all disabled so the output matches the plain scene
+    pub fn defaults() -> Vec<EffectSlot> {
+        let effects = [
+            PostEffect::Bloom {
+                threshold: 1.0,
+                intensity: 0.5,
+                blur_passes: 4,
+            },
+            PostEffect::ToneMapping {
+                operator: ToneOperator::Aces,
+                exposure: 1.0,
+                gamma: 2.2,
+            },
+            PostEffect::ColorGrading { intensity: 1.0 },
+            PostEffect::Vignette {
+                strength: 0.5,
+                radius: 0.75,
+                softness: 0.45,
+            },
+            PostEffect::Fxaa {
+                span_max: 8.0,
+                reduce_mul: 1.0 / 8.0,
+            },
+        ];
+        effects
+            .into_iter()
+            .map(|effect| EffectSlot {
+                enabled: false,
+                effect,
+            })
+            .collect()
+    }
+}
+
+struct PostPrograms {
+    copy: Program,
+    bloom_extract: Program,
+    blur: Program,
+    bloom_composite: Program,
+    fxaa: Program,
+    vignette: Program,
+    color_grading: Program,
+    tone_mapping: Program,
+}
+
+impl PostPrograms {
+    fn new(display: &Display) -> Self {
+        let program = |fragment_shader| {
+            Program::from_source(
+                display,
+                include_str!("shaders/fullscreen.vs"),
+                fragment_shader,
+                None,
+            )
+            .unwrap()
+        };
+
+        PostPrograms {
+            copy: program(include_str!("shaders/post_copy.fs")),
+            bloom_extract: program(include_str!("shaders/post_bloom_extract.fs")),
+            blur: program(include_str!("shaders/post_blur.fs")),
+            bloom_composite: program(include_str!("shaders/post_bloom_composite.fs")),
+            fxaa: program(include_str!("shaders/post_fxaa.fs")),
+            vignette: program(include_str!("shaders/post_vignette.fs")),
+            color_grading: program(include_str!("shaders/post_color_grading.fs")),
+            tone_mapping: program(include_str!("shaders/post_tone_mapping.fs")),
+        }
+    }
+}
+
+pub struct PostStack {
+    pub effects: Vec<EffectSlot>,
+    lut: Texture3d,
+    lut_size: u32,
+    lut_path: Option<String>,
+    programs: PostPrograms,
+    fullscreen: VertexBuffer<VertexP2>,
+    msaa_samples: u32,
+    // the scene is drawn here instead of `scene_color` when msaa is on, then resolved into it
+    multisampled: Option<(Texture2dMultisample, DepthTexture2dMultisample)>,
+    scene_color: Texture2d,
+    scene_depth: DepthRenderBuffer,
+    ping_pong: [Texture2d; 2],
+    bloom: [Texture2d; 2],
+}
+
+impl PostStack {
+    pub fn new(display: &Display, msaa_samples: u32) -> Self {
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (lut_size, lut_data) = identity_lut(IDENTITY_LUT_SIZE);
+
+        PostStack {
+            effects: EffectSlot::defaults(),
+            lut: create_lut(display, lut_size, lut_data).unwrap(),
+            lut_size,
+            lut_path: None,
+            programs: PostPrograms::new(display),
+            fullscreen: fullscreen_triangle(display),
+            msaa_samples,
+            multisampled: multisampled_targets(display, width, height, msaa_samples),
+            scene_color: color_target(display, width, height),
+            scene_depth: DepthRenderBuffer::new(
+                display,
+                DepthFormat::F32,
+                width.max(1),
+                height.max(1),
+            )
+            .unwrap(),
+            ping_pong: [
+                color_target(display, width, height),
+                color_target(display, width, height),
+            ],
+            bloom: [
+                color_target(display, width / 2, height / 2),
+                color_target(display, width / 2, height / 2),
+            ],
+        }
+    }
+
+    fn resize(&mut self, display: &Display, width: u32, height: u32) {
+        if self.scene_color.dimensions() == (width.max(1), height.max(1)) {
+            return;
+        }
+        self.multisampled = multisampled_targets(display, width, height, self.msaa_samples);
+        self.scene_color = color_target(display, width, height);
+        self.scene_depth =
+            DepthRenderBuffer::new(display, DepthFormat::F32, width.max(1), height.max(1)).unwrap();
+        self.ping_pong = [
+            color_target(display, width, height),
+            color_target(display, width, height),
+        ];
+        self.bloom = [
+            color_target(display, width / 2, height / 2),
+            color_target(display, width / 2, height / 2),
+        ];
+    }
+
+    pub fn set_msaa_samples(&mut self, display: &Display, msaa_samples: u32) {
+        if self.msaa_samples != msaa_samples {
+            self.msaa_samples = msaa_samples;
+            let (width, height) = self.scene_color.dimensions();
+            self.multisampled = multisampled_targets(display, width, height, msaa_samples);
+        }
+    }
+
+    // the hdr target the scene is rendered into before the effect chain runs
+    pub fn scene_target(
+        &mut self,
+        display: &Display,
+        width: u32,
+        height: u32,
+    ) -> SimpleFrameBuffer<'_> {
+        self.resize(display, width, height);
+        match &self.multisampled {
+            Some((color, depth)) => SimpleFrameBuffer::with_depth_buffer(display, color, depth),
+            None => {
+                SimpleFrameBuffer::with_depth_buffer(display, &self.scene_color, &self.scene_depth)
+            }
+        }
+        .unwrap()
+    }
+
+    // accepts `.cube` files and horizontal strips of `size` tiles of `size`x`size` pixels
+    pub fn load_lut(
+        &mut self,
+        display: &Display,
+        file_path: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (size, data) = if file_path.to_lowercase().ends_with(".cube") {
+            parse_cube_lut(&fs::read_to_string(file_path)?)?
+        } else {
+            strip_lut(&image::open(file_path)?.into_rgb32f())?
+        };
+
+        self.lut = create_lut(display, size, data)?;
+        self.lut_size = size;
+        self.lut_path = Some(String::from(file_path));
+        Ok(())
+    }
+
+    pub fn reset_lut(&mut self, display: &Display) {
+        let (size, data) = identity_lut(IDENTITY_LUT_SIZE);
+        self.lut = create_lut(display, size, data).unwrap();
+        self.lut_size = size;
+        self.lut_path = None;
+    }
+
+    fn pass<U: Uniforms>(
+        &self,
+        display: &Display,
+        target: &Texture2d,
+        program: &Program,
+        uniforms: &U,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut framebuffer = SimpleFrameBuffer::new(display, target)?;
+        framebuffer.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            program,
+            uniforms,
+            &Default::default(),
+        )?;
+        Ok(())
+    }
+
+    // runs the enabled effects in order on the scene target and writes the result to `frame`
+    pub fn apply<S: Surface>(
+        &self,
+        display: &Display,
+        frame: &mut S,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((color, _)) = &self.multisampled {
+            let source = SimpleFrameBuffer::new(display, color)?;
+            let resolved = SimpleFrameBuffer::new(display, &self.scene_color)?;
+            source.fill(&resolved, MagnifySamplerFilter::Nearest);
+        }
+
+        let mut current = &self.scene_color;
+        let mut next = 0;
+
+        for slot in self.effects.iter().filter(|slot| slot.enabled) {
+            let output = &self.ping_pong[next];
+            match slot.effect {
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                    blur_passes,
+                } => {
+                    self.pass(
+                        display,
+                        &self.bloom[0],
+                        &self.programs.bloom_extract,
+                        &uniform! {
+                            source: linear(current),
+                            threshold: threshold,
+                        },
+                    )?;
+                    for _ in 0..blur_passes.max(1) {
+                        self.pass(
+                            display,
+                            &self.bloom[1],
+                            &self.programs.blur,
+                            &uniform! {
+                                source: linear(&self.bloom[0]),
+                                direction: [1.0f32, 0.0],
+                            },
+                        )?;
+                        self.pass(
+                            display,
+                            &self.bloom[0],
+                            &self.programs.blur,
+                            &uniform! {
+                                source: linear(&self.bloom[1]),
+                                direction: [0.0f32, 1.0],
+                            },
+                        )?;
+                    }
+                    self.pass(
+                        display,
+                        output,
+                        &self.programs.bloom_composite,
+                        &uniform! {
+                            source: linear(current),
+                            bloom: linear(&self.bloom[0]),
+                            intensity: intensity,
+                        },
+                    )?;
+                }
+                PostEffect::Fxaa {
+                    span_max,
+                    reduce_mul,
+                } => {
+                    self.pass(
+                        display,
+                        output,
+                        &self.programs.fxaa,
+                        &uniform! {
+                            source: linear(current),
+                            span_max: span_max,
+                            reduce_mul: reduce_mul,
+                        },
+                    )?;
+                }
+                PostEffect::Vignette {
+                    strength,
+                    radius,
+                    softness,
+                } => {
+                    self.pass(
+                        display,
+                        output,
+                        &self.programs.vignette,
+                        &uniform! {
+                            source: linear(current),
+                            strength: strength,
+                            radius: radius,
+                            softness: softness,
+                        },
+                    )?;
+                }
+                PostEffect::ColorGrading { intensity } => {
+                    self.pass(
+                        display,
+                        output,
+                        &self.programs.color_grading,
+                        &uniform! {
+                            source: linear(current),
+                            lut: self
+                                .lut
+                                .sampled()
+                                .wrap_function(SamplerWrapFunction::Clamp)
+                                .minify_filter(MinifySamplerFilter::Linear)
+                                .magnify_filter(MagnifySamplerFilter::Linear),
+                            lut_size: self.lut_size as f32,
+                            intensity: intensity,
+                        },
+                    )?;
+                }
+                PostEffect::ToneMapping {
+                    operator,
+                    exposure,
+                    gamma,
+                } => {
+                    let tone_operator = ToneOperator::ALL
+                        .iter()
+                        .position(|op| *op == operator)
+                        .unwrap() as i32;
+                    self.pass(
+                        display,
+                        output,
+                        &self.programs.tone_mapping,
+                        &uniform! {
+                            source: linear(current),
+                            tone_operator: tone_operator,
+                            exposure: exposure,
+                            gamma: gamma,
+                        },
+                    )?;
+                }
+            }
+            current = output;
+            next = 1 - next;
+        }
+
+        frame.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.programs.copy,
+            &uniform! { source: linear(current) },
+            &Default::default(),
+        )?;
+        Ok(())
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui, display: &Display) {
+        imgui::Window::new("Post Processing").build(ui, || {
+            let mut swap = None;
+            let mut load_lut = false;
+            let mut reset_lut = false;
+            let effect_count = self.effects.len();
+
+            for (i, slot) in self.effects.iter_mut().enumerate() {
+                let _id = ui.push_id(i as i32);
+                ui.checkbox("##enabled", &mut slot.enabled);
+                ui.same_line();
+                if ui.arrow_button("up", imgui::Direction::Up) && i > 0 {
+                    swap = Some((i - 1, i));
+                }
+                ui.same_line();
+                if ui.arrow_button("down", imgui::Direction::Down) && i + 1 < effect_count {
+                    swap = Some((i, i + 1));
+                }
+                ui.same_line();
+                imgui::TreeNode::new(slot.effect.name()).build(ui, || match &mut slot.effect {
+                    PostEffect::Bloom {
+                        threshold,
+                        intensity,
+                        blur_passes,
+                    } => {
+                        imgui::Slider::new("threshold", 0.0, 4.0).build(ui, threshold);
+                        imgui::Slider::new("intensity", 0.0, 2.0).build(ui, intensity);
+                        imgui::Slider::new("blur passes", 1, 10).build(ui, blur_passes);
+                    }
+                    PostEffect::Fxaa {
+                        span_max,
+                        reduce_mul,
+                    } => {
+                        imgui::Slider::new("span max", 1.0, 16.0).build(ui, span_max);
+                        imgui::Slider::new("reduce mul", 0.0, 0.5).build(ui, reduce_mul);
+                    }
+                    PostEffect::Vignette {
+                        strength,
+                        radius,
+                        softness,
+                    } => {
+                        imgui::Slider::new("strength", 0.0, 1.0).build(ui, strength);
+                        imgui::Slider::new("radius", 0.1, 1.5).build(ui, radius);
+                        imgui::Slider::new("softness", 0.01, 1.0).build(ui, softness);
+                    }
+                    PostEffect::ColorGrading { intensity } => {
+                        imgui::Slider::new("intensity", 0.0, 1.0).build(ui, intensity);
+                        match self.lut_path.as_deref() {
+                            Some(path) => ui.text_wrapped(path),
+                            None => ui.text("identity lut"),
+                        }
+                        load_lut = ui.button("load lut");
+                        ui.same_line();
+                        reset_lut = ui.button("reset lut");
+                    }
+                    PostEffect::ToneMapping {
+                        operator,
+                        exposure,
+                        gamma,
+                    } => {
+                        let mut operator_index = ToneOperator::ALL
+                            .iter()
+                            .position(|op| op == operator)
+                            .unwrap();
+                        if ui.combo("operator", &mut operator_index, &ToneOperator::ALL, |op| {
+                            op.name().into()
+                        }) {
+                            *operator = ToneOperator::ALL[operator_index];
+                        }
+                        imgui::Slider::new("exposure", 0.0, 8.0).build(ui, exposure);
+                        imgui::Slider::new("gamma", 1.0, 3.0).build(ui, gamma);
+                    }
+                });
+            }
+
+            if let Some((a, b)) = swap {
+                self.effects.swap(a, b);
+            }
+            if load_lut {
+                if let Some(file_path) = tinyfiledialogs::open_file_dialog(
+                    "Choose a Color Grading LUT",
+                    "./",
+                    Some((&["*.cube", "*.png"], "lut")),
+                ) {
+                    if let Err(e) = self.load_lut(display, &file_path) {
+                        eprintln!("failed to load lut {}: {}", file_path, e);
+                    }
+                }
+            }
+            if reset_lut {
+                self.reset_lut(display);
+            }
+        });
+    }
+}
+
+fn color_target(display: &Display, width: u32, height: u32) -> Texture2d {
+    Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        width.max(1),
+        height.max(1),
+    )
+    .unwrap()
+}
+
+fn multisampled_targets(
+    display: &Display,
+    width: u32,
+    height: u32,
+    samples: u32,
+) -> Option<(Texture2dMultisample, DepthTexture2dMultisample)> {
+    if samples <= 1 {
+        return None;
+    }
+
+    let (width, height) = (width.max(1), height.max(1));
+    let color = Texture2dMultisample::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+        samples,
+    );
+    let depth = DepthTexture2dMultisample::empty_with_format(
+        display,
+        DepthFormat::F32,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+        samples,
+    );
+    match (color, depth) {
+        (Ok(color), Ok(depth)) => Some((color, depth)),
+        _ => {
+            eprintln!("{}x msaa is not supported, rendering without it", samples);
+            None
+        }
+    }
+}
+
+fn linear(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    Sampler(
+        texture,
+        SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..Default::default()
+        },
+    )
+}
+
+fn create_lut(
+    display: &Display,
+    size: u32,
+    data: Vec<f32>,
+) -> Result<Texture3d, Box<dyn std::error::Error>> {
+    let raw = RawImage3d {
+        data: Cow::Owned(data),
+        width: size,
+        height: size,
+        depth: size,
+        format: ClientFormat::F32F32F32,
+    };
+    Ok(Texture3d::with_format(
+        display,
+        raw,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+    )?)
+}
+
+// lut data is stored red-fastest, then green, then blue, like the `.cube` format
+fn identity_lut(size: u32) -> (u32, Vec<f32>) {
+    let mut data = Vec::with_capacity(lut_len(size));
+    let scale = (size - 1) as f32;
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                data.extend_from_slice(&[r as f32 / scale, g as f32 / scale, b as f32 / scale]);
+            }
+        }
+    }
+    (size, data)
+}
+
+fn parse_cube_lut(file: &str) -> Result<(u32, Vec<f32>), Box<dyn std::error::Error>> {
+    let mut size = None;
+    let mut data = Vec::new();
+
+    for mut line in file.lines() {
+        if let Some(index) = line.find('#') {
+            line = &line[0..index];
+        }
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("LUT_3D_SIZE") => {
+                size = Some(
+                    words
+                        .next()
+                        .ok_or("missing LUT_3D_SIZE value")?
+                        .parse::<u32>()?,
+                );
+            }
+            Some(word)
+                if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') =>
+            {
+                data.push(word.parse::<f32>()?);
+                for _ in 0..2 {
+                    data.push(words.next().ok_or("incomplete lut entry")?.parse::<f32>()?);
+                }
+            }
+            _ => {
+                // TITLE, DOMAIN_MIN/MAX and 1D luts are not supported
+            }
+        }
+    }
+
+    let size = check_lut_size(size.ok_or("missing LUT_3D_SIZE")?)?;
+    if data.len() != lut_len(size) {
+        return Err("lut entry count does not match LUT_3D_SIZE".into());
+    }
+    Ok((size, data))
+}
+
+// the .cube format's own limits, which also keep the entry count far from overflowing
+fn check_lut_size(size: u32) -> Result<u32, Box<dyn std::error::Error>> {
+    if !(2..=MAX_LUT_SIZE).contains(&size) {
+        return Err(format!("lut size {} is outside 2..={}", size, MAX_LUT_SIZE).into());
+    }
+    Ok(size)
+}
+
+// rgb floats in a lut of `size`
+fn lut_len(size: u32) -> usize {
+    let size = size as usize;
+    size * size * size * 3
+}
+
+fn strip_lut(image: &image::Rgb32FImage) -> Result<(u32, Vec<f32>), Box<dyn std::error::Error>> {
+    let size = check_lut_size(image.height())?;
+    if image.width() != size * size {
+        return Err("lut strips must be size*size pixels wide and size pixels high".into());
+    }
+
+    // each tile is one blue slice, red grows to the right and green downwards
+    let mut data = Vec::with_capacity(lut_len(size));
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                let pixel = image.get_pixel(b * size + r, g);
+                data.extend_from_slice(&pixel.0);
+            }
+        }
+    }
+    Ok((size, data))
+}
@@ -0,0 +1,383 @@
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use glium::Display;
+
+use crate::{scene_obj::SceneObject, shading::ShadingMode};
+
+// gap left between objects placed next to each other
+const PLACEMENT_GAP: f32 = 0.1;
+
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+    pub selected: Option<usize>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            objects: Vec::new(),
+            selected: None,
+        }
+    }
+
+    // adds the object and selects it
+    pub fn add(&mut self, object: SceneObject) -> usize {
+        self.objects.push(object);
+        self.selected = Some(self.objects.len() - 1);
+        self.objects.len() - 1
+    }
+
+    // adds a root object to the right of the existing root objects along +x
+    pub fn add_beside(&mut self, mut object: SceneObject) -> usize {
+        let extent_x = |object: &SceneObject| {
+            let (center, radius) = object.mesh().bounding_sphere();
+            let scale = object
+                .scale
+                .x
+                .abs()
+                .max(object.scale.y.abs())
+                .max(object.scale.z.abs());
+            (center.x * object.scale.x, radius * scale)
+        };
+
+        let right = self
+            .objects
+            .iter()
+            .filter(|other| other.parent.is_none())
+            .map(|other| {
+                let (center_x, radius) = extent_x(other);
+                other.translation.x + center_x + radius
+            })
+            .fold(None, |right: Option<f32>, x| {
+                Some(right.map_or(x, |r| r.max(x)))
+            });
+        if let Some(right) = right {
+            let (center_x, radius) = extent_x(&object);
+            object.translation = Vector3::new(right + PLACEMENT_GAP + radius - center_x, 0.0, 0.0);
+        }
+        object.parent = None;
+        self.add(object)
+    }
+
+    // puts an object back at `index`, shifting the ones after it up; the inverse of `remove`
+    // except for the parents of its former children
+    pub fn insert(&mut self, index: usize, object: SceneObject) {
+        for other in self.objects.iter_mut() {
+            other.parent = other.parent.map(|parent| shift_up(parent, index));
+        }
+        self.selected = self.selected.map(|selected| shift_up(selected, index));
+        self.objects.insert(index, object);
+    }
+
+    // children of the removed object move up to its parent
+    pub fn remove(&mut self, index: usize) -> SceneObject {
+        let removed = self.objects.remove(index);
+        for object in self.objects.iter_mut() {
+            object.parent = match object.parent {
+                Some(parent) if parent == index => removed.parent.map(|p| shift_down(p, index)),
+                Some(parent) => Some(shift_down(parent, index)),
+                None => None,
+            };
+        }
+        self.selected = match self.selected {
+            Some(selected) if selected == index => None,
+            Some(selected) => Some(shift_down(selected, index)),
+            None => None,
+        };
+        removed
+    }
+
+    pub fn index_of(&self, id: u64) -> Option<usize> {
+        self.objects.iter().position(|object| object.id() == id)
+    }
+
+    pub fn selected_object(&self) -> Option<&SceneObject> {
+        self.selected.and_then(|index| self.objects.get(index))
+    }
+
+    pub fn selected_object_mut(&mut self) -> Option<&mut SceneObject> {
+        self.selected
+            .and_then(move |index| self.objects.get_mut(index))
+    }
+
+    // true if `ancestor` is `index` itself or one of its parents
+    pub fn is_ancestor(&self, ancestor: usize, index: usize) -> bool {
+        let mut current = Some(index);
+        for _ in 0..=self.objects.len() {
+            match current {
+                Some(i) if i == ancestor => return true,
+                Some(i) => current = self.objects.get(i).and_then(|object| object.parent),
+                None => return false,
+            }
+        }
+        false
+    }
+
+    // refuses parents that would make a cycle; the local transform is kept, so the object moves
+    // along with its new parent
+    pub fn set_parent(&mut self, index: usize, parent: Option<usize>) -> bool {
+        if let Some(parent) = parent {
+            if parent >= self.objects.len() || self.is_ancestor(index, parent) {
+                return false;
+            }
+        }
+        self.objects[index].parent = parent;
+        true
+    }
+
+    // recomputes every world matrix from the local transforms; `root` is applied above all
+    // root objects
+    pub fn update_world_matrices(&mut self, root: Matrix4<f32>) {
+        let mut world = vec![None; self.objects.len()];
+        for index in 0..self.objects.len() {
+            self.resolve_world_matrix(index, root, &mut world, 0);
+        }
+        for (object, matrix) in self.objects.iter_mut().zip(world) {
+            object.set_world_matrix(matrix.unwrap_or_else(Matrix4::identity));
+        }
+    }
+
+    fn resolve_world_matrix(
+        &self,
+        index: usize,
+        root: Matrix4<f32>,
+        world: &mut Vec<Option<Matrix4<f32>>>,
+        depth: usize,
+    ) -> Matrix4<f32> {
+        if let Some(matrix) = world[index] {
+            return matrix;
+        }
+        let object = &self.objects[index];
+        // a broken parent index or a cycle (e.g. from a hand-edited file) treats the object as a root
+        let parent = match object.parent {
+            Some(parent) if parent < self.objects.len() && depth < self.objects.len() => {
+                self.resolve_world_matrix(parent, root, world, depth + 1)
+            }
+            _ => root,
+        };
+        let matrix = parent * object.local_matrix();
+        world[index] = Some(matrix);
+        matrix
+    }
+
+    // hidden objects hide their children too
+    pub fn is_visible(&self, index: usize) -> bool {
+        let mut current = Some(index);
+        for _ in 0..=self.objects.len() {
+            match current.and_then(|i| self.objects.get(i)) {
+                Some(object) if !object.visible => return false,
+                Some(object) => current = object.parent,
+                None => return true,
+            }
+        }
+        true
+    }
+
+    pub fn visible_objects(&self) -> impl Iterator<Item = &SceneObject> {
+        self.objects
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| self.is_visible(*index))
+            .map(|(_, object)| object)
+    }
+
+    // sphere around every visible object, None for an empty scene
+    pub fn bounding_sphere(&self) -> Option<(Point3<f32>, f32)> {
+        self.visible_objects()
+            .map(|object| object.bounding_sphere())
+            .reduce(merge_spheres)
+    }
+
+    // instance buffers are only rebuilt for objects whose instances changed
+    pub fn update_instance_buffers(&mut self, display: &Display) {
+        for object in self.objects.iter_mut() {
+            object.update_instance_buffer(display);
+        }
+    }
+
+    // returns the object the user asked to remove, the caller removes it so it can be undone
+    pub fn build_ui(&mut self, ui: &imgui::Ui) -> Option<usize> {
+        let mut remove = None;
+        imgui::Window::new("Scene").build(ui, || {
+            for index in 0..self.objects.len() {
+                if self.objects[index].parent.is_none() {
+                    self.build_tree_ui(ui, index, 0);
+                }
+            }
+
+            let index = match self.selected {
+                Some(index) if index < self.objects.len() => index,
+                _ => return,
+            };
+            ui.separator();
+
+            let names = std::iter::once(String::from("(none)"))
+                .chain(
+                    self.objects
+                        .iter()
+                        .enumerate()
+                        .map(|(i, object)| format!("{} ({})", object.name, i)),
+                )
+                .collect::<Vec<_>>();
+            let mut parent_index = self.objects[index].parent.map_or(0, |parent| parent + 1);
+            if ui.combo_simple_string("parent", &mut parent_index, &names)
+                && !self.set_parent(index, parent_index.checked_sub(1))
+            {
+                eprintln!("can't parent an object to itself or one of its children");
+            }
+
+            let object = &mut self.objects[index];
+            ui.input_text("name", &mut object.name).build();
+            ui.checkbox("visible", &mut object.visible);
+
+            // index 0 means the object follows the global shading mode
+            let mut override_index = match object.shading_override {
+                Some(mode) => 1 + ShadingMode::ALL.iter().position(|m| *m == mode).unwrap(),
+                None => 0,
+            };
+            let override_names = std::iter::once("use global")
+                .chain(ShadingMode::ALL.iter().map(|mode| mode.name()))
+                .collect::<Vec<_>>();
+            if ui.combo_simple_string("object shading", &mut override_index, &override_names) {
+                object.shading_override = match override_index {
+                    0 => None,
+                    i => Some(ShadingMode::ALL[i - 1]),
+                };
+            }
+
+            if ui.button("remove object") {
+                remove = Some(index);
+            }
+        });
+        remove
+    }
+
+    fn build_tree_ui(&mut self, ui: &imgui::Ui, index: usize, depth: usize) {
+        if depth > self.objects.len() {
+            return;
+        }
+        let _id = ui.push_id(index as i32);
+        ui.checkbox("##visible", &mut self.objects[index].visible);
+        ui.same_line();
+        let triangles: usize = self.objects[index]
+            .mesh()
+            .submeshes()
+            .iter()
+            .map(|submesh| submesh.triangle_count())
+            .sum();
+        let label = match self.objects[index].instances().len() {
+            0 => format!("{} ({} tris)##object", self.objects[index].name, triangles),
+            copies => format!(
+                "{} ({} tris x{})##object",
+                self.objects[index].name, triangles, copies
+            ),
+        };
+        let node = imgui::TreeNode::new(&label)
+            .selected(self.selected == Some(index))
+            .open_on_arrow(true)
+            .open_on_double_click(true)
+            .push(ui);
+        if ui.is_item_clicked() && !ui.is_item_toggled_open() {
+            self.selected = Some(index);
+        }
+        let node = match node {
+            Some(node) => node,
+            None => return,
+        };
+
+        self.build_submeshes_ui(ui, index);
+        for child in 0..self.objects.len() {
+            if child != index && self.objects[child].parent == Some(index) {
+                self.build_tree_ui(ui, child, depth + 1);
+            }
+        }
+        node.pop();
+    }
+
+    // one row per submesh: visibility, solo, name, material and triangle count
+    fn build_submeshes_ui(&mut self, ui: &imgui::Ui, index: usize) {
+        // the child objects drawn after these rows push their indices in the same parent scope
+        let _submeshes = ui.push_id("submeshes");
+        let mesh = self.objects[index].mesh_mut();
+        let mut isolate = None;
+        let mut hide_others = None;
+        for i in 0..mesh.submeshes().len() {
+            let _id = ui.push_id(i as i32);
+            let drawn = mesh.is_submesh_drawn(i);
+            let submesh = &mut mesh.submeshes_mut()[i];
+            ui.checkbox("##visible", &mut submesh.visible);
+            ui.same_line();
+            ui.checkbox("solo", &mut submesh.solo);
+            ui.same_line();
+            let text = format!(
+                "{} [{}] {} tris",
+                submesh
+                    .name()
+                    .map_or_else(|| format!("submesh {}", i), String::from),
+                submesh.material_name(),
+                submesh.triangle_count()
+            );
+            if drawn {
+                ui.text(text);
+            } else {
+                ui.text_disabled(text);
+            }
+            ui.same_line();
+            if ui.small_button("isolate") {
+                isolate = Some(i);
+            }
+            ui.same_line();
+            if ui.small_button("hide others") {
+                hide_others = Some(i);
+            }
+        }
+        if let Some(i) = isolate {
+            mesh.isolate_submesh(i);
+        }
+        if let Some(i) = hide_others {
+            mesh.hide_other_submeshes(i);
+        }
+        if mesh.submeshes().len() > 1 && ui.small_button("show all submeshes") {
+            mesh.show_all_submeshes();
+        }
+    }
+}
+
+// smallest sphere around both spheres
+pub fn merge_spheres(
+    (c1, r1): (Point3<f32>, f32),
+    (c2, r2): (Point3<f32>, f32),
+) -> (Point3<f32>, f32) {
+    let d = (c2 - c1).magnitude();
+    if d + r2 <= r1 {
+        (c1, r1)
+    } else if d + r1 <= r2 {
+        (c2, r2)
+    } else {
+        let radius = (d + r1 + r2) / 2.0;
+        (c1 + (c2 - c1) / d * (radius - r1), radius)
+    }
+}
+
+fn shift_up(index: usize, inserted: usize) -> usize {
+    if index >= inserted {
+        index + 1
+    } else {
+        index
+    }
+}
+
+fn shift_down(index: usize, removed: usize) -> usize {
+    if index > removed {
+        index - 1
+    } else {
+        index
+    }
+}
+
+// object name shown in the scene outliner, taken from the file name
+pub fn model_name(file_path: &str) -> String {
+    std::path::Path::new(file_path).file_stem().map_or_else(
+        || String::from("model"),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
@@ -0,0 +1,554 @@
+use std::{fs, path::Path};
+
+use cgmath::{Deg, Euler, Quaternion};
+use glium::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bookmark::{Bookmarks, CameraBookmark, CameraPath},
+    camera::{Camera, Projection},
+    deferred::RenderPath,
+    environment::Environment,
+    instance::Instance,
+    light::{DirectionalLight, PointLight, SpotLight},
+    loader::{LoadTarget, ModelLoader},
+    mesh::TriangleMesh,
+    scene::{model_name, Scene},
+    scene_obj::SceneObject,
+    settings::{RenderSettings, MAX_RENDER_SCALE, MIN_RENDER_SCALE},
+    shading::ShadingMode,
+};
+
+// everything a scene file describes, borrowed from the viewer
+pub struct SceneState<'a> {
+    pub scene: &'a mut Scene,
+    pub point_light: &'a mut PointLight,
+    pub spot_light: &'a mut SpotLight,
+    pub dir_light: &'a mut DirectionalLight,
+    pub ambient_light: &'a mut [f32; 3],
+    pub environment: &'a mut Environment,
+    pub camera: &'a mut Camera,
+    pub bookmarks: &'a mut Bookmarks,
+    pub render_settings: &'a mut RenderSettings,
+    pub shading_mode: &'a mut ShadingMode,
+    pub render_path: &'a mut RenderPath,
+}
+
+// a toml document; every section is optional when loading so hand-written scenes can be short:
+//
+//   ambient = [0.005, 0.005, 0.005]
+//   shading = "phong"
+//
+//   [[models]]
+//   name = "Ivysaur"
+//   path = "models/Ivysaur/Ivysaur.obj"
+//   translation = [0.0, 0.0, 0.0]
+//   rotation = [0.0, 90.0, 0.0]     # euler angles in degrees
+//   parent = "Ground"               # name of another model, the first match is used
+//
+//   [[models.instances]]            # drawn instead of the model itself, relative to it
+//   translation = [1.0, 0.0, 0.0]
+//   tint = [1.0, 0.5, 0.5]
+//
+//   [point_light]
+//   position = [0.8, 0.0, 0.8]
+//   intensity = [0.5, 0.1, 0.1]
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SceneFile {
+    pub ambient: Option<[f64; 3]>,
+    pub shading: Option<String>,
+    pub render_path: Option<String>,
+    pub render: Option<RenderSection>,
+    pub environment: Option<EnvironmentSection>,
+    pub camera: Option<CameraSection>,
+    pub point_light: Option<PointLightSection>,
+    pub spot_light: Option<SpotLightSection>,
+    pub directional_light: Option<DirectionalLightSection>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<ModelSection>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<BookmarkSection>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathSection>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RenderSection {
+    pub msaa: u32,
+    pub render_scale: f64,
+    // only read at startup, the window's context can't change it afterwards
+    pub vsync: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnvironmentSection {
+    pub path: Option<String>,
+    #[serde(default = "one")]
+    pub intensity: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "yes")]
+    pub show_skybox: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CameraSection {
+    pub position: [f64; 3],
+    pub direction: [f64; 3],
+    #[serde(default = "unit_y")]
+    pub up: [f64; 3],
+    #[serde(default = "default_fovy")]
+    pub fovy: f64,
+    pub projection: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PointLightSection {
+    pub position: [f64; 3],
+    pub intensity: [f64; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SpotLightSection {
+    pub position: [f64; 3],
+    pub intensity: [f64; 3],
+    pub direction: [f64; 3],
+    pub cutoff_start: f64,
+    pub total_width: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DirectionalLightSection {
+    pub direction: [f64; 3],
+    pub radiance: [f64; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ModelSection {
+    pub name: Option<String>,
+    pub path: String,
+    #[serde(default = "yes")]
+    pub normalize: bool,
+    #[serde(default)]
+    pub translation: [f64; 3],
+    #[serde(default)]
+    pub rotation: [f64; 3],
+    #[serde(default = "unit_scale")]
+    pub scale: [f64; 3],
+    pub parent: Option<String>,
+    #[serde(default = "yes")]
+    pub visible: bool,
+    pub shading: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceSection>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InstanceSection {
+    #[serde(default)]
+    pub translation: [f64; 3],
+    #[serde(default)]
+    pub rotation: [f64; 3],
+    #[serde(default = "unit_scale")]
+    pub scale: [f64; 3],
+    #[serde(default = "unit_scale")]
+    pub tint: [f64; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BookmarkSection {
+    pub name: String,
+    pub position: [f64; 3],
+    pub direction: [f64; 3],
+    #[serde(default = "unit_y")]
+    pub up: [f64; 3],
+    #[serde(default = "default_fovy")]
+    pub fovy: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathSection {
+    pub name: String,
+    pub keys: Vec<PathKeySection>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathKeySection {
+    pub bookmark: String,
+    pub time: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn unit_y() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn unit_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_fovy() -> f64 {
+    30.0
+}
+
+// the file stores the shortest decimal that reads back as the same f32, so 0.1 is written as 0.1
+// rather than 0.10000000149011612
+fn to_file(value: f32) -> f64 {
+    value.to_string().parse().unwrap()
+}
+
+fn to_file3<V: Into<[f32; 3]>>(value: V) -> [f64; 3] {
+    let [x, y, z] = value.into();
+    [to_file(x), to_file(y), to_file(z)]
+}
+
+fn from_file3<V: From<[f32; 3]>>([x, y, z]: [f64; 3]) -> V {
+    V::from([x as f32, y as f32, z as f32])
+}
+
+fn to_file_rotation(rotation: Quaternion<f32>) -> [f64; 3] {
+    let euler = Euler::from(rotation);
+    to_file3([
+        Deg::from(euler.x).0,
+        Deg::from(euler.y).0,
+        Deg::from(euler.z).0,
+    ])
+}
+
+fn from_file_rotation(rotation: [f64; 3]) -> Quaternion<f32> {
+    Quaternion::from(Euler::new(
+        Deg(rotation[0] as f32),
+        Deg(rotation[1] as f32),
+        Deg(rotation[2] as f32),
+    ))
+}
+
+fn find_by_name<T: Copy>(all: &[T], name: &str, to_name: fn(&T) -> &'static str) -> Option<T> {
+    let found = all.iter().find(|value| to_name(value) == name).copied();
+    if found.is_none() {
+        eprintln!("unknown name `{}` in scene file", name);
+    }
+    found
+}
+
+impl SceneFile {
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::read_to_string(file_path)?;
+        Ok(toml::from_str(&file)?)
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(file_path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    // render settings have to be known before the window exists, so they are applied separately
+    pub fn apply_render_settings(&self, render_settings: &mut RenderSettings) {
+        if let Some(render) = &self.render {
+            render_settings.msaa_samples = render.msaa;
+            render_settings.render_scale =
+                (render.render_scale as f32).clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+            render_settings.vsync = render.vsync;
+        }
+    }
+
+    pub fn capture(state: &SceneState) -> Self {
+        let scene = &*state.scene;
+        let models = scene
+            .objects
+            .iter()
+            .map(|object| ModelSection {
+                name: Some(object.name.clone()),
+                path: String::from(object.mesh().file_path()),
+                normalize: object.mesh().is_normalized(),
+                translation: to_file3(object.translation),
+                rotation: to_file_rotation(object.rotation),
+                scale: to_file3(object.scale),
+                parent: object
+                    .parent
+                    .and_then(|parent| scene.objects.get(parent))
+                    .map(|parent| parent.name.clone()),
+                visible: object.visible,
+                shading: object
+                    .shading_override
+                    .map(|mode| String::from(mode.name())),
+                instances: object
+                    .instances()
+                    .iter()
+                    .map(|instance| InstanceSection {
+                        translation: to_file3(instance.translation),
+                        rotation: to_file_rotation(instance.rotation),
+                        scale: to_file3(instance.scale),
+                        tint: to_file3(instance.tint),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let BookmarkFile { bookmarks, paths } = BookmarkFile::capture(state.bookmarks);
+        let spot_light = &*state.spot_light;
+        SceneFile {
+            ambient: Some(to_file3(*state.ambient_light)),
+            shading: Some(String::from(state.shading_mode.name())),
+            render_path: Some(String::from(state.render_path.name())),
+            render: Some(RenderSection {
+                msaa: state.render_settings.msaa_samples,
+                render_scale: to_file(state.render_settings.render_scale),
+                vsync: state.render_settings.vsync,
+            }),
+            environment: Some(EnvironmentSection {
+                path: state.environment.source_path().map(String::from),
+                intensity: to_file(state.environment.intensity),
+                rotation: to_file(state.environment.rotation_deg),
+                show_skybox: state.environment.show_skybox,
+            }),
+            camera: Some(CameraSection {
+                position: to_file3(state.camera.position()),
+                direction: to_file3(state.camera.direction()),
+                up: to_file3(state.camera.up()),
+                fovy: to_file(state.camera.fovy()),
+                projection: Some(String::from(state.camera.projection.name())),
+            }),
+            point_light: Some(PointLightSection {
+                position: to_file3(state.point_light.position),
+                intensity: to_file3(state.point_light.intensity),
+            }),
+            spot_light: Some(SpotLightSection {
+                position: to_file3(spot_light.point_light.position),
+                intensity: to_file3(spot_light.point_light.intensity),
+                direction: to_file3(spot_light.direction()),
+                cutoff_start: to_file(spot_light.cutoff_start_deg()),
+                total_width: to_file(spot_light.total_width_deg()),
+            }),
+            directional_light: Some(DirectionalLightSection {
+                direction: to_file3(state.dir_light.direction()),
+                radiance: to_file3(state.dir_light.radiance()),
+            }),
+            models,
+            bookmarks,
+            paths,
+        }
+    }
+
+    // replaces the models and bookmarks; sections missing from the file leave the current state
+    // alone. relative paths are tried against the working directory, then the scene file's. the
+    // objects start out with placeholder meshes that `model_loader` fills in as they load
+    pub fn apply(
+        &self,
+        display: &Display,
+        file_path: &str,
+        state: &mut SceneState,
+        model_loader: &mut ModelLoader,
+    ) {
+        let base_dir = Path::new(file_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let resolve = |path: &str| {
+            if Path::new(path).exists() {
+                String::from(path)
+            } else {
+                base_dir.join(path).to_string_lossy().into_owned()
+            }
+        };
+
+        if let Some(ambient) = self.ambient {
+            *state.ambient_light = from_file3(ambient);
+        }
+        if let Some(mode) = self.shading.as_deref() {
+            *state.shading_mode = find_by_name(&ShadingMode::ALL, mode, ShadingMode::name)
+                .unwrap_or(*state.shading_mode);
+        }
+        if let Some(path) = self.render_path.as_deref() {
+            *state.render_path = find_by_name(&RenderPath::ALL, path, RenderPath::name)
+                .unwrap_or(*state.render_path);
+        }
+
+        if let Some(environment) = &self.environment {
+            match environment.path.as_deref() {
+                Some(path) => {
+                    let path = resolve(path);
+                    if let Err(e) = state.environment.load(display, &path) {
+                        eprintln!("failed to load environment {}: {}", path, e);
+                    }
+                }
+                None => state.environment.unload(),
+            }
+            state.environment.intensity = environment.intensity as f32;
+            state.environment.rotation_deg = environment.rotation as f32;
+            state.environment.show_skybox = environment.show_skybox;
+        }
+
+        if let Some(camera) = &self.camera {
+            state.camera.set_pose(
+                from_file3(camera.position),
+                from_file3(camera.direction),
+                from_file3(camera.up),
+            );
+            state.camera.set_fovy(camera.fovy as f32);
+            if let Some(projection) = camera.projection.as_deref() {
+                state.camera.projection =
+                    find_by_name(&Projection::ALL, projection, Projection::name)
+                        .unwrap_or(state.camera.projection);
+            }
+        }
+
+        if let Some(light) = &self.point_light {
+            *state.point_light =
+                PointLight::new_pi(from_file3(light.position), from_file3(light.intensity));
+        }
+        if let Some(light) = &self.spot_light {
+            *state.spot_light = SpotLight::new_pdw(
+                PointLight::new_pi(from_file3(light.position), from_file3(light.intensity)),
+                from_file3(light.direction),
+                light.cutoff_start as f32,
+                light.total_width as f32,
+            );
+        }
+        if let Some(light) = &self.directional_light {
+            *state.dir_light =
+                DirectionalLight::new_dr(from_file3(light.direction), from_file3(light.radiance));
+        }
+
+        if !self.models.is_empty() {
+            let mut scene = Scene::new();
+            let mut parents = Vec::new();
+            for model in &self.models {
+                let path = resolve(&model.path);
+                let name = model.name.clone().unwrap_or_else(|| model_name(&path));
+                let mut object =
+                    SceneObject::new(&name, TriangleMesh::placeholder(&path, model.normalize));
+                model_loader.start(&path, model.normalize, LoadTarget::Placeholder(object.id()));
+                object.translation = from_file3(model.translation);
+                object.rotation = from_file_rotation(model.rotation);
+                object.scale = from_file3(model.scale);
+                object.visible = model.visible;
+                object.shading_override = model
+                    .shading
+                    .as_deref()
+                    .and_then(|mode| find_by_name(&ShadingMode::ALL, mode, ShadingMode::name));
+                object.set_instances(
+                    model
+                        .instances
+                        .iter()
+                        .map(|instance| Instance {
+                            translation: from_file3(instance.translation),
+                            rotation: from_file_rotation(instance.rotation),
+                            scale: from_file3(instance.scale),
+                            tint: from_file3(instance.tint),
+                        })
+                        .collect(),
+                );
+                parents.push(model.parent.clone());
+                scene.add(object);
+            }
+
+            for (index, parent) in parents.iter().enumerate() {
+                if let Some(parent) = parent {
+                    let found = scene
+                        .objects
+                        .iter()
+                        .position(|object| &object.name == parent);
+                    if found.is_none() || !scene.set_parent(index, found) {
+                        eprintln!(
+                            "ignoring parent `{}` of `{}`",
+                            parent, scene.objects[index].name
+                        );
+                    }
+                }
+            }
+            scene.selected = None;
+            *state.scene = scene;
+        }
+
+        if !self.bookmarks.is_empty() || !self.paths.is_empty() {
+            *state.bookmarks = to_bookmarks(&self.bookmarks, &self.paths);
+        }
+    }
+}
+
+// the bookmark editor's own file, the bookmark and path sections of a scene file on their own
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BookmarkFile {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<BookmarkSection>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathSection>,
+}
+
+impl BookmarkFile {
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::read_to_string(file_path)?;
+        Ok(toml::from_str(&file)?)
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(file_path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn capture(bookmarks: &Bookmarks) -> Self {
+        BookmarkFile {
+            bookmarks: bookmarks
+                .bookmarks
+                .iter()
+                .map(|bookmark| BookmarkSection {
+                    name: bookmark.name.clone(),
+                    position: to_file3(bookmark.position),
+                    direction: to_file3(bookmark.direction),
+                    up: to_file3(bookmark.up),
+                    fovy: to_file(bookmark.fovy),
+                })
+                .collect(),
+            paths: bookmarks
+                .paths
+                .iter()
+                .map(|path| PathSection {
+                    name: path.name.clone(),
+                    keys: path
+                        .keys
+                        .iter()
+                        .map(|key| PathKeySection {
+                            bookmark: key.bookmark.clone(),
+                            time: to_file(key.time),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_bookmarks(&self) -> Bookmarks {
+        to_bookmarks(&self.bookmarks, &self.paths)
+    }
+}
+
+fn to_bookmarks(sections: &[BookmarkSection], paths: &[PathSection]) -> Bookmarks {
+    let mut bookmarks = Bookmarks::new();
+    bookmarks.bookmarks = sections
+        .iter()
+        .map(|bookmark| CameraBookmark {
+            name: bookmark.name.clone(),
+            position: from_file3(bookmark.position),
+            direction: from_file3(bookmark.direction),
+            up: from_file3(bookmark.up),
+            fovy: bookmark.fovy as f32,
+        })
+        .collect();
+    for path in paths {
+        let mut camera_path = CameraPath::new(&path.name);
+        for key in &path.keys {
+            camera_path.add_key(&key.bookmark, key.time as f32);
+        }
+        bookmarks.paths.push(camera_path);
+    }
+    bookmarks
+}
@@ -1,10 +1,24 @@
-use cgmath::{Matrix4, SquareMatrix};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use cgmath::{
+    InnerSpace, Matrix, Matrix4, One, Point3, Quaternion, SquareMatrix, Transform, Vector3,
+};
 use glium::{
     uniforms::{AsUniformValue, Uniforms, UniformsStorage},
+    vertex::VertexBufferSlice,
     Display, DrawParameters, IndexBuffer, Program, Surface, VertexBuffer,
 };
 
-use crate::{light::PointLight, TriangleMesh};
+use crate::{
+    instance::{Instance, InstanceAttr},
+    light::PointLight,
+    scene::merge_spheres,
+    shading::ShadingMode,
+    TriangleMesh,
+};
 
 #[derive(Copy, Clone, Debug)]
 struct VertexP {
@@ -13,24 +27,152 @@ struct VertexP {
 
 glium::implement_vertex!(VertexP, position);
 
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct SceneObject {
+    // unlike the index this stays the same while objects are added and removed around it
+    id: u64,
+    pub name: String,
     mesh: TriangleMesh,
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+    // index into the owning scene's objects; the local transform is relative to the parent
+    pub parent: Option<usize>,
+    pub visible: bool,
+    // local transform combined with every ancestor's, kept up to date by the scene
     world_matrix: Matrix4<f32>,
+    pub shading_override: Option<ShadingMode>,
+    // copies of the mesh relative to the object; none draws the object once, as itself
+    instances: Vec<Instance>,
+    // rebuilt from `instances` by `update_instance_buffer` once they change
+    instance_buffer: Option<VertexBuffer<InstanceAttr>>,
+    instances_dirty: bool,
 }
 
 impl SceneObject {
     // let mesh = TriangleMesh::new(&display, "models/Forklift/Forklift.obj", true).unwrap();
-    pub fn new(mesh: TriangleMesh) -> Self {
+    pub fn new(name: &str, mesh: TriangleMesh) -> Self {
         Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: String::from(name),
             mesh,
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            parent: None,
+            visible: true,
             world_matrix: Matrix4::from_scale(1.0),
+            shading_override: None,
+            instances: Vec::new(),
+            instance_buffer: None,
+            instances_dirty: true,
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn mesh(&self) -> &TriangleMesh {
+        &self.mesh
+    }
+
+    pub fn mesh_mut(&mut self) -> &mut TriangleMesh {
+        &mut self.mesh
+    }
+
+    // trades meshes with the caller, which keeps the old one e.g. for undo
+    pub fn swap_mesh(&mut self, mesh: &mut TriangleMesh) {
+        std::mem::swap(&mut self.mesh, mesh);
+        self.mesh.refit_bvh(self.world_matrix);
+    }
+
+    pub fn local_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world_matrix
+    }
+
+    // the mesh's bvh follows the object, refit only when the matrix actually changed
     pub fn set_world_matrix(&mut self, matrix: Matrix4<f32>) {
-        self.world_matrix = matrix;
+        if matrix != self.world_matrix {
+            self.world_matrix = matrix;
+            self.mesh.refit_bvh(matrix);
+        }
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        self.instances = instances;
+        self.instances_dirty = true;
+    }
+
+    // copies that get drawn, at least one
+    pub fn instance_count(&self) -> usize {
+        self.instances.len().max(1)
+    }
+
+    pub fn instance_matrix(&self, instance: usize) -> Matrix4<f32> {
+        self.instances
+            .get(instance)
+            .map_or_else(Matrix4::identity, Instance::matrix)
     }
 
+    pub fn instance_world_matrix(&self, instance: usize) -> Matrix4<f32> {
+        self.world_matrix * self.instance_matrix(instance)
+    }
+
+    pub fn update_instance_buffer(&mut self, display: &Display) {
+        if !self.instances_dirty {
+            return;
+        }
+        let attributes = if self.instances.is_empty() {
+            vec![Instance::new().attributes()]
+        } else {
+            self.instances.iter().map(Instance::attributes).collect()
+        };
+        self.instance_buffer = Some(VertexBuffer::new(display, &attributes).unwrap());
+        self.instances_dirty = false;
+    }
+
+    // None until the first `update_instance_buffer`, or for a range past the instances
+    pub fn instance_slice(
+        &self,
+        instances: Range<usize>,
+    ) -> Option<VertexBufferSlice<'_, InstanceAttr>> {
+        self.instance_buffer
+            .as_ref()
+            .and_then(|buffer| buffer.slice(instances))
+    }
+
+    // world-space bounding sphere around every instance, each grown by the largest axis scale of
+    // its matrix
+    pub fn bounding_sphere(&self) -> (Point3<f32>, f32) {
+        let (center, radius) = self.mesh.bounding_sphere();
+        (0..self.instance_count())
+            .map(|instance| {
+                let matrix = self.instance_world_matrix(instance);
+                let scale = matrix
+                    .x
+                    .truncate()
+                    .magnitude()
+                    .max(matrix.y.truncate().magnitude())
+                    .max(matrix.z.truncate().magnitude());
+                (matrix.transform_point(center), radius * scale)
+            })
+            .reduce(merge_spheres)
+            .unwrap()
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn add_uniforms<'a, T, R>(
         &self,
         uniforms: UniformsStorage<'a, T, R>,
@@ -43,17 +185,20 @@ impl SceneObject {
         T: AsUniformValue,
         R: Uniforms,
     {
-        let mut normal_matrix = self.world_matrix.invert().unwrap();
-        normal_matrix.transpose_self();
+        // a zero scale makes the world matrix singular, its normals are meaningless anyway
+        let normal_matrix = self
+            .world_matrix
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+            .transpose();
 
         uniforms
             .add("world_matrix", self.world_matrix.into())
-            .add("normal_matrix", normal_matrix.to_owned().into())
+            .add("normal_matrix", normal_matrix.into())
     }
 
     pub fn draw<S, T, R>(
         &self,
-        display: &Display,
         frame: &mut S,
         program: &Program,
         uniforms: UniformsStorage<T, R>,
@@ -63,7 +208,28 @@ impl SceneObject {
         T: AsUniformValue + Clone,
         R: Uniforms + Clone,
     {
-        self.mesh.draw(display, frame, program, uniforms)?;
+        if let Some(instances) = self.instance_slice(0..self.instance_count()) {
+            self.mesh.draw(frame, &instances, program, uniforms)?;
+        }
+        Ok(())
+    }
+
+    pub fn draw_with_parameters<S, T, R>(
+        &self,
+        frame: &mut S,
+        program: &Program,
+        uniforms: UniformsStorage<T, R>,
+        params: &DrawParameters,
+    ) -> Result<(), glium::DrawError>
+    where
+        S: Surface,
+        T: AsUniformValue + Clone,
+        R: Uniforms + Clone,
+    {
+        if let Some(instances) = self.instance_slice(0..self.instance_count()) {
+            self.mesh
+                .draw_with_parameters(frame, &instances, program, uniforms, params)?;
+        }
         Ok(())
     }
 }
@@ -115,7 +281,7 @@ impl<'a> SceneLight<'a> {
         frame.draw(
             &self.vertex_buffer,
             &self.index_buffer,
-            &program,
+            program,
             &self.add_uniforms(uniforms),
             &DrawParameters {
                 point_size: Some(16.0),
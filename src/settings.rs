@@ -0,0 +1,120 @@
+use std::fs;
+
+pub const DEFAULT_SETTINGS_PATH: &str = "settings.cfg";
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+pub struct RenderSettings {
+    // 0 or 1 disables multisampling of the offscreen scene target
+    pub msaa_samples: u32,
+    // the scene is rendered at window size * render_scale and resampled to the window
+    pub render_scale: f32,
+    pub vsync: bool,
+}
+
+impl RenderSettings {
+    pub fn new() -> Self {
+        RenderSettings {
+            msaa_samples: 0,
+            render_scale: 1.0,
+            vsync: true,
+        }
+    }
+
+    // reads the settings file (`--settings <path>`, or `settings.cfg` if it exists), then applies
+    // `--msaa <samples>`, `--render-scale <factor>`, `--vsync` and `--no-vsync` on top of it
+    pub fn from_args<I>(args: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let args = args.into_iter().collect::<Vec<_>>();
+
+        let mut settings = RenderSettings::new();
+        match args.iter().position(|arg| arg == "--settings") {
+            Some(index) => {
+                let file_path = args.get(index + 1).ok_or("--settings needs a file path")?;
+                settings.load(file_path)?;
+            }
+            None => {
+                if std::path::Path::new(DEFAULT_SETTINGS_PATH).exists() {
+                    settings.load(DEFAULT_SETTINGS_PATH)?;
+                }
+            }
+        }
+
+        settings.apply_args(&args)?;
+        Ok(settings)
+    }
+
+    // command line flags override whatever the settings or scene file said
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--settings" | "--scene" => {
+                    args.next();
+                }
+                "--msaa" => {
+                    self.msaa_samples =
+                        args.next().ok_or("--msaa needs a sample count")?.parse()?;
+                }
+                "--render-scale" => {
+                    self.render_scale = args
+                        .next()
+                        .ok_or("--render-scale needs a factor")?
+                        .parse()?;
+                }
+                "--vsync" => self.vsync = true,
+                "--no-vsync" => self.vsync = false,
+                _ => {}
+            }
+        }
+
+        self.render_scale = self.render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+        Ok(())
+    }
+
+    // one `key value` pair per line, `#` starts a comment:
+    //   msaa 4
+    //   render_scale 1.5
+    //   vsync off
+    pub fn load(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string(file_path)?;
+
+        for mut line in file.lines() {
+            if let Some(index) = line.find('#') {
+                line = &line[0..index];
+            }
+            line = line.trim();
+
+            let mut data = line.split_whitespace();
+            if let Some(first_word) = data.next() {
+                let value = data
+                    .next()
+                    .ok_or_else(|| format!("missing value for `{}`", first_word))?;
+                match first_word {
+                    "msaa" => self.msaa_samples = value.parse()?,
+                    "render_scale" => self.render_scale = value.parse()?,
+                    "vsync" => {
+                        self.vsync = match value {
+                            "on" | "true" | "1" => true,
+                            "off" | "false" | "0" => false,
+                            _ => return Err(format!("invalid vsync value `{}`", value).into()),
+                        }
+                    }
+                    _ => {
+                        eprintln!("unknown setting `{}` in {}", first_word, file_path);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn scaled_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            ((width as f32 * self.render_scale).round() as u32).max(1),
+            ((height as f32 * self.render_scale).round() as u32).max(1),
+        )
+    }
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec3 i_position;
+in vec3 i_color;
+
+out vec4 frag_color;
+
+uniform vec3 camera_pos;
+uniform float fade_distance;
+
+void main() {
+    float fade = 1.0 - smoothstep(0.5 * fade_distance, fade_distance, distance(i_position.xz, camera_pos.xz));
+    frag_color = vec4(i_color, fade);
+}
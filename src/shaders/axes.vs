@@ -0,0 +1,17 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 color;
+
+uniform mat4 view_projection;
+
+out vec3 i_position;
+out vec3 i_color;
+
+void main() {
+    i_position = position;
+    i_color = color;
+    gl_Position = view_projection * vec4(position, 1.0);
+    // the x and z axes lie in the grid and ground plane, pull them slightly in front of it
+    gl_Position.z -= 1e-5 * gl_Position.w;
+}
@@ -0,0 +1,159 @@
+#version 330 core
+
+out vec4 frag_color;
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+uniform sampler2D g_albedo;
+uniform sampler2D g_specular;
+uniform sampler2D g_ambient;
+uniform sampler2D g_depth;
+uniform vec2 viewport_size;
+
+uniform vec3 camera_pos;
+
+uniform vec3 ambient_light;
+
+uniform bool ssao_enabled;
+uniform sampler2D ssao_map;
+
+uniform vec3 point_light_pos;
+uniform vec3 point_light_intensity;
+
+uniform vec3 spot_light_pos;
+uniform vec3 spot_light_intensity;
+uniform vec3 spot_light_dir;
+uniform float cutoff_start;
+uniform float total_width;
+
+uniform vec3 dir_light_dir;
+uniform vec3 dir_light_radiance;
+
+uniform bool env_enabled;
+uniform sampler2D env_irradiance;
+uniform sampler2D env_prefiltered;
+uniform float env_max_lod;
+uniform float env_rotation;
+uniform float env_intensity;
+
+uniform int toon_bands;
+uniform float toon_specular_threshold;
+
+const float PI = 3.14159265359;
+
+// the values of `shading_model` in deferred.rs
+const int PHONG = 0;
+const int BLINN_PHONG = 1;
+const int TOON = 2;
+
+// material attributes of the current pixel, read from the g-buffer
+vec3 albedo;
+vec3 ks;
+float ns;
+int shading_model;
+
+// the same terms as phong_shading.fs, picked per pixel instead of per program
+vec3 diffuse(vec3 i, vec3 n, vec3 light_dir) {
+    if (shading_model == TOON) {
+        float bands = float(max(toon_bands, 1));
+        return albedo * i * ceil(max(dot(n, light_dir), 0.0) * bands) / bands;
+    }
+    return albedo * i * max(dot(n, light_dir), 0.0);
+}
+
+vec3 specular(vec3 i, vec3 n, vec3 camera_dir, vec3 light_dir) {
+    if (shading_model == TOON) {
+        vec3 half_dir = normalize(light_dir + camera_dir);
+        float highlight = pow(max(dot(n, half_dir), 0.0), ns);
+        return ks * i * step(toon_specular_threshold, highlight);
+    }
+    if (shading_model == BLINN_PHONG) {
+        vec3 half_dir = normalize(light_dir + camera_dir);
+        return ks * i * pow(max(dot(n, half_dir), 0.0), ns);
+    }
+    vec3 reflect_dir = normalize(reflect(-light_dir, n));
+    return ks * i * pow(max(dot(camera_dir, reflect_dir), 0.0), ns);
+}
+
+vec3 point_light(vec3 position, vec3 normal, vec3 camera_dir) {
+    vec3 light_dir = normalize(point_light_pos - position);
+
+    float distance = length(point_light_pos - position);
+    float attenuation = 1.0 / (distance * distance);
+    vec3 intensity = point_light_intensity * attenuation;
+
+    return diffuse(intensity, normal, light_dir) + specular(intensity, normal, camera_dir, light_dir);
+}
+
+vec3 spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
+    vec3 light_dir = normalize(spot_light_pos - position);
+
+    float cos_theta = dot(light_dir, normalize(-spot_light_dir));
+    float epsilon = cos(radians(cutoff_start)) - cos(radians(total_width));
+
+    float distance = length(spot_light_pos - position);
+    float attenuation = 1.0 / (distance * distance);
+    vec3 intensity = spot_light_intensity * clamp((cos_theta - cos(radians(total_width))) / epsilon, 0.0, 1.0)  * attenuation;
+
+    return diffuse(intensity, normal, light_dir) + specular(intensity, normal, camera_dir, light_dir);
+}
+
+vec3 dir_light(vec3 normal, vec3 camera_dir) {
+    vec3 light_dir = normalize(-dir_light_dir);
+
+    return diffuse(dir_light_radiance, normal, light_dir) + specular(dir_light_radiance, normal, camera_dir, light_dir);
+}
+
+vec2 equirect_uv(vec3 dir) {
+    float c = cos(env_rotation);
+    float s = sin(env_rotation);
+    dir = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+vec3 env_light(vec3 normal, vec3 camera_dir) {
+    vec3 reflect_dir = reflect(-camera_dir, normal);
+    float roughness = clamp(sqrt(2.0 / (ns + 2.0)), 0.0, 1.0);
+
+    vec3 irradiance = textureLod(env_irradiance, equirect_uv(normal), 0.0).rgb;
+    vec3 prefiltered = textureLod(env_prefiltered, equirect_uv(reflect_dir), roughness * env_max_lod).rgb;
+
+    return env_intensity * (albedo * irradiance + ks * prefiltered);
+}
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / viewport_size;
+    vec4 position = texture(g_position, uv);
+    if (position.w == 0.0) {
+        discard;
+    }
+
+    vec3 normal = normalize(texture(g_normal, uv).xyz);
+    vec4 specular_attr = texture(g_specular, uv);
+    vec4 albedo_attr = texture(g_albedo, uv);
+    albedo = albedo_attr.rgb;
+    shading_model = int(albedo_attr.a + 0.5);
+    ks = specular_attr.rgb;
+    ns = specular_attr.a;
+
+    vec3 camera_dir = normalize(camera_pos - position.xyz);
+
+#if defined(POINT_LIGHT)
+    frag_color = vec4(point_light(position.xyz, normal, camera_dir), 1.0);
+#elif defined(SPOT_LIGHT)
+    frag_color = vec4(spot_light(position.xyz, normal, camera_dir), 1.0);
+#else
+    // the base pass also restores the scene depth so later passes are occluded correctly
+    gl_FragDepth = texture(g_depth, uv).r;
+
+    vec3 i_color = texture(g_ambient, uv).rgb * ambient_light;
+    if (ssao_enabled) {
+        i_color *= texelFetch(ssao_map, ivec2(gl_FragCoord.xy), 0).r;
+    }
+    i_color += dir_light(normal, camera_dir);
+    if (env_enabled) {
+        i_color += env_light(normal, camera_dir);
+    }
+    frag_color = vec4(i_color, 1.0);
+#endif
+}
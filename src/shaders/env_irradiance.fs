@@ -0,0 +1,43 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D env_map;
+uniform float source_lod;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+vec3 equirect_dir(vec2 uv) {
+    float phi = (uv.x - 0.5) * 2.0 * PI;
+    float theta = (uv.y - 0.5) * PI;
+    return vec3(cos(theta) * cos(phi), sin(theta), cos(theta) * sin(phi));
+}
+
+vec2 equirect_uv(vec3 dir) {
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+void main() {
+    vec3 normal = equirect_dir(i_uv);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    // cosine-weighted integral over the hemisphere around the normal
+    vec3 irradiance = vec3(0.0);
+    float sample_count = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangent_dir = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 sample_dir = tangent_dir.x * right + tangent_dir.y * up + tangent_dir.z * normal;
+
+            irradiance += textureLod(env_map, equirect_uv(sample_dir), source_lod).rgb * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+
+    frag_color = vec4(PI * irradiance / sample_count, 1.0);
+}
@@ -0,0 +1,80 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D env_map;
+uniform vec2 env_map_size;
+uniform float roughness;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 512u;
+
+vec3 equirect_dir(vec2 uv) {
+    float phi = (uv.x - 0.5) * 2.0 * PI;
+    float theta = (uv.y - 0.5) * PI;
+    return vec3(cos(theta) * cos(phi), sin(theta), cos(theta) * sin(phi));
+}
+
+vec2 equirect_uv(vec3 dir) {
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float a) {
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + normal * h.z);
+}
+
+float distribution_ggx(float n_dot_h, float a) {
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+void main() {
+    // assume the view direction equals the reflection direction (split-sum approximation)
+    vec3 normal = equirect_dir(i_uv);
+    vec3 view_dir = normal;
+    float a = roughness * roughness;
+
+    float texel_solid_angle = 4.0 * PI / (env_map_size.x * env_map_size.y);
+
+    vec3 color = vec3(0.0);
+    float total_weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec2 xi = vec2(float(i) / float(SAMPLE_COUNT), radical_inverse(i));
+        vec3 h = importance_sample_ggx(xi, normal, a);
+        vec3 light_dir = normalize(2.0 * dot(view_dir, h) * h - view_dir);
+
+        float n_dot_l = dot(normal, light_dir);
+        if (n_dot_l > 0.0) {
+            // sample a blurrier source level for low-probability directions to avoid fireflies
+            float n_dot_h = max(dot(normal, h), 0.0);
+            float pdf = distribution_ggx(n_dot_h, a) * 0.25 + 0.0001;
+            float sample_solid_angle = 1.0 / (float(SAMPLE_COUNT) * pdf);
+            float lod = roughness == 0.0 ? 0.0 : 0.5 * log2(sample_solid_angle / texel_solid_angle);
+
+            color += textureLod(env_map, equirect_uv(light_dir), max(lod, 0.0)).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    frag_color = vec4(color / total_weight, 1.0);
+}
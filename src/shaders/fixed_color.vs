@@ -2,9 +2,10 @@
 
 layout (location = 0) in vec3 position;
 
-uniform mat4 mvp;
+uniform mat4 world_matrix;
+uniform mat4 view_projection;
 
 void main()
 {
-    gl_Position = mvp * vec4(position, 1.0);
+    gl_Position = view_projection * world_matrix * vec4(position, 1.0);
 }
@@ -0,0 +1,48 @@
+#version 330 core
+
+in vec3 i_position;
+in vec3 i_normal;
+in vec2 i_texcoord;
+in vec3 i_tint;
+
+layout (location = 0) out vec4 g_position;
+layout (location = 1) out vec4 g_normal;
+layout (location = 2) out vec4 g_albedo;
+layout (location = 3) out vec4 g_specular;
+layout (location = 4) out vec4 g_ambient;
+
+uniform vec3 ka;
+uniform vec3 kd;
+uniform bool has_mapkd;
+uniform sampler2D mapkd;
+uniform vec3 ks;
+uniform float ns;
+
+// flat shading takes the face normal, the lighting passes only see the stored normal
+uniform bool flat_normals;
+// how the lighting passes shade the pixel, see `shading_model` in deferred.rs
+uniform int shading_model;
+
+void main() {
+    vec3 albedo = kd;
+    if (has_mapkd) {
+        albedo = texture(mapkd, vec2(1.0 - i_texcoord.y, i_texcoord.x)).rgb;
+    }
+    albedo *= i_tint;
+
+    vec3 normal = normalize(i_normal);
+    if (flat_normals) {
+        // face normal from the screen-space derivatives, oriented like the vertex normals
+        normal = normalize(cross(dFdx(i_position), dFdy(i_position)));
+        if (dot(normal, i_normal) < 0.0) {
+            normal = -normal;
+        }
+    }
+
+    // w = 1 marks the pixel as covered by geometry
+    g_position = vec4(i_position, 1.0);
+    g_normal = vec4(normal, 0.0);
+    g_albedo = vec4(albedo, float(shading_model));
+    g_specular = vec4(ks, ns);
+    g_ambient = vec4(ka, 1.0);
+}
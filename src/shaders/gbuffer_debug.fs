@@ -0,0 +1,36 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+uniform sampler2D g_albedo;
+uniform sampler2D g_specular;
+uniform sampler2D g_ambient;
+uniform sampler2D g_depth;
+uniform int attachment;
+
+void main() {
+    vec3 color;
+    if (attachment == 0) {
+        color = fract(texture(g_position, i_uv).xyz);
+    } else if (attachment == 1) {
+        color = texture(g_normal, i_uv).xyz * 0.5 + 0.5;
+    } else if (attachment == 2) {
+        color = texture(g_albedo, i_uv).rgb;
+    } else if (attachment == 3) {
+        color = texture(g_specular, i_uv).rgb;
+    } else if (attachment == 4) {
+        // shininess is stored unnormalized in the alpha channel
+        color = vec3(texture(g_specular, i_uv).a / 256.0);
+    } else if (attachment == 5) {
+        color = texture(g_ambient, i_uv).rgb;
+    } else {
+        // raw depth is crowded near 1.0, stretch it to make it readable
+        color = vec3(pow(texture(g_depth, i_uv).r, 64.0));
+    }
+
+    frag_color = vec4(color, 1.0);
+}
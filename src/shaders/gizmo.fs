@@ -0,0 +1,15 @@
+#version 330 core
+
+// handle under the cursor or being dragged, -1 for none
+uniform int active_handle;
+
+in vec4 v_color;
+flat in int v_handle;
+
+out vec4 frag_color;
+
+void main()
+{
+    vec3 color = v_handle == active_handle ? vec3(1.0, 0.85, 0.1) : v_color.rgb;
+    frag_color = vec4(color, v_color.a);
+}
@@ -0,0 +1,18 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 color;
+layout (location = 2) in float handle;
+
+uniform mat4 gizmo_matrix;
+uniform mat4 view_projection;
+
+out vec4 v_color;
+flat out int v_handle;
+
+void main()
+{
+    v_color = color;
+    v_handle = int(handle + 0.5);
+    gl_Position = view_projection * gizmo_matrix * vec4(position, 1.0);
+}
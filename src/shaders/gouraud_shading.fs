@@ -1,11 +1,38 @@
 #version 330 core
 
-in vec3 i_light_color;
+in vec3 i_diffuse_light;
+in vec3 i_specular_light;
+in vec2 i_texcoord;
+in vec3 i_tint;
 
 out vec4 frag_color;
 
+uniform vec3 ka;
+uniform vec3 kd;
+uniform bool has_mapkd;
+uniform sampler2D mapkd;
+
+uniform vec3 ambient_light;
+
+uniform bool ssao_enabled;
+uniform sampler2D ssao_map;
+
+
+float ambient_occlusion() {
+    if (ssao_enabled) {
+        return texelFetch(ssao_map, ivec2(gl_FragCoord.xy), 0).r;
+    }
+    return 1.0;
+}
 
 void main()
 {
-    frag_color = vec4(i_light_color, 1.0);
+    vec3 diffuse_color = kd;
+    if (has_mapkd) {
+        diffuse_color = texture(mapkd, vec2(1.0 - i_texcoord.y, i_texcoord.x)).rgb;
+    }
+
+    diffuse_color *= i_tint;
+
+    frag_color = vec4(ka * ambient_light * ambient_occlusion() + diffuse_color * i_diffuse_light + i_specular_light, 1.0);
 }
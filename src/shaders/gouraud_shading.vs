@@ -2,20 +2,20 @@
 
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 texcoord;
+layout (location = 3) in mat4 instance_matrix;
+layout (location = 7) in mat4 instance_normal_matrix;
+layout (location = 11) in vec3 instance_tint;
 
 uniform mat4 world_matrix;
 uniform mat4 normal_matrix;
-uniform mat4 mvp;
+uniform mat4 view_projection;
 
 uniform vec3 camera_pos;
 
-uniform vec3 ka;
-uniform vec3 kd;
 uniform vec3 ks;
 uniform float ns;
 
-uniform vec3 ambient_light;
-
 uniform vec3 point_light_pos;
 uniform vec3 point_light_intensity;
 
@@ -28,24 +28,38 @@ uniform float total_width;
 uniform vec3 dir_light_dir;
 uniform vec3 dir_light_radiance;
 
-out vec3 i_light_color;
+uniform bool env_enabled;
+uniform sampler2D env_irradiance;
+uniform sampler2D env_prefiltered;
+uniform float env_max_lod;
+uniform float env_rotation;
+uniform float env_intensity;
+
+// the diffuse term is kept apart from the albedo so the texture can be applied per fragment
+out vec3 i_diffuse_light;
+out vec3 i_specular_light;
+out vec2 i_texcoord;
+out vec3 i_tint;
+
+const float PI = 3.14159265359;
 
-vec3 diffuse(vec3 kd, vec3 i, vec3 n, vec3 light_dir);
+vec3 diffuse(vec3 i, vec3 n, vec3 light_dir);
 vec3 specular(vec3 ks, vec3 i, vec3 n, vec3 camera_dir, vec3 reflect_dir, float ns);
-vec3 point_light(vec3 point_light_pos, vec3 position, vec3 normal, vec3 camera_dir);
-vec3 spot_light(vec3 spot_light_pos, vec3 position, vec3 normal, vec3 camera_dir);
-vec3 dir_light(vec3 dir_light_dir, vec3 normal, vec3 camera_dir);
+void point_light(vec3 position, vec3 normal, vec3 camera_dir);
+void spot_light(vec3 position, vec3 normal, vec3 camera_dir);
+void dir_light(vec3 normal, vec3 camera_dir);
+void env_light(vec3 normal, vec3 camera_dir);
 
 
-vec3 diffuse(vec3 kd, vec3 i, vec3 n, vec3 light_dir) {
-    return kd * i * max(dot(n, light_dir), 0.0);
+vec3 diffuse(vec3 i, vec3 n, vec3 light_dir) {
+    return i * max(dot(n, light_dir), 0.0);
 }
 
 vec3 specular(vec3 ks, vec3 i, vec3 n, vec3 camera_dir, vec3 reflect_dir, float ns) {
     return ks * i * pow(max(dot(camera_dir, reflect_dir), 0.0), ns);
 }
 
-vec3 point_light(vec3 position, vec3 normal, vec3 camera_dir) {
+void point_light(vec3 position, vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(point_light_pos - position);
     vec3 reflect_dir = reflect(-light_dir, normal);
 
@@ -53,12 +67,11 @@ vec3 point_light(vec3 position, vec3 normal, vec3 camera_dir) {
     float attenuation = 1.0 / (distance * distance);
     vec3 intensity = point_light_intensity * attenuation;
 
-    vec3 diffuse = diffuse(kd, intensity, normal, light_dir);
-    vec3 specular = specular(ks, intensity, normal, camera_dir, reflect_dir, ns);
-    return diffuse + specular;
+    i_diffuse_light += diffuse(intensity, normal, light_dir);
+    i_specular_light += specular(ks, intensity, normal, camera_dir, reflect_dir, ns);
 }
 
-vec3 spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
+void spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(spot_light_pos - position);
     vec3 reflect_dir = reflect(-light_dir, normal);
 
@@ -69,32 +82,52 @@ vec3 spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
     float attenuation = 1.0 / (distance * distance);
     vec3 intensity = spot_light_intensity * clamp((cos_theta - cos(radians(total_width))) / epsilon, 0.0, 1.0)  * attenuation;
 
-    vec3 diffuse = diffuse(kd, intensity, normal, light_dir);
-    vec3 specular = specular(ks, intensity, normal, camera_dir, reflect_dir, ns);
-    return diffuse + specular;
+    i_diffuse_light += diffuse(intensity, normal, light_dir);
+    i_specular_light += specular(ks, intensity, normal, camera_dir, reflect_dir, ns);
 }
 
-vec3 dir_light(vec3 normal, vec3 camera_dir) {
+void dir_light(vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(-dir_light_dir);
     vec3 reflect_dir = reflect(-light_dir, normal);
-    
-    vec3 diffuse = diffuse(kd, dir_light_radiance, normal, light_dir);
-    vec3 specular = specular(ks, dir_light_radiance, normal, camera_dir, reflect_dir, ns);
-    return diffuse + specular;
+
+    i_diffuse_light += diffuse(dir_light_radiance, normal, light_dir);
+    i_specular_light += specular(ks, dir_light_radiance, normal, camera_dir, reflect_dir, ns);
+}
+
+vec2 equirect_uv(vec3 dir) {
+    float c = cos(env_rotation);
+    float s = sin(env_rotation);
+    dir = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+void env_light(vec3 normal, vec3 camera_dir) {
+    vec3 reflect_dir = reflect(-camera_dir, normal);
+    float roughness = clamp(sqrt(2.0 / (ns + 2.0)), 0.0, 1.0);
+
+    vec3 irradiance = textureLod(env_irradiance, equirect_uv(normal), 0.0).rgb;
+    vec3 prefiltered = textureLod(env_prefiltered, equirect_uv(reflect_dir), roughness * env_max_lod).rgb;
+
+    i_diffuse_light += env_intensity * irradiance;
+    i_specular_light += env_intensity * ks * prefiltered;
 }
 
 void main() {
-    vec3 i_position = vec3(world_matrix * vec4(position, 1.0));
-    vec3 i_normal = vec3(normal_matrix * vec4(normal ,0.0));
-    gl_Position = mvp * vec4(position, 1.0);
+    vec3 i_position = vec3(world_matrix * instance_matrix * vec4(position, 1.0));
+    vec3 i_normal = vec3(normal_matrix * instance_normal_matrix * vec4(normal, 0.0));
+    i_texcoord = texcoord;
+    i_tint = instance_tint;
+    gl_Position = view_projection * vec4(i_position, 1.0);
 
     vec3 normalized_normal = normalize(i_normal);
     vec3 camera_dir = normalize(camera_pos - i_position);
-    
-    vec3 i_color = ka * ambient_light;
-    i_color += point_light(position, normalized_normal, camera_dir);
-    i_color += spot_light(position, normalized_normal, camera_dir);
-    i_color += dir_light(normalized_normal, camera_dir);
 
-    i_light_color = i_color; 
+    i_diffuse_light = vec3(0.0);
+    i_specular_light = vec3(0.0);
+    point_light(i_position, normalized_normal, camera_dir);
+    spot_light(i_position, normalized_normal, camera_dir);
+    dir_light(normalized_normal, camera_dir);
+    if (env_enabled) {
+        env_light(normalized_normal, camera_dir);
+    }
 }
@@ -0,0 +1,60 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform mat4 view_projection;
+uniform mat4 inv_view_projection;
+uniform vec3 camera_pos;
+
+uniform float plane_height;
+uniform float grid_spacing;
+uniform vec3 grid_color;
+uniform float fade_distance;
+
+// where the ray through this pixel meets the plane; false if that is behind the camera or past
+// the far plane
+bool hit_plane(out vec3 point) {
+    vec2 ndc = i_uv * 2.0 - 1.0;
+    vec4 near = inv_view_projection * vec4(ndc, -1.0, 1.0);
+    vec4 far = inv_view_projection * vec4(ndc, 1.0, 1.0);
+    near /= near.w;
+    far /= far.w;
+    float t = (plane_height - near.y) / (far.y - near.y);
+    point = mix(near.xyz, far.xyz, t);
+    return t > 0.0 && t < 1.0;
+}
+
+float plane_depth(vec3 point) {
+    vec4 clip = view_projection * vec4(point, 1.0);
+    return clip.z / clip.w * 0.5 + 0.5;
+}
+
+// coverage of the lines through integer coordinates, about a pixel wide at any distance
+float grid_lines(vec2 coord) {
+    vec2 width = fwidth(coord);
+    vec2 lines = abs(fract(coord - 0.5) - 0.5) / width;
+    float line = 1.0 - min(min(lines.x, lines.y), 1.0);
+    // cells only a few pixels wide would turn into moire, fade them out instead
+    return line * (1.0 - smoothstep(0.2, 0.5, max(width.x, width.y)));
+}
+
+void main() {
+    vec3 point;
+    if (!hit_plane(point)) {
+        discard;
+    }
+
+    vec2 coord = point.xz / grid_spacing;
+    float minor = grid_lines(coord);
+    float major = grid_lines(coord / 10.0);
+    float fade = 1.0 - smoothstep(0.5 * fade_distance, fade_distance, distance(point.xz, camera_pos.xz));
+    float alpha = max(0.35 * minor, 0.8 * major) * fade;
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    frag_color = vec4(grid_color, alpha);
+    gl_FragDepth = plane_depth(point);
+}
@@ -0,0 +1,122 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform mat4 view_projection;
+uniform mat4 inv_view_projection;
+uniform vec3 camera_pos;
+uniform vec2 viewport_size;
+
+uniform float plane_height;
+uniform vec3 ground_color;
+uniform float fade_distance;
+
+uniform vec3 ambient_light;
+
+uniform vec3 point_light_pos;
+uniform vec3 point_light_intensity;
+
+uniform vec3 spot_light_pos;
+uniform vec3 spot_light_intensity;
+uniform vec3 spot_light_dir;
+uniform float cutoff_start;
+uniform float total_width;
+
+uniform vec3 dir_light_dir;
+uniform vec3 dir_light_radiance;
+
+// 0 without shadows, 1 for the directional light, 2 for the spot light
+uniform int shadow_caster;
+uniform mat4 shadow_matrix;
+uniform sampler2DShadow shadow_map;
+uniform float shadow_strength;
+uniform float shadow_bias;
+
+uniform bool reflection_enabled;
+uniform sampler2D reflection_map;
+uniform float reflectivity;
+
+bool hit_plane(out vec3 point) {
+    vec2 ndc = i_uv * 2.0 - 1.0;
+    vec4 near = inv_view_projection * vec4(ndc, -1.0, 1.0);
+    vec4 far = inv_view_projection * vec4(ndc, 1.0, 1.0);
+    near /= near.w;
+    far /= far.w;
+    float t = (plane_height - near.y) / (far.y - near.y);
+    point = mix(near.xyz, far.xyz, t);
+    return t > 0.0 && t < 1.0;
+}
+
+float plane_depth(vec3 point) {
+    vec4 clip = view_projection * vec4(point, 1.0);
+    return clip.z / clip.w * 0.5 + 0.5;
+}
+
+// 1 where the caster light reaches the point, averaged over a 3x3 neighbourhood
+float light_visibility(vec3 point) {
+    vec4 light_clip = shadow_matrix * vec4(point, 1.0);
+    vec3 coord = light_clip.xyz / light_clip.w * 0.5 + 0.5;
+    // nothing outside the shadow map casts shadows
+    if (light_clip.w <= 0.0 || any(lessThan(coord, vec3(0.0))) || any(greaterThan(coord, vec3(1.0)))) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec3(coord.xy + vec2(x, y) * texel, coord.z - shadow_bias));
+        }
+    }
+    return lit / 9.0;
+}
+
+vec3 point_light(vec3 position, vec3 normal) {
+    vec3 to_light = point_light_pos - position;
+    return point_light_intensity * max(dot(normal, normalize(to_light)), 0.0) / dot(to_light, to_light);
+}
+
+vec3 spot_light(vec3 position, vec3 normal) {
+    vec3 to_light = spot_light_pos - position;
+    vec3 light_dir = normalize(to_light);
+    float cos_theta = dot(light_dir, normalize(-spot_light_dir));
+    float epsilon = cos(radians(cutoff_start)) - cos(radians(total_width));
+    float cone = clamp((cos_theta - cos(radians(total_width))) / epsilon, 0.0, 1.0);
+    return spot_light_intensity * cone * max(dot(normal, light_dir), 0.0) / dot(to_light, to_light);
+}
+
+vec3 dir_light(vec3 normal) {
+    return dir_light_radiance * max(dot(normal, normalize(-dir_light_dir)), 0.0);
+}
+
+void main() {
+    vec3 point;
+    if (!hit_plane(point)) {
+        discard;
+    }
+    float alpha = 1.0 - smoothstep(0.5 * fade_distance, fade_distance, distance(point.xz, camera_pos.xz));
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    vec3 normal = vec3(0.0, 1.0, 0.0);
+    float shadow = 1.0;
+    if (shadow_caster != 0) {
+        shadow = 1.0 - shadow_strength * (1.0 - light_visibility(point));
+    }
+
+    vec3 light = ambient_light + point_light(point, normal);
+    light += spot_light(point, normal) * (shadow_caster == 2 ? shadow : 1.0);
+    light += dir_light(normal) * (shadow_caster == 1 ? shadow : 1.0);
+    vec3 color = ground_color * light;
+
+    if (reflection_enabled) {
+        // the reflection was rendered from a mirrored camera, which leaves it flipped sideways
+        vec2 uv = gl_FragCoord.xy / viewport_size;
+        color = mix(color, texture(reflection_map, vec2(1.0 - uv.x, uv.y)).rgb, reflectivity);
+    }
+
+    frag_color = vec4(color, alpha);
+    gl_FragDepth = plane_depth(point);
+}
@@ -0,0 +1,11 @@
+#version 330 core
+
+uniform vec4 highlight_color;
+
+out vec4 frag_color;
+
+
+void main()
+{
+    frag_color = highlight_color;
+}
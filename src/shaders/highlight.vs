@@ -0,0 +1,11 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+
+uniform mat4 world_matrix;
+uniform mat4 view_projection;
+
+void main()
+{
+    gl_Position = view_projection * world_matrix * vec4(position, 1.0);
+}
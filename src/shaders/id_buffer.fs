@@ -0,0 +1,14 @@
+#version 330 core
+
+flat in uint i_instance;
+
+// 0 is left for the background, so object ids start at 1
+uniform uint object_id;
+uniform uint submesh_id;
+
+out uvec4 id;
+
+void main() {
+    // every submesh is its own draw call, so the primitive id is the triangle within it
+    id = uvec4(object_id, submesh_id, uint(gl_PrimitiveID), i_instance);
+}
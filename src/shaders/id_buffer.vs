@@ -0,0 +1,16 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 texcoord;
+layout (location = 3) in mat4 instance_matrix;
+
+uniform mat4 world_matrix;
+uniform mat4 view_projection;
+
+flat out uint i_instance;
+
+void main() {
+    i_instance = uint(gl_InstanceID);
+    gl_Position = view_projection * world_matrix * instance_matrix * vec4(position, 1.0);
+}
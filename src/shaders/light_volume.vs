@@ -0,0 +1,10 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+
+uniform mat4 view_projection;
+uniform mat4 volume_matrix;
+
+void main() {
+    gl_Position = view_projection * volume_matrix * vec4(position, 1.0);
+}
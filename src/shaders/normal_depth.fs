@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec3 i_position;
+in vec3 i_normal;
+in vec2 i_texcoord;
+
+out vec4 frag_color;
+
+uniform vec3 camera_pos;
+
+void main() {
+    frag_color = vec4(normalize(i_normal), length(camera_pos - i_position));
+}
@@ -0,0 +1,58 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D normal_depth;
+uniform sampler2D object_depth;
+uniform vec2 viewport_size;
+uniform float outline_width;
+uniform vec3 outline_color;
+uniform float depth_threshold;
+uniform float normal_threshold;
+
+const float BACKGROUND_DEPTH = 1.0e6;
+
+vec4 fetch(vec2 offset) {
+    vec4 value = texture(normal_depth, i_uv + offset / viewport_size);
+    // the buffer is cleared to zero where nothing was drawn
+    if (value.w == 0.0) {
+        return vec4(0.0, 0.0, 0.0, BACKGROUND_DEPTH);
+    }
+    return value;
+}
+
+void main() {
+    float r = max(outline_width * 0.5, 0.5);
+    vec4 center = fetch(vec2(0.0));
+    vec2 offsets[4] = vec2[](vec2(-r, -r), vec2(r, r), vec2(r, -r), vec2(-r, r));
+    vec4 samples[4] = vec4[](
+        fetch(offsets[0]),
+        fetch(offsets[1]),
+        fetch(offsets[2]),
+        fetch(offsets[3])
+    );
+
+    // roberts cross on the depth and normal channels
+    float depth_edge = abs(samples[0].w - samples[1].w) + abs(samples[2].w - samples[3].w);
+    float normal_edge = length(samples[0].xyz - samples[1].xyz) + length(samples[2].xyz - samples[3].xyz);
+
+    float nearest = min(min(samples[0].w, samples[1].w), min(samples[2].w, samples[3].w));
+    nearest = min(nearest, center.w);
+    if (nearest >= BACKGROUND_DEPTH) {
+        discard;
+    }
+    if (depth_edge < depth_threshold * nearest && normal_edge < normal_threshold) {
+        discard;
+    }
+
+    // window depth of the nearest of the same samples, the background's is 1
+    float window_depth = texture(object_depth, i_uv).r;
+    for (int i = 0; i < 4; i++) {
+        window_depth = min(window_depth, texture(object_depth, i_uv + offsets[i] / viewport_size).r);
+    }
+    gl_FragDepth = window_depth;
+
+    frag_color = vec4(outline_color, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 3) in mat4 instance_matrix;
+
+uniform mat4 world_matrix;
+uniform mat4 view_projection;
+uniform vec2 viewport_size;
+uniform float outline_width;
+
+void main() {
+    mat4 mvp = view_projection * world_matrix * instance_matrix;
+    vec4 clip_position = mvp * vec4(position, 1.0);
+    vec3 clip_normal = mat3(mvp) * normal;
+
+    // push the back faces outwards by a constant number of pixels
+    vec2 offset = normalize(clip_normal.xy) / viewport_size * outline_width * 2.0;
+    clip_position.xy += offset * clip_position.w;
+
+    gl_Position = clip_position;
+}
@@ -3,6 +3,7 @@
 in vec3 i_position;
 in vec3 i_normal;
 in vec2 i_texcoord;
+in vec3 i_tint;
 
 out vec4 frag_color;
 
@@ -10,12 +11,16 @@ uniform vec3 camera_pos;
 
 uniform vec3 ka;
 uniform vec3 kd;
+uniform bool has_mapkd;
 uniform sampler2D mapkd;
 uniform vec3 ks;
 uniform float ns;
 
 uniform vec3 ambient_light;
 
+uniform bool ssao_enabled;
+uniform sampler2D ssao_map;
+
 uniform vec3 point_light_pos;
 uniform vec3 point_light_intensity;
 
@@ -28,39 +33,73 @@ uniform float total_width;
 uniform vec3 dir_light_dir;
 uniform vec3 dir_light_radiance;
 
+uniform bool env_enabled;
+uniform sampler2D env_irradiance;
+uniform sampler2D env_prefiltered;
+uniform float env_max_lod;
+uniform float env_rotation;
+uniform float env_intensity;
+
+uniform int toon_bands;
+uniform float toon_specular_threshold;
+
+const float PI = 3.14159265359;
+
+vec3 diffuse_color();
 vec3 diffuse(vec3 i, vec3 n, vec3 light_dir);
-vec3 specular(vec3 i, vec3 n, vec3 camera_dir, vec3 reflect_dir);
+vec3 specular(vec3 i, vec3 n, vec3 camera_dir, vec3 light_dir);
 vec3 point_light(vec3 point_light_pos, vec3 position, vec3 normal, vec3 camera_dir);
 vec3 spot_light(vec3 spot_light_pos, vec3 position, vec3 normal, vec3 camera_dir);
 vec3 dir_light(vec3 dir_light_dir, vec3 normal, vec3 camera_dir);
+vec3 env_light(vec3 normal, vec3 camera_dir);
+float ambient_occlusion();
 
 
+vec3 diffuse_color() {
+    if (has_mapkd) {
+        return texture(mapkd, vec2(1.0 - i_texcoord.y, i_texcoord.x)).rgb * i_tint;
+    }
+    return kd * i_tint;
+}
+
 vec3 diffuse(vec3 i, vec3 n, vec3 light_dir) {
-    return texture(mapkd, vec2(1.0 - i_texcoord.y, i_texcoord.x)).rgb * i * max(dot(n, light_dir), 0.0);
-    // return kd * i * max(dot(n, light_dir), 0.0);
+#ifdef TOON_SHADING
+    float bands = float(max(toon_bands, 1));
+    return diffuse_color() * i * ceil(max(dot(n, light_dir), 0.0) * bands) / bands;
+#else
+    return diffuse_color() * i * max(dot(n, light_dir), 0.0);
+#endif
 }
 
-vec3 specular(vec3 i, vec3 n, vec3 camera_dir, vec3 reflect_dir) {
+vec3 specular(vec3 i, vec3 n, vec3 camera_dir, vec3 light_dir) {
+#if defined(TOON_SHADING)
+    vec3 half_dir = normalize(light_dir + camera_dir);
+    float highlight = pow(max(dot(n, half_dir), 0.0), ns);
+    return ks * i * step(toon_specular_threshold, highlight);
+#elif defined(BLINN_PHONG)
+    vec3 half_dir = normalize(light_dir + camera_dir);
+    return ks * i * pow(max(dot(n, half_dir), 0.0), ns);
+#else
+    vec3 reflect_dir = normalize(reflect(-light_dir, n));
     return ks * i * pow(max(dot(camera_dir, reflect_dir), 0.0), ns);
+#endif
 }
 
 vec3 point_light(vec3 position, vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(point_light_pos - position);
-    vec3 reflect_dir = normalize(reflect(-light_dir, normal));
 
     float distance = length(point_light_pos - position);
     float attenuation = 1.0 / (distance * distance);
     vec3 intensity = point_light_intensity * attenuation;
 
     vec3 diffuse = diffuse(intensity, normal, light_dir);
-    vec3 specular = specular(intensity, normal, camera_dir, reflect_dir);
+    vec3 specular = specular(intensity, normal, camera_dir, light_dir);
 
     return diffuse + specular;
 }
 
 vec3 spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(spot_light_pos - position);
-    vec3 reflect_dir = normalize(reflect(-light_dir, normal));
 
     float cos_theta = dot(light_dir, normalize(-spot_light_dir));
     float epsilon = cos(radians(cutoff_start)) - cos(radians(total_width));
@@ -70,29 +109,67 @@ vec3 spot_light(vec3 position, vec3 normal, vec3 camera_dir) {
     vec3 intensity = spot_light_intensity * clamp((cos_theta - cos(radians(total_width))) / epsilon, 0.0, 1.0)  * attenuation;
 
     vec3 diffuse = diffuse(intensity, normal, light_dir);
-    vec3 specular = specular(intensity, normal, camera_dir, reflect_dir);
+    vec3 specular = specular(intensity, normal, camera_dir, light_dir);
 
     return diffuse + specular;
 }
 
 vec3 dir_light(vec3 normal, vec3 camera_dir) {
     vec3 light_dir = normalize(-dir_light_dir);
-    vec3 reflect_dir = normalize(reflect(-light_dir, normal));
-    
+
     vec3 diffuse = diffuse(dir_light_radiance, normal, light_dir);
-    vec3 specular = specular(dir_light_radiance, normal, camera_dir, reflect_dir);
+    vec3 specular = specular(dir_light_radiance, normal, camera_dir, light_dir);
 
     return diffuse + specular;
 }
 
+vec2 equirect_uv(vec3 dir) {
+    float c = cos(env_rotation);
+    float s = sin(env_rotation);
+    dir = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+vec3 env_light(vec3 normal, vec3 camera_dir) {
+    vec3 reflect_dir = reflect(-camera_dir, normal);
+    // map the phong exponent to the roughness of the prefiltered mip chain
+    float roughness = clamp(sqrt(2.0 / (ns + 2.0)), 0.0, 1.0);
+
+    vec3 irradiance = textureLod(env_irradiance, equirect_uv(normal), 0.0).rgb;
+    vec3 prefiltered = textureLod(env_prefiltered, equirect_uv(reflect_dir), roughness * env_max_lod).rgb;
+
+    vec3 diffuse = diffuse_color() * irradiance;
+    vec3 specular = ks * prefiltered;
+
+    return env_intensity * (diffuse + specular);
+}
+
+float ambient_occlusion() {
+    if (ssao_enabled) {
+        return texelFetch(ssao_map, ivec2(gl_FragCoord.xy), 0).r;
+    }
+    return 1.0;
+}
+
 void main() {
+#ifdef FLAT_SHADING
+    // face normal from the screen-space derivatives, oriented like the vertex normals
+    vec3 normal = normalize(cross(dFdx(i_position), dFdy(i_position)));
+    if (dot(normal, i_normal) < 0.0) {
+        normal = -normal;
+    }
+#else
     vec3 normal = normalize(i_normal);
+#endif
     vec3 camera_dir = normalize(camera_pos - i_position);
-    
-    vec3 i_color = ka * ambient_light;
+
+    vec3 i_color = ka * ambient_light * ambient_occlusion();
     i_color += point_light(i_position, normal, camera_dir);
     i_color += spot_light(i_position, normal, camera_dir);
     i_color += dir_light(normal, camera_dir);
+    if (env_enabled) {
+        i_color += env_light(normal, camera_dir);
+    }
 
     frag_color = vec4(i_color, 1.0);
 }
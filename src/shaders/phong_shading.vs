@@ -3,18 +3,23 @@
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec3 normal;
 layout (location = 2) in vec2 texcoord;
+layout (location = 3) in mat4 instance_matrix;
+layout (location = 7) in mat4 instance_normal_matrix;
+layout (location = 11) in vec3 instance_tint;
 
 uniform mat4 world_matrix;
 uniform mat4 normal_matrix;
-uniform mat4 mvp;
+uniform mat4 view_projection;
 
 out vec3 i_position;
 out vec3 i_normal;
 out vec2 i_texcoord;
+out vec3 i_tint;
 
 void main() {
-    i_position = vec3(world_matrix * vec4(position, 1.0));
-    i_normal = vec3(normal_matrix * vec4(normal ,0.0));
+    i_position = vec3(world_matrix * instance_matrix * vec4(position, 1.0));
+    i_normal = vec3(normal_matrix * instance_normal_matrix * vec4(normal ,0.0));
     i_texcoord = texcoord;
-    gl_Position = mvp * vec4(position, 1.0);
+    i_tint = instance_tint;
+    gl_Position = view_projection * vec4(i_position, 1.0);
 }
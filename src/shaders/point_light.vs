@@ -2,6 +2,8 @@
 
 layout (location = 0) in vec3 position;
 
+uniform mat4 view_projection;
+
 void main() {
-    gl_Position = vec4(position, 1.0);
+    gl_Position = view_projection * vec4(position, 1.0);
 }
@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform sampler2D bloom;
+uniform float intensity;
+
+void main() {
+    frag_color = vec4(texture(source, i_uv).rgb + intensity * texture(bloom, i_uv).rgb, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform float threshold;
+
+void main() {
+    vec3 color = texture(source, i_uv).rgb;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    // keep only the part of the pixel above the threshold, preserving its hue
+    float weight = max(luma - threshold, 0.0) / max(luma, 0.0001);
+    frag_color = vec4(color * weight, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform vec2 direction;
+
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 texel = direction / vec2(textureSize(source, 0));
+
+    vec3 color = texture(source, i_uv).rgb * WEIGHTS[0];
+    for (int i = 1; i < 5; i++) {
+        color += texture(source, i_uv + texel * float(i)).rgb * WEIGHTS[i];
+        color += texture(source, i_uv - texel * float(i)).rgb * WEIGHTS[i];
+    }
+
+    frag_color = vec4(color, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform sampler3D lut;
+uniform float lut_size;
+uniform float intensity;
+
+void main() {
+    vec3 color = clamp(texture(source, i_uv).rgb, 0.0, 1.0);
+    // sample at texel centers so the outermost lut entries map to 0 and 1
+    vec3 lut_coord = color * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+    vec3 graded = texture(lut, lut_coord).rgb;
+
+    frag_color = vec4(mix(color, graded, intensity), 1.0);
+}
@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+
+void main() {
+    frag_color = vec4(texture(source, i_uv).rgb, 1.0);
+}
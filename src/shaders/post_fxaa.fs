@@ -0,0 +1,46 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform float span_max;
+uniform float reduce_mul;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+    vec3 rgb_nw = texture(source, i_uv + vec2(-1.0, -1.0) * texel).rgb;
+    vec3 rgb_ne = texture(source, i_uv + vec2(1.0, -1.0) * texel).rgb;
+    vec3 rgb_sw = texture(source, i_uv + vec2(-1.0, 1.0) * texel).rgb;
+    vec3 rgb_se = texture(source, i_uv + vec2(1.0, 1.0) * texel).rgb;
+    vec3 rgb_m = texture(source, i_uv).rgb;
+
+    float luma_nw = dot(rgb_nw, LUMA);
+    float luma_ne = dot(rgb_ne, LUMA);
+    float luma_sw = dot(rgb_sw, LUMA);
+    float luma_se = dot(rgb_se, LUMA);
+    float luma_m = dot(rgb_m, LUMA);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // blur along the edge, perpendicular to the luma gradient
+    vec2 dir = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * reduce_mul, REDUCE_MIN);
+    float rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2(-span_max), vec2(span_max)) * texel;
+
+    vec3 rgb_a = 0.5 * (
+        texture(source, i_uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source, i_uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+        texture(source, i_uv + dir * -0.5).rgb +
+        texture(source, i_uv + dir * 0.5).rgb);
+
+    float luma_b = dot(rgb_b, LUMA);
+    frag_color = vec4((luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform int tone_operator;
+uniform float exposure;
+uniform float gamma;
+
+vec3 aces(vec3 x) {
+    // Narkowicz's fit of the ACES filmic curve
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(source, i_uv).rgb * exposure;
+
+    if (tone_operator == 0) {
+        color = color / (color + 1.0);
+    } else if (tone_operator == 1) {
+        color = aces(color);
+    } else {
+        color = clamp(color, 0.0, 1.0);
+    }
+
+    frag_color = vec4(pow(color, vec3(1.0 / gamma)), 1.0);
+}
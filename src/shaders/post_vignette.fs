@@ -0,0 +1,19 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform float strength;
+uniform float radius;
+uniform float softness;
+
+void main() {
+    vec2 size = vec2(textureSize(source, 0));
+    // measure the distance in a square space so the falloff stays round
+    vec2 offset = (i_uv - 0.5) * vec2(size.x / size.y, 1.0);
+    float falloff = smoothstep(radius, radius - softness, length(offset));
+
+    frag_color = vec4(texture(source, i_uv).rgb * mix(1.0, falloff, strength), 1.0);
+}
@@ -0,0 +1,11 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 3) in mat4 instance_matrix;
+
+uniform mat4 world_matrix;
+uniform mat4 light_view_projection;
+
+void main() {
+    gl_Position = light_view_projection * world_matrix * instance_matrix * vec4(position, 1.0);
+}
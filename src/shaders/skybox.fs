@@ -0,0 +1,27 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform mat4 inv_view_projection;
+
+uniform sampler2D env_map;
+uniform float env_rotation;
+uniform float env_intensity;
+
+const float PI = 3.14159265359;
+
+vec2 equirect_uv(vec3 dir) {
+    float c = cos(env_rotation);
+    float s = sin(env_rotation);
+    dir = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+    return vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+void main() {
+    vec4 far_point = inv_view_projection * vec4(i_uv * 2.0 - 1.0, 1.0, 1.0);
+    vec3 dir = normalize(far_point.xyz / far_point.w);
+
+    frag_color = vec4(env_intensity * textureLod(env_map, equirect_uv(dir), 0.0).rgb, 1.0);
+}
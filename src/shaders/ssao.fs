@@ -0,0 +1,73 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D position_map;
+uniform sampler2D normal_map;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+uniform int kernel_size;
+uniform float radius;
+uniform float bias;
+uniform float power;
+
+const float PI = 3.14159265359;
+const float GOLDEN_ANGLE = 2.39996323;
+
+// points on a fibonacci spiral over the +z hemisphere, packed closer to the origin for low i
+vec3 kernel_sample(int i) {
+    float t = (float(i) + 0.5) / float(kernel_size);
+    float cos_theta = 1.0 - t;
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    float phi = float(i) * GOLDEN_ANGLE;
+    vec3 dir = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    return dir * mix(0.1, 1.0, t * t);
+}
+
+// interleaved gradient noise, rotates the kernel per pixel so the blur can hide the banding
+float noise(vec2 pixel) {
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+void main() {
+    vec4 position = texture(position_map, i_uv);
+    if (position.w == 0.0) {
+        frag_color = vec4(1.0);
+        return;
+    }
+
+    vec3 view_pos = vec3(view * vec4(position.xyz, 1.0));
+    vec3 normal = normalize(mat3(view) * texture(normal_map, i_uv).xyz);
+
+    vec3 helper = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(helper, normal));
+    vec3 bitangent = cross(normal, tangent);
+    float angle = 2.0 * PI * noise(gl_FragCoord.xy);
+    mat3 tbn = mat3(cos(angle) * tangent + sin(angle) * bitangent,
+                    -sin(angle) * tangent + cos(angle) * bitangent,
+                    normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < kernel_size; i++) {
+        vec3 sample_pos = view_pos + tbn * kernel_sample(i) * radius;
+
+        vec4 clip = projection * vec4(sample_pos, 1.0);
+        vec2 sample_uv = clip.xy / clip.w * 0.5 + 0.5;
+        vec4 occluder = texture(position_map, sample_uv);
+        if (occluder.w == 0.0) {
+            continue;
+        }
+
+        float occluder_depth = (view * vec4(occluder.xyz, 1.0)).z;
+        // fade out occluders far outside the radius so silhouettes don't darken the background
+        float range = smoothstep(0.0, 1.0, radius / abs(view_pos.z - occluder_depth));
+        occlusion += (occluder_depth >= sample_pos.z + bias ? 1.0 : 0.0) * range;
+    }
+
+    float ao = pow(1.0 - occlusion / float(max(kernel_size, 1)), power);
+    frag_color = vec4(ao, ao, ao, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 330 core
+
+in vec2 i_uv;
+
+out vec4 frag_color;
+
+uniform sampler2D source;
+uniform int blur_radius;
+
+void main() {
+    ivec2 size = textureSize(source, 0);
+    ivec2 center = ivec2(gl_FragCoord.xy);
+
+    float ao = 0.0;
+    for (int x = -blur_radius; x <= blur_radius; x++) {
+        for (int y = -blur_radius; y <= blur_radius; y++) {
+            ivec2 texel = clamp(center + ivec2(x, y), ivec2(0), size - 1);
+            ao += texelFetch(source, texel, 0).r;
+        }
+    }
+    float taps = float((2 * blur_radius + 1) * (2 * blur_radius + 1));
+    ao /= taps;
+    frag_color = vec4(ao, ao, ao, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec3 i_position;
+in vec3 i_normal;
+in vec2 i_texcoord;
+
+layout (location = 0) out vec4 ssao_position;
+layout (location = 1) out vec4 ssao_normal;
+
+void main() {
+    // w = 1 marks the pixel as covered by geometry, like the g-buffer
+    ssao_position = vec4(i_position, 1.0);
+    ssao_normal = vec4(normalize(i_normal), 0.0);
+}
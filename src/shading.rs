@@ -0,0 +1,107 @@
+use glium::{Display, Program};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadingMode {
+    Flat,
+    Gouraud,
+    Phong,
+    BlinnPhong,
+    Toon,
+}
+
+impl ShadingMode {
+    pub const ALL: [ShadingMode; 5] = [
+        ShadingMode::Flat,
+        ShadingMode::Gouraud,
+        ShadingMode::Phong,
+        ShadingMode::BlinnPhong,
+        ShadingMode::Toon,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadingMode::Flat => "flat",
+            ShadingMode::Gouraud => "gouraud",
+            ShadingMode::Phong => "phong",
+            ShadingMode::BlinnPhong => "blinn-phong",
+            ShadingMode::Toon => "toon",
+        }
+    }
+}
+
+pub struct ShadingPrograms {
+    flat: Program,
+    gouraud: Program,
+    phong: Program,
+    blinn_phong: Program,
+    toon: Program,
+}
+
+impl ShadingPrograms {
+    pub fn new(display: &Display) -> Self {
+        let phong_vs = include_str!("shaders/phong_shading.vs");
+        let phong_fs = include_str!("shaders/phong_shading.fs");
+
+        let flat = Program::from_source(
+            display,
+            phong_vs,
+            &with_defines(phong_fs, &["FLAT_SHADING"]),
+            None,
+        )
+        .unwrap();
+        let gouraud = Program::from_source(
+            display,
+            include_str!("shaders/gouraud_shading.vs"),
+            include_str!("shaders/gouraud_shading.fs"),
+            None,
+        )
+        .unwrap();
+        let phong = Program::from_source(display, phong_vs, phong_fs, None).unwrap();
+        let blinn_phong = Program::from_source(
+            display,
+            phong_vs,
+            &with_defines(phong_fs, &["BLINN_PHONG"]),
+            None,
+        )
+        .unwrap();
+        let toon = Program::from_source(
+            display,
+            phong_vs,
+            &with_defines(phong_fs, &["TOON_SHADING"]),
+            None,
+        )
+        .unwrap();
+
+        ShadingPrograms {
+            flat,
+            gouraud,
+            phong,
+            blinn_phong,
+            toon,
+        }
+    }
+
+    pub fn get(&self, mode: ShadingMode) -> &Program {
+        match mode {
+            ShadingMode::Flat => &self.flat,
+            ShadingMode::Gouraud => &self.gouraud,
+            ShadingMode::Phong => &self.phong,
+            ShadingMode::BlinnPhong => &self.blinn_phong,
+            ShadingMode::Toon => &self.toon,
+        }
+    }
+}
+
+// inserts `#define`s right after the `#version` line of a shader source
+pub fn with_defines(source: &str, defines: &[&str]) -> String {
+    let (version, body) = source.split_once('\n').unwrap_or((source, ""));
+    let mut result = String::from(version);
+    result.push('\n');
+    for define in defines {
+        result.push_str("#define ");
+        result.push_str(define);
+        result.push('\n');
+    }
+    result.push_str(body);
+    result
+}
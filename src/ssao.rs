@@ -0,0 +1,280 @@
+use glium::{
+    framebuffer::{DepthRenderBuffer, MultiOutputFrameBuffer, SimpleFrameBuffer},
+    index::{NoIndices, PrimitiveType},
+    texture::{DepthFormat, MipmapsOption, UncompressedFloatFormat},
+    uniform,
+    uniforms::{
+        AsUniformValue, MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction,
+        Uniforms, UniformsStorage,
+    },
+    Display, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::{
+    camera::Camera,
+    environment::{fullscreen_triangle, VertexP2},
+    scene_obj::SceneObject,
+};
+
+pub struct SsaoSettings {
+    pub enabled: bool,
+    pub kernel_size: i32,
+    pub radius: f32,
+    pub bias: f32,
+    pub power: f32,
+    pub blur_radius: i32,
+    pub show_buffer: bool,
+}
+
+impl SsaoSettings {
+    pub fn new() -> Self {
+        SsaoSettings {
+            enabled: false,
+            kernel_size: 16,
+            radius: 0.25,
+            bias: 0.025,
+            power: 1.5,
+            blur_radius: 2,
+            show_buffer: false,
+        }
+    }
+}
+
+pub struct Ssao {
+    pub settings: SsaoSettings,
+    geometry_program: Program,
+    ssao_program: Program,
+    blur_program: Program,
+    debug_program: Program,
+    fullscreen: VertexBuffer<VertexP2>,
+    // world-space position and normal of the forward path; the deferred path uses its g-buffer
+    position: Texture2d,
+    normal: Texture2d,
+    depth_buffer: DepthRenderBuffer,
+    raw: Texture2d,
+    blurred: Texture2d,
+}
+
+impl Ssao {
+    pub fn new(display: &Display) -> Self {
+        let fullscreen_vs = include_str!("shaders/fullscreen.vs");
+        let geometry_program = Program::from_source(
+            display,
+            include_str!("shaders/phong_shading.vs"),
+            include_str!("shaders/ssao_geometry.fs"),
+            None,
+        )
+        .unwrap();
+        let ssao_program = Program::from_source(
+            display,
+            fullscreen_vs,
+            include_str!("shaders/ssao.fs"),
+            None,
+        )
+        .unwrap();
+        let blur_program = Program::from_source(
+            display,
+            fullscreen_vs,
+            include_str!("shaders/ssao_blur.fs"),
+            None,
+        )
+        .unwrap();
+        let debug_program = Program::from_source(
+            display,
+            fullscreen_vs,
+            include_str!("shaders/post_copy.fs"),
+            None,
+        )
+        .unwrap();
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (position, normal, depth_buffer, raw, blurred) =
+            Self::create_buffers(display, width, height);
+
+        Ssao {
+            settings: SsaoSettings::new(),
+            geometry_program,
+            ssao_program,
+            blur_program,
+            debug_program,
+            fullscreen: fullscreen_triangle(display),
+            position,
+            normal,
+            depth_buffer,
+            raw,
+            blurred,
+        }
+    }
+
+    fn create_buffers(
+        display: &Display,
+        width: u32,
+        height: u32,
+    ) -> (
+        Texture2d,
+        Texture2d,
+        DepthRenderBuffer,
+        Texture2d,
+        Texture2d,
+    ) {
+        let (width, height) = (width.max(1), height.max(1));
+        let attachment = |format| {
+            Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+                .unwrap()
+        };
+
+        (
+            attachment(UncompressedFloatFormat::F32F32F32F32),
+            attachment(UncompressedFloatFormat::F16F16F16F16),
+            DepthRenderBuffer::new(display, DepthFormat::F32, width, height).unwrap(),
+            attachment(UncompressedFloatFormat::U8U8U8U8),
+            attachment(UncompressedFloatFormat::U8U8U8U8),
+        )
+    }
+
+    pub fn resize(&mut self, display: &Display, width: u32, height: u32) {
+        if self.raw.dimensions() != (width.max(1), height.max(1)) {
+            let (position, normal, depth_buffer, raw, blurred) =
+                Self::create_buffers(display, width, height);
+            self.position = position;
+            self.normal = normal;
+            self.depth_buffer = depth_buffer;
+            self.raw = raw;
+            self.blurred = blurred;
+        }
+    }
+
+    // forward path prepass over `objects`; expects the camera uniforms in `uniforms`
+    pub fn draw_geometry<'a, 'b, I, T, R>(
+        &self,
+        display: &Display,
+        objects: I,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = &'b SceneObject>,
+        T: AsUniformValue + Clone,
+        R: Uniforms + Clone,
+    {
+        let outputs = [
+            ("ssao_position", &self.position),
+            ("ssao_normal", &self.normal),
+        ];
+        let mut target =
+            MultiOutputFrameBuffer::with_depth_buffer(display, outputs, &self.depth_buffer)?;
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+        for scene_obj in objects {
+            scene_obj.draw(
+                &mut target,
+                &self.geometry_program,
+                scene_obj.add_uniforms(uniforms.clone()),
+            )?;
+        }
+        Ok(())
+    }
+
+    // computes the occlusion from the prepass buffers filled by `draw_geometry`
+    pub fn compute_from_prepass(
+        &self,
+        display: &Display,
+        camera: &Camera,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.compute(display, camera, &self.position, &self.normal)
+    }
+
+    // `position` and `normal` hold world-space values, with position.w = 0 where nothing was drawn
+    pub fn compute(
+        &self,
+        display: &Display,
+        camera: &Camera,
+        position: &Texture2d,
+        normal: &Texture2d,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        fn nearest(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+            texture
+                .sampled()
+                .wrap_function(SamplerWrapFunction::Clamp)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+        }
+
+        let mut target = SimpleFrameBuffer::new(display, &self.raw)?;
+        target.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.ssao_program,
+            &camera.add_uniforms(uniform! {
+                position_map: nearest(position),
+                normal_map: nearest(normal),
+                kernel_size: self.settings.kernel_size,
+                radius: self.settings.radius,
+                bias: self.settings.bias,
+                power: self.settings.power,
+            }),
+            &Default::default(),
+        )?;
+
+        let mut target = SimpleFrameBuffer::new(display, &self.blurred)?;
+        target.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.blur_program,
+            &uniform! {
+                source: nearest(&self.raw),
+                blur_radius: self.settings.blur_radius,
+            },
+            &Default::default(),
+        )?;
+        Ok(())
+    }
+
+    pub fn add_uniforms<'a, T, R>(
+        &'a self,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> UniformsStorage<
+        'a,
+        Sampler<'a, Texture2d>,
+        UniformsStorage<'a, bool, UniformsStorage<'a, T, R>>,
+    >
+    where
+        T: AsUniformValue,
+        R: Uniforms,
+    {
+        uniforms.add("ssao_enabled", self.settings.enabled).add(
+            "ssao_map",
+            self.blurred
+                .sampled()
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+        )
+    }
+
+    // shows the unblurred occlusion buffer over the whole frame
+    pub fn draw_debug<S: Surface>(&self, frame: &mut S) -> Result<(), glium::DrawError> {
+        frame.draw(
+            &self.fullscreen,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.debug_program,
+            &uniform! {
+                source: self
+                    .raw
+                    .sampled()
+                    .minify_filter(MinifySamplerFilter::Nearest)
+                    .magnify_filter(MagnifySamplerFilter::Nearest),
+            },
+            &Default::default(),
+        )
+    }
+
+    pub fn build_ui(&mut self, ui: &imgui::Ui) {
+        let settings = &mut self.settings;
+        imgui::Window::new("SSAO").build(ui, || {
+            ui.checkbox("enabled", &mut settings.enabled);
+            imgui::Slider::new("kernel size", 1, 64).build(ui, &mut settings.kernel_size);
+            imgui::Slider::new("radius", 0.01, 2.0).build(ui, &mut settings.radius);
+            imgui::Slider::new("bias", 0.0, 0.1).build(ui, &mut settings.bias);
+            imgui::Slider::new("power", 0.5, 4.0).build(ui, &mut settings.power);
+            imgui::Slider::new("blur radius", 0, 4).build(ui, &mut settings.blur_radius);
+            ui.checkbox("show ao buffer", &mut settings.show_buffer);
+        });
+    }
+}
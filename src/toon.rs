@@ -0,0 +1,229 @@
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    index::{NoIndices, PrimitiveType},
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, UncompressedFloatFormat},
+    uniform,
+    uniforms::{
+        AsUniformValue, MagnifySamplerFilter, MinifySamplerFilter, Uniforms, UniformsStorage,
+    },
+    BackfaceCullingMode, Blend, Display, DrawParameters, Program, Surface, Texture2d, VertexBuffer,
+};
+
+use crate::{
+    environment::{fullscreen_triangle, VertexP2},
+    scene_obj::SceneObject,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutlineMode {
+    None,
+    BackFace,
+    EdgeDetect,
+}
+
+impl OutlineMode {
+    pub const ALL: [OutlineMode; 3] = [
+        OutlineMode::None,
+        OutlineMode::BackFace,
+        OutlineMode::EdgeDetect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutlineMode::None => "none",
+            OutlineMode::BackFace => "back-face extrusion",
+            OutlineMode::EdgeDetect => "edge detection",
+        }
+    }
+}
+
+pub struct ToonSettings {
+    pub bands: i32,
+    pub specular_threshold: f32,
+    pub outline_mode: OutlineMode,
+    pub outline_color: [f32; 3],
+    pub outline_width: f32,
+}
+
+impl ToonSettings {
+    pub fn new() -> Self {
+        ToonSettings {
+            bands: 3,
+            specular_threshold: 0.5,
+            outline_mode: OutlineMode::BackFace,
+            outline_color: [0.0, 0.0, 0.0],
+            outline_width: 2.0,
+        }
+    }
+
+    pub fn add_uniforms<'a, T, R>(
+        &self,
+        uniforms: UniformsStorage<'a, T, R>,
+    ) -> UniformsStorage<'a, f32, UniformsStorage<'a, i32, UniformsStorage<'a, T, R>>>
+    where
+        T: AsUniformValue,
+        R: Uniforms,
+    {
+        uniforms
+            .add("toon_bands", self.bands)
+            .add("toon_specular_threshold", self.specular_threshold)
+    }
+}
+
+pub struct ToonOutline {
+    extrude_program: Program,
+    normal_depth_program: Program,
+    edge_program: Program,
+    fullscreen: VertexBuffer<VertexP2>,
+    normal_depth: Texture2d,
+    // the object's own depth, so its outline can be tested against the scene's
+    depth_buffer: DepthTexture2d,
+}
+
+impl ToonOutline {
+    pub fn new(display: &Display) -> Self {
+        let extrude_program = Program::from_source(
+            display,
+            include_str!("shaders/outline_extrude.vs"),
+            include_str!("shaders/fixed_color.fs"),
+            None,
+        )
+        .unwrap();
+        let normal_depth_program = Program::from_source(
+            display,
+            include_str!("shaders/phong_shading.vs"),
+            include_str!("shaders/normal_depth.fs"),
+            None,
+        )
+        .unwrap();
+        let edge_program = Program::from_source(
+            display,
+            include_str!("shaders/fullscreen.vs"),
+            include_str!("shaders/outline_edge.fs"),
+            None,
+        )
+        .unwrap();
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (normal_depth, depth_buffer) = Self::create_buffers(display, width, height);
+
+        ToonOutline {
+            extrude_program,
+            normal_depth_program,
+            edge_program,
+            fullscreen: fullscreen_triangle(display),
+            normal_depth,
+            depth_buffer,
+        }
+    }
+
+    fn create_buffers(display: &Display, width: u32, height: u32) -> (Texture2d, DepthTexture2d) {
+        let normal_depth = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .unwrap();
+        let depth_buffer = DepthTexture2d::empty_with_format(
+            display,
+            DepthFormat::F32,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .unwrap();
+        (normal_depth, depth_buffer)
+    }
+
+    // expects the object's world/normal matrices and the camera uniforms in `uniforms`
+    pub fn draw<S, T, R>(
+        &mut self,
+        display: &Display,
+        frame: &mut S,
+        scene_obj: &SceneObject,
+        uniforms: UniformsStorage<T, R>,
+        settings: &ToonSettings,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: Surface,
+        T: AsUniformValue + Clone,
+        R: Uniforms + Clone,
+    {
+        let (width, height) = frame.get_dimensions();
+        let viewport_size = [width as f32, height as f32];
+
+        match settings.outline_mode {
+            OutlineMode::None => {}
+            OutlineMode::BackFace => {
+                let uniforms = uniforms
+                    .add("outline_width", settings.outline_width)
+                    .add("fixed_color", settings.outline_color);
+                scene_obj.draw_with_parameters(
+                    frame,
+                    &self.extrude_program,
+                    uniforms,
+                    &DrawParameters {
+                        depth: glium::Depth {
+                            test: glium::DepthTest::IfLess,
+                            write: true,
+                            ..Default::default()
+                        },
+                        backface_culling: BackfaceCullingMode::CullCounterClockwise,
+                        ..Default::default()
+                    },
+                )?;
+            }
+            OutlineMode::EdgeDetect => {
+                if self.normal_depth.dimensions() != (width, height) {
+                    let (normal_depth, depth_buffer) = Self::create_buffers(display, width, height);
+                    self.normal_depth = normal_depth;
+                    self.depth_buffer = depth_buffer;
+                }
+
+                let mut target = SimpleFrameBuffer::with_depth_buffer(
+                    display,
+                    &self.normal_depth,
+                    &self.depth_buffer,
+                )?;
+                target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+                scene_obj.draw(&mut target, &self.normal_depth_program, uniforms)?;
+
+                frame.draw(
+                    &self.fullscreen,
+                    NoIndices(PrimitiveType::TrianglesList),
+                    &self.edge_program,
+                    &uniform! {
+                        normal_depth: self
+                            .normal_depth
+                            .sampled()
+                            .minify_filter(MinifySamplerFilter::Nearest)
+                            .magnify_filter(MagnifySamplerFilter::Nearest),
+                        object_depth: self
+                            .depth_buffer
+                            .sampled()
+                            .minify_filter(MinifySamplerFilter::Nearest)
+                            .magnify_filter(MagnifySamplerFilter::Nearest),
+                        viewport_size: viewport_size,
+                        outline_width: settings.outline_width,
+                        outline_color: settings.outline_color,
+                        depth_threshold: 0.05f32,
+                        normal_threshold: 0.8f32,
+                    },
+                    // the outline sits at the depth of the object's nearest sample, so whatever is
+                    // in front of the object hides it too
+                    &DrawParameters {
+                        depth: glium::Depth {
+                            test: glium::DepthTest::IfLessOrEqual,
+                            write: false,
+                            ..Default::default()
+                        },
+                        blend: Blend::alpha_blending(),
+                        ..Default::default()
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+}